@@ -0,0 +1,260 @@
+// Works out the root note and velocity layer of a sample from its name or file name,
+// e.g. "PNO C3 V2", "BASS_A#1" or "Strings-F#2-mf.wav", and turns a set of them into zones.
+// Notes follow the Akai convention of C3 being middle C (60).
+
+use std::collections::HashMap;
+
+use crate::program_builder::ZoneSpec;
+use crate::sampler_name;
+use crate::stereo;
+
+pub const MAXIMUM_NAME_LENGTH: usize = 12;
+
+const MIDDLE_C: i32 = 60;
+const MIDDLE_C_OCTAVE: i32 = 3;
+const NOTE_LETTERS: [(char, i32); 7] = [('C', 0), ('D', 2), ('E', 4), ('F', 5), ('G', 7), ('A', 9), ('B', 11)];
+const DYNAMICS: [&str; 8] = ["PPP", "PP", "P", "MP", "MF", "F", "FF", "FFF"];
+const AUDIO_FILE_EXTENSIONS: [&str; 4] = [".wav", ".wave", ".aif", ".aiff"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParsedName {
+    pub base: String, // everything that is not the note, the velocity tag or the stereo suffix
+    pub root_note: u8,
+    pub velocity_layer: Option<u32>, // V2 is 2, PPP to FFF are 1 to 8
+    pub velocity_tag: Option<String>,
+    pub side: Option<stereo::Side>,
+}
+
+pub fn parse_name(name: &str) -> Option<ParsedName> {
+    let name = strip_extension(name.trim());
+    let (name, side) = match stereo::split_name(name) {
+        Some((base, side)) => (base, Some(side)),
+        None => (name, None),
+    };
+
+    let tokens = tokenize(name);
+    // the last note wins - "A PIANO C3" is a C
+    let (note_index, root_note) = tokens.iter().enumerate().rev().find_map(|(index, token)| parse_note(token).map(|note| (index, note)))?;
+    let velocity = tokens.iter().enumerate().find_map(|(index, token)| {
+        if index == note_index { None } else { parse_velocity_tag(token).map(|layer| (index, layer)) }
+    });
+
+    let base = tokens.iter().enumerate()
+        .filter(|(index, _)| *index != note_index && Some(*index) != velocity.map(|(velocity_index, _)| velocity_index))
+        .map(|(_, token)| token.to_uppercase())
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    Some(ParsedName {
+        base,
+        root_note,
+        velocity_layer: velocity.map(|(_, layer)| layer),
+        velocity_tag: velocity.map(|(index, _)| tokens[index].to_uppercase()),
+        side,
+    })
+}
+
+fn strip_extension(name: &str) -> &str {
+    let lower_case_name = name.to_lowercase();
+
+    AUDIO_FILE_EXTENSIONS.iter()
+        .find(|extension| lower_case_name.ends_with(*extension))
+        .map(|extension| &name[..(name.len() - extension.len())])
+        .unwrap_or(name)
+}
+
+// splits on spaces, underscores, dots and dashes - except the dash of a negative octave
+fn tokenize(name: &str) -> Vec<String> {
+    let characters: Vec<char> = name.chars().collect();
+    let mut tokens = vec![];
+    let mut token = String::new();
+
+    for (index, character) in characters.iter().enumerate() {
+        let is_negative_octave = *character == '-'
+            && characters.get(index + 1).map(|digit| parse_note(&format!("{}-{}", token, digit)).is_some()).unwrap_or(false);
+
+        if matches!(character, ' ' | '_' | '.' | '-') && !is_negative_octave {
+            if !token.is_empty() {
+                tokens.push(token.clone());
+                token.clear();
+            }
+        }
+        else {
+            token.push(*character);
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+// a letter, an optional accidental (#, + or a lower case b) and an octave from -2 to 8
+pub fn parse_note(token: &str) -> Option<u8> {
+    let mut characters = token.chars().peekable();
+    let letter = characters.next()?.to_ascii_uppercase();
+    let mut note = NOTE_LETTERS.iter().find(|(note_letter, _)| *note_letter == letter)?.1;
+
+    match characters.peek() {
+        Some('#') | Some('+') => {
+            note += 1;
+            characters.next();
+        }
+        Some('b') => {
+            note -= 1;
+            characters.next();
+        }
+        _ => (),
+    }
+
+    let octave: i32 = characters.collect::<String>().parse().ok()?;
+    let note = MIDDLE_C + (octave - MIDDLE_C_OCTAVE) * 12 + note;
+
+    if (0..=127).contains(&note) { Some(note as u8) } else { None }
+}
+
+pub fn note_name(note: u8) -> String {
+    let names = ["C", "C+", "D", "D+", "E", "F", "F+", "G", "G+", "A", "A+", "B"];
+    let octave = (note as i32 - MIDDLE_C).div_euclid(12) + MIDDLE_C_OCTAVE;
+
+    format!("{}{}", names[note as usize % 12], octave)
+}
+
+// V1, VEL3, L2 or a dynamic from PPP to FFF
+fn parse_velocity_tag(token: &str) -> Option<u32> {
+    let token = token.to_uppercase();
+
+    if let Some(position) = DYNAMICS.iter().position(|dynamic| *dynamic == token) {
+        return Some(position as u32 + 1)
+    }
+
+    ["VEL", "V", "L"].iter()
+        .find_map(|prefix| token.strip_prefix(prefix))
+        .filter(|number| !number.is_empty() && number.chars().all(|character| character.is_ascii_digit()))
+        .and_then(|number| number.parse().ok())
+}
+
+// Each note gets one keygroup spanning up to the next note, and its velocity layers share 0-127 evenly
+// from the softest tag upwards. Names that do not parse are returned separately.
+pub fn map_zones(names: &[String]) -> (Vec<ZoneSpec>, Vec<String>) {
+    let mut parsed_names = vec![];
+    let mut unmapped = vec![];
+
+    for name in names.iter() {
+        match parse_name(name) {
+            Some(parsed_name) => parsed_names.push((name.clone(), parsed_name)),
+            None => unmapped.push(name.clone()),
+        }
+    }
+
+    let mut layers: HashMap<u8, Vec<Option<u32>>> = HashMap::new();
+    for (_, parsed_name) in parsed_names.iter() {
+        let note_layers = layers.entry(parsed_name.root_note).or_default();
+        if !note_layers.contains(&parsed_name.velocity_layer) {
+            note_layers.push(parsed_name.velocity_layer);
+        }
+    }
+    for note_layers in layers.values_mut() {
+        note_layers.sort();
+    }
+
+    let zones = parsed_names.iter().map(|(name, parsed_name)| {
+        let note_layers = &layers[&parsed_name.root_note];
+        let layer = note_layers.iter().position(|layer| *layer == parsed_name.velocity_layer).unwrap_or(0);
+
+        ZoneSpec {
+            sample_name: name.clone(),
+            root_note: parsed_name.root_note,
+            key_range: None,
+            velocity_range: velocity_range(layer, note_layers.len()),
+            pan: 0,
+        }
+    }).collect();
+
+    (zones, unmapped)
+}
+
+fn velocity_range(layer: usize, number_of_layers: usize) -> (u8, u8) {
+    let number_of_layers = number_of_layers.max(1);
+    let low = layer * 128 / number_of_layers;
+    let high = (layer + 1) * 128 / number_of_layers - 1;
+
+    (low as u8, high as u8)
+}
+
+// Sampler names for a set of file names - "<base> <note> <velocity tag>" with the base abbreviated
+// then truncated to fit, and a number replacing the end of the base when two names still collide.
+// Names are given out in sorted file name order so the same files always get the same names.
+pub fn sampler_names(file_names: &[String]) -> Vec<String> {
+    let mut order: Vec<usize> = (0..file_names.len()).collect();
+    order.sort_by(|first, second| file_names[*first].cmp(&file_names[*second]));
+
+    let mut names = vec![String::new(); file_names.len()];
+    let mut used: Vec<String> = vec![];
+
+    for index in order {
+        let (base, suffix) = match parse_name(&file_names[index]) {
+            Some(parsed_name) => {
+                let mut suffix = format!(" {}", note_name(parsed_name.root_note));
+                if let Some(velocity_tag) = parsed_name.velocity_tag.as_ref() {
+                    suffix.push(' ');
+                    suffix.push_str(velocity_tag);
+                }
+                if let Some(side) = parsed_name.side {
+                    suffix.push_str(if side == stereo::Side::Left { stereo::LEFT_SUFFIX } else { stereo::RIGHT_SUFFIX });
+                }
+                (parsed_name.base, suffix)
+            }
+            None => (strip_extension(file_names[index].trim()).to_uppercase(), String::new()),
+        };
+
+        let base = to_sampler_characters(&base);
+        let suffix = to_sampler_characters(&suffix);
+        let room = MAXIMUM_NAME_LENGTH.saturating_sub(suffix.chars().count());
+        let short_base = abbreviate(&base, room);
+
+        let mut name = format!("{}{}", short_base, suffix).chars().take(MAXIMUM_NAME_LENGTH).collect::<String>();
+        let mut counter = 1;
+        while used.contains(&name) {
+            let number = counter.to_string();
+            let kept: String = short_base.chars().take(room.saturating_sub(number.len())).collect();
+            name = format!("{}{}{}", kept, number, suffix).chars().take(MAXIMUM_NAME_LENGTH).collect();
+            counter += 1;
+        }
+
+        used.push(name.clone());
+        names[index] = name;
+    }
+
+    names
+}
+
+// sharps are written with '+' as there is no '#' on the sampler, and anything else it cannot show becomes a space
+fn to_sampler_characters(name: &str) -> String {
+    let name = sampler_name::transliterate(name.to_uppercase().replace('#', "+").as_str());
+    let invalid_characters = sampler_name::invalid_characters(name.as_str());
+
+    name.chars().enumerate()
+        .map(|(position, character)| if invalid_characters.iter().any(|(invalid_position, _)| *invalid_position == position) { ' ' } else { character })
+        .collect()
+}
+
+// drops spaces, then vowels after the first letter of each word, then truncates
+fn abbreviate(name: &str, length: usize) -> String {
+    let name = name.trim();
+    if name.chars().count() <= length {
+        return name.to_string()
+    }
+
+    let without_spaces: String = name.split_whitespace().collect();
+    if without_spaces.chars().count() <= length {
+        return without_spaces
+    }
+
+    let without_vowels: String = name.split_whitespace().map(|word| {
+        word.chars().enumerate().filter(|(index, character)| *index == 0 || !"AEIOU".contains(*character)).map(|(_, character)| character).collect::<String>()
+    }).collect();
+
+    without_vowels.chars().take(length).collect()
+}
@@ -0,0 +1,409 @@
+// Typed view of the S3000's cue list - a header (selector 0 of RequestCueList/ResponseCueList) followed
+// by events (selector 1), each firing at an SMPTE time. Both are unnibbled bytes; anything not modelled
+// here is kept so it goes back to the sampler untouched.
+
+use std::fmt;
+
+use crate::midi_file::{MidiMessage, TimedMidiMessage};
+
+pub const HEADER_SELECTOR: u8 = 0;
+pub const EVENT_SELECTOR: u8 = 1;
+
+pub const HEADER_SIZE: usize = 18;
+pub const HEADER_NAME_OFFSET: usize = 3;
+pub const HEADER_FRAME_RATE_OFFSET: usize = 15;
+pub const HEADER_NUMBER_OF_EVENTS_OFFSET: usize = 16; // u16 little endian
+
+pub const EVENT_SIZE: usize = 16;
+pub const MAXIMUM_NUMBER_OF_EVENTS: usize = 200;
+
+const EVENT_TYPE_OFFSET: usize = 0;
+const EVENT_TIME_OFFSET: usize = 1; // hours, minutes, seconds, frames, 1/100ths of a frame
+const EVENT_NUMBER_OFFSET: usize = 6; // u16 little endian
+const EVENT_CHANNEL_OFFSET: usize = 8;
+const EVENT_KEY_OFFSET: usize = 9;
+const EVENT_VELOCITY_OFFSET: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameRate {
+    Fps24,
+    Fps25,
+    Fps30Drop,
+    Fps30,
+}
+
+impl FrameRate {
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(FrameRate::Fps24),
+            1 => Some(FrameRate::Fps25),
+            2 => Some(FrameRate::Fps30Drop),
+            3 => Some(FrameRate::Fps30),
+            _ => None,
+        }
+    }
+
+    pub fn to_byte(self) -> u8 {
+        match self {
+            FrameRate::Fps24 => 0,
+            FrameRate::Fps25 => 1,
+            FrameRate::Fps30Drop => 2,
+            FrameRate::Fps30 => 3,
+        }
+    }
+
+    pub fn frames_per_second(self) -> f64 {
+        match self {
+            FrameRate::Fps24 => 24.0,
+            FrameRate::Fps25 => 25.0,
+            FrameRate::Fps30Drop => 29.97,
+            FrameRate::Fps30 => 30.0,
+        }
+    }
+
+    fn frames_in_a_second(self) -> u8 {
+        match self {
+            FrameRate::Fps24 => 24,
+            FrameRate::Fps25 => 25,
+            FrameRate::Fps30Drop | FrameRate::Fps30 => 30,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CueEventType {
+    PlayTake,
+    StopTake,
+    ProgramChange,
+    NoteOn,
+    NoteOff,
+}
+
+impl CueEventType {
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(CueEventType::PlayTake),
+            2 => Some(CueEventType::StopTake),
+            3 => Some(CueEventType::ProgramChange),
+            4 => Some(CueEventType::NoteOn),
+            5 => Some(CueEventType::NoteOff),
+            _ => None,
+        }
+    }
+
+    pub fn to_byte(self) -> u8 {
+        match self {
+            CueEventType::PlayTake => 1,
+            CueEventType::StopTake => 2,
+            CueEventType::ProgramChange => 3,
+            CueEventType::NoteOn => 4,
+            CueEventType::NoteOff => 5,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CueEventType::PlayTake => "play_take",
+            CueEventType::StopTake => "stop_take",
+            CueEventType::ProgramChange => "program_change",
+            CueEventType::NoteOn => "note_on",
+            CueEventType::NoteOff => "note_off",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [CueEventType::PlayTake, CueEventType::StopTake, CueEventType::ProgramChange, CueEventType::NoteOn, CueEventType::NoteOff]
+            .iter().copied().find(|event_type| event_type.name() == name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct CueTime {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+    pub subframes: u8, // 1/100ths of a frame
+}
+
+impl CueTime {
+    pub fn to_seconds(self, frame_rate: FrameRate) -> f64 {
+        self.hours as f64 * 3600.0 + self.minutes as f64 * 60.0 + self.seconds as f64
+            + (self.frames as f64 + self.subframes as f64 / 100.0) / frame_rate.frames_per_second()
+    }
+
+    // rounded to the nearest subframe
+    pub fn from_seconds(seconds: f64, frame_rate: FrameRate) -> Self {
+        let whole_seconds = seconds.max(0.0).floor();
+        let subframes_into_second = ((seconds.max(0.0) - whole_seconds) * frame_rate.frames_per_second() * 100.0).round() as u64;
+        let frames_per_second = frame_rate.frames_in_a_second() as u64;
+        let (whole_seconds, subframes_into_second) = if subframes_into_second >= frames_per_second * 100 {
+            (whole_seconds as u64 + 1, 0)
+        }
+        else {
+            (whole_seconds as u64, subframes_into_second)
+        };
+
+        Self {
+            hours: (whole_seconds / 3600).min(23) as u8,
+            minutes: (whole_seconds / 60 % 60) as u8,
+            seconds: (whole_seconds % 60) as u8,
+            frames: (subframes_into_second / 100) as u8,
+            subframes: (subframes_into_second % 100) as u8,
+        }
+    }
+
+    fn validate(&self, frame_rate: FrameRate) -> Result<(), CueListError> {
+        if self.hours > 23 || self.minutes > 59 || self.seconds > 59 || self.frames >= frame_rate.frames_in_a_second() || self.subframes > 99 {
+            return Err(CueListError::InvalidTime(*self))
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for CueTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}:{:02}:{:02}.{:02}", self.hours, self.minutes, self.seconds, self.frames, self.subframes)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CueEvent {
+    pub time: CueTime,
+    pub event_type: CueEventType,
+    pub number: u16, // take number for take events, program number for program changes
+    pub midi_channel: u8, // 0 - 15
+    pub key: u8,
+    pub velocity: u8,
+    data: Vec<u8>, // the bytes read, so unmodelled ones are written back as they were
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CueListError {
+    WrongSize(usize),
+    UnknownEventType(u8),
+    UnknownFrameRate(u8),
+    InvalidTime(CueTime),
+    OutOfRange(&'static str, u32, u32), // field, value, maximum
+    NoSuchEvent(usize),
+    Full,
+}
+
+impl fmt::Display for CueListError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CueListError::WrongSize(size) => write!(f, "{} bytes is the wrong size for a cue list header or event", size),
+            CueListError::UnknownEventType(event_type) => write!(f, "{} is not a cue event type", event_type),
+            CueListError::UnknownFrameRate(frame_rate) => write!(f, "{} is not a frame rate", frame_rate),
+            CueListError::InvalidTime(time) => write!(f, "{} is not a valid time", time),
+            CueListError::OutOfRange(field, value, maximum) => write!(f, "{} must be from 0 to {} but was {}", field, maximum, value),
+            CueListError::NoSuchEvent(index) => write!(f, "there is no cue event {}", index),
+            CueListError::Full => write!(f, "a cue list holds at most {} events", MAXIMUM_NUMBER_OF_EVENTS),
+        }
+    }
+}
+
+impl CueEvent {
+    pub fn new(time: CueTime, event_type: CueEventType, number: u16, midi_channel: u8, key: u8, velocity: u8) -> Self {
+        Self { time, event_type, number, midi_channel, key, velocity, data: vec![0; EVENT_SIZE] }
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, CueListError> {
+        if data.len() != EVENT_SIZE {
+            return Err(CueListError::WrongSize(data.len()))
+        }
+        let event_type = CueEventType::from_byte(data[EVENT_TYPE_OFFSET]).ok_or(CueListError::UnknownEventType(data[EVENT_TYPE_OFFSET]))?;
+        let time = &data[EVENT_TIME_OFFSET..(EVENT_TIME_OFFSET + 5)];
+
+        Ok(Self {
+            time: CueTime { hours: time[0], minutes: time[1], seconds: time[2], frames: time[3], subframes: time[4] },
+            event_type,
+            number: data[EVENT_NUMBER_OFFSET] as u16 | ((data[EVENT_NUMBER_OFFSET + 1] as u16) << 8),
+            midi_channel: data[EVENT_CHANNEL_OFFSET],
+            key: data[EVENT_KEY_OFFSET],
+            velocity: data[EVENT_VELOCITY_OFFSET],
+            data: data.to_vec(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = self.data.clone();
+        data[EVENT_TYPE_OFFSET] = self.event_type.to_byte();
+        data[EVENT_TIME_OFFSET..(EVENT_TIME_OFFSET + 5)].copy_from_slice(&[self.time.hours, self.time.minutes, self.time.seconds, self.time.frames, self.time.subframes]);
+        data[EVENT_NUMBER_OFFSET..(EVENT_NUMBER_OFFSET + 2)].copy_from_slice(&self.number.to_le_bytes());
+        data[EVENT_CHANNEL_OFFSET] = self.midi_channel;
+        data[EVENT_KEY_OFFSET] = self.key;
+        data[EVENT_VELOCITY_OFFSET] = self.velocity;
+
+        data
+    }
+
+    pub fn validate(&self, frame_rate: FrameRate) -> Result<(), CueListError> {
+        self.time.validate(frame_rate)?;
+        if self.midi_channel > 15 {
+            return Err(CueListError::OutOfRange("midi_channel", self.midi_channel as u32, 15))
+        }
+        if self.key > 127 {
+            return Err(CueListError::OutOfRange("key", self.key as u32, 127))
+        }
+        if self.velocity > 127 {
+            return Err(CueListError::OutOfRange("velocity", self.velocity as u32, 127))
+        }
+        if self.event_type == CueEventType::ProgramChange && self.number > 127 {
+            return Err(CueListError::OutOfRange("number", self.number as u32, 127))
+        }
+
+        Ok(())
+    }
+}
+
+fn header_name(header: &[u8]) -> String {
+    crate::convert_sampler_sysex_name_to_name(&header[HEADER_NAME_OFFSET..(HEADER_NAME_OFFSET + 12)].to_vec()).trim_end().to_string()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CueList {
+    pub frame_rate: FrameRate,
+    pub events: Vec<CueEvent>,
+    header: Vec<u8>,
+}
+
+impl CueList {
+    // the events are read separately - the header says how many there are
+    pub fn from_header(header: &[u8]) -> Result<Self, CueListError> {
+        if header.len() != HEADER_SIZE {
+            return Err(CueListError::WrongSize(header.len()))
+        }
+        let frame_rate = FrameRate::from_byte(header[HEADER_FRAME_RATE_OFFSET]).ok_or(CueListError::UnknownFrameRate(header[HEADER_FRAME_RATE_OFFSET]))?;
+
+        Ok(Self {
+            frame_rate,
+            events: vec![],
+            header: header.to_vec(),
+        })
+    }
+
+    pub fn name(&self) -> String {
+        header_name(&self.header)
+    }
+
+    pub fn number_of_events_in_header(header: &[u8]) -> usize {
+        (header[HEADER_NUMBER_OF_EVENTS_OFFSET] as usize | ((header[HEADER_NUMBER_OF_EVENTS_OFFSET + 1] as usize) << 8)).min(MAXIMUM_NUMBER_OF_EVENTS)
+    }
+
+    pub fn header_bytes(&self) -> Vec<u8> {
+        let mut header = self.header.clone();
+        header[HEADER_FRAME_RATE_OFFSET] = self.frame_rate.to_byte();
+        header[HEADER_NUMBER_OF_EVENTS_OFFSET..(HEADER_NUMBER_OF_EVENTS_OFFSET + 2)].copy_from_slice(&(self.events.len() as u16).to_le_bytes());
+
+        header
+    }
+
+    // at the end if no index is given
+    pub fn add(&mut self, event: CueEvent, index: Option<usize>) -> Result<usize, CueListError> {
+        if self.events.len() >= MAXIMUM_NUMBER_OF_EVENTS {
+            return Err(CueListError::Full)
+        }
+        event.validate(self.frame_rate)?;
+        let index = index.unwrap_or(self.events.len()).min(self.events.len());
+        self.events.insert(index, event);
+
+        Ok(index)
+    }
+
+    pub fn edit(&mut self, index: usize, event: CueEvent) -> Result<(), CueListError> {
+        event.validate(self.frame_rate)?;
+        let existing = self.events.get_mut(index).ok_or(CueListError::NoSuchEvent(index))?;
+        *existing = CueEvent { data: existing.data.clone(), ..event };
+
+        Ok(())
+    }
+
+    pub fn delete(&mut self, index: usize) -> Result<CueEvent, CueListError> {
+        if index >= self.events.len() {
+            return Err(CueListError::NoSuchEvent(index))
+        }
+
+        Ok(self.events.remove(index))
+    }
+
+    pub fn move_event(&mut self, from: usize, to: usize) -> Result<(), CueListError> {
+        if from >= self.events.len() {
+            return Err(CueListError::NoSuchEvent(from))
+        }
+        if to >= self.events.len() {
+            return Err(CueListError::NoSuchEvent(to))
+        }
+        let event = self.events.remove(from);
+        self.events.insert(to, event);
+
+        Ok(())
+    }
+
+    // the sampler plays events in order, so this puts them in time order keeping ties as they were
+    pub fn sort_by_time(&mut self) {
+        self.events.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+    }
+}
+
+// Note and program change events become the matching MIDI messages. Take events have no MIDI
+// equivalent so they go as markers - "play_take 3 channel 1" - which DAWs show on the timeline.
+impl CueList {
+    pub fn to_midi_messages(&self) -> Vec<TimedMidiMessage> {
+        self.events.iter().map(|event| {
+            let channel = event.midi_channel;
+            let message = match event.event_type {
+                CueEventType::NoteOn => MidiMessage::NoteOn { channel, key: event.key, velocity: event.velocity },
+                CueEventType::NoteOff => MidiMessage::NoteOff { channel, key: event.key, velocity: event.velocity },
+                CueEventType::ProgramChange => MidiMessage::ProgramChange { channel, program: event.number as u8 },
+                CueEventType::PlayTake | CueEventType::StopTake => MidiMessage::Marker(format!("{} {} channel {}", event.event_type.name(), event.number, channel as u16 + 1)),
+            };
+
+            TimedMidiMessage { seconds: event.time.to_seconds(self.frame_rate), message }
+        }).collect()
+    }
+
+    // replaces the events - markers that are not take events are skipped and the number skipped returned
+    pub fn set_events_from_midi(&mut self, messages: &[TimedMidiMessage]) -> Result<usize, CueListError> {
+        let mut events = vec![];
+        let mut skipped = 0;
+
+        for timed in messages.iter() {
+            let time = CueTime::from_seconds(timed.seconds, self.frame_rate);
+            let event = match &timed.message {
+                MidiMessage::NoteOn { channel, key, velocity } => CueEvent::new(time, CueEventType::NoteOn, 0, *channel, *key, *velocity),
+                MidiMessage::NoteOff { channel, key, velocity } => CueEvent::new(time, CueEventType::NoteOff, 0, *channel, *key, *velocity),
+                MidiMessage::ProgramChange { channel, program } => CueEvent::new(time, CueEventType::ProgramChange, *program as u16, *channel, 0, 0),
+                MidiMessage::Marker(text) => match take_event_from_marker(time, text.as_str()) {
+                    Some(event) => event,
+                    None => {
+                        skipped += 1;
+                        continue
+                    }
+                },
+            };
+            event.validate(self.frame_rate)?;
+            events.push(event);
+        }
+
+        if events.len() > MAXIMUM_NUMBER_OF_EVENTS {
+            return Err(CueListError::Full)
+        }
+        self.events = events;
+
+        Ok(skipped)
+    }
+}
+
+fn take_event_from_marker(time: CueTime, text: &str) -> Option<CueEvent> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let event_type = CueEventType::from_name(words.first()?).filter(|event_type| matches!(event_type, CueEventType::PlayTake | CueEventType::StopTake))?;
+    let number = words.get(1)?.parse::<u16>().ok()?;
+    let channel = match (words.get(2), words.get(3)) {
+        (Some(&"channel"), Some(channel)) => channel.parse::<u8>().ok()?.checked_sub(1)?,
+        _ => 0,
+    };
+
+    Some(CueEvent::new(time, event_type, number, channel, 0, 0))
+}
@@ -0,0 +1,258 @@
+// Offline processing of downloaded sample words before they are sent back to the sampler.
+
+use std::f64::consts::FRAC_PI_2;
+
+// Blends the audio leading up to the loop end into the audio leading up to the loop start so that
+// the jump back is seamless. The loop points do not move. Returns the crossfade length actually used,
+// which is limited by the loop length and by the audio available before the loop start.
+pub fn crossfade_loop(samples: &mut [i16], loop_start: usize, loop_end: usize, crossfade_length: usize) -> usize {
+    if loop_start >= loop_end || loop_end > samples.len() {
+        return 0
+    }
+
+    let crossfade_length = crossfade_length.min(loop_start).min(loop_end - loop_start);
+    let fade_start = loop_end - crossfade_length;
+    let source_start = loop_start - crossfade_length;
+
+    for index in 0..crossfade_length {
+        // equal power - the gains always satisfy fade_out^2 + fade_in^2 = 1
+        let position = (index as f64 + 0.5) / crossfade_length as f64;
+        let fade_out = (position * FRAC_PI_2).cos();
+        let fade_in = (position * FRAC_PI_2).sin();
+
+        let mixed = samples[fade_start + index] as f64 * fade_out + samples[source_start + index] as f64 * fade_in;
+        samples[fade_start + index] = to_sample_word(mixed);
+    }
+
+    crossfade_length
+}
+
+pub fn to_sample_word(value: f64) -> i16 {
+    value.round().clamp(i16::MIN as f64, i16::MAX as f64) as i16
+}
+
+pub const SUPPORTED_SAMPLE_RATES: [u32; 4] = [22050, 32000, 44100, 48000];
+
+// the rate audio at another rate is resampled to - one that divides it exactly (96kHz to 48kHz,
+// 88.2kHz to 44.1kHz) or else the nearest
+pub fn nearest_supported_sample_rate(sample_rate: u32) -> u32 {
+    SUPPORTED_SAMPLE_RATES.iter().rev().find(|supported| sample_rate.is_multiple_of(**supported))
+        .or_else(|| SUPPORTED_SAMPLE_RATES.iter().min_by_key(|supported| (**supported as i64 - sample_rate as i64).abs()))
+        .copied()
+        .unwrap_or(44100)
+}
+
+const FULL_SCALE: f64 = 32767.0;
+const RESAMPLER_HALF_WIDTH: usize = 32; // zero crossings of the sinc either side of each output sample
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operation {
+    NormalizePeak(f64), // target level in dBFS
+    NormalizeRms(f64), // target level in dBFS
+    TrimSilence(f64), // threshold in dBFS
+    FadeIn(usize), // length in samples
+    FadeOut(usize), // length in samples
+    Reverse,
+    RemoveDc,
+    Gain(f64), // dB
+    Resample(u32), // new sample rate
+}
+
+impl Operation {
+    pub fn new(name: &str, value: f64) -> Option<Self> {
+        match name {
+            "normalize_peak" => Some(Operation::NormalizePeak(value)),
+            "normalize_rms" => Some(Operation::NormalizeRms(value)),
+            "trim_silence" => Some(Operation::TrimSilence(value)),
+            "fade_in" => Some(Operation::FadeIn(value.max(0.0) as usize)),
+            "fade_out" => Some(Operation::FadeOut(value.max(0.0) as usize)),
+            "reverse" => Some(Operation::Reverse),
+            "remove_dc" => Some(Operation::RemoveDc),
+            "gain" => Some(Operation::Gain(value)),
+            "resample" if SUPPORTED_SAMPLE_RATES.contains(&(value as u32)) => Some(Operation::Resample(value as u32)),
+            _ => None,
+        }
+    }
+}
+
+// the result of a chain of operations and how positions in the original map onto it
+#[derive(Clone, Debug)]
+pub struct ProcessedSample {
+    pub samples: Vec<i16>,
+    pub sample_rate: u32,
+    pub position_scale: f64,
+    pub position_shift: f64,
+    pub reversed: bool,
+}
+
+impl ProcessedSample {
+    // where a position in the original sample ends up - None if it has been trimmed off
+    pub fn map_position(&self, position: u32) -> Option<u32> {
+        let position = position as f64 * self.position_scale + self.position_shift;
+
+        if position < 0.0 || position > self.samples.len() as f64 {
+            return None
+        }
+
+        let position = position.round() as u32;
+        Some(if self.reversed { self.samples.len() as u32 - position } else { position })
+    }
+}
+
+pub fn process(samples: Vec<i16>, sample_rate: u32, operations: &[Operation]) -> ProcessedSample {
+    let mut processed = ProcessedSample {
+        samples,
+        sample_rate,
+        position_scale: 1.0,
+        position_shift: 0.0,
+        reversed: false,
+    };
+
+    for operation in operations.iter() {
+        match operation {
+            Operation::NormalizePeak(level) => normalize_peak(&mut processed.samples, *level),
+            Operation::NormalizeRms(level) => normalize_rms(&mut processed.samples, *level),
+            Operation::TrimSilence(threshold) => {
+                let (start, end) = silence_trim_range(&processed.samples, *threshold);
+
+                // positions are measured on the unreversed audio so a reversed trim comes off the other end
+                let removed = if processed.reversed { processed.samples.len() - end } else { start };
+                processed.position_shift -= removed as f64;
+                processed.samples = processed.samples[start..end].to_vec();
+            }
+            Operation::FadeIn(length) => fade_in(&mut processed.samples, *length),
+            Operation::FadeOut(length) => fade_out(&mut processed.samples, *length),
+            Operation::Reverse => {
+                processed.samples.reverse();
+                processed.reversed = !processed.reversed;
+            }
+            Operation::RemoveDc => remove_dc(&mut processed.samples),
+            Operation::Gain(gain) => apply_gain(&mut processed.samples, db_to_gain(*gain)),
+            Operation::Resample(new_sample_rate) => {
+                let ratio = *new_sample_rate as f64 / processed.sample_rate as f64;
+                processed.samples = resample(&processed.samples, processed.sample_rate, *new_sample_rate);
+                processed.sample_rate = *new_sample_rate;
+                processed.position_scale *= ratio;
+                processed.position_shift *= ratio;
+            }
+        }
+    }
+
+    processed
+}
+
+pub fn db_to_gain(db: f64) -> f64 {
+    10.0_f64.powf(db / 20.0)
+}
+
+pub fn apply_gain(samples: &mut [i16], gain: f64) {
+    for sample in samples.iter_mut() {
+        *sample = to_sample_word(*sample as f64 * gain);
+    }
+}
+
+pub fn normalize_peak(samples: &mut [i16], level: f64) {
+    let peak = samples.iter().map(|sample| (*sample as f64).abs()).fold(0.0, f64::max);
+
+    if peak > 0.0 {
+        apply_gain(samples, db_to_gain(level) * FULL_SCALE / peak);
+    }
+}
+
+pub fn normalize_rms(samples: &mut [i16], level: f64) {
+    if samples.is_empty() {
+        return
+    }
+
+    let sum_of_squares: f64 = samples.iter().map(|sample| (*sample as f64).powi(2)).sum();
+    let rms = (sum_of_squares / samples.len() as f64).sqrt();
+
+    if rms > 0.0 {
+        apply_gain(samples, db_to_gain(level) * FULL_SCALE / rms);
+    }
+}
+
+// the range left once everything quieter than the threshold has been taken off both ends
+pub fn silence_trim_range(samples: &[i16], threshold: f64) -> (usize, usize) {
+    let threshold = db_to_gain(threshold) * FULL_SCALE;
+    let is_sound = |sample: &i16| (*sample as f64).abs() > threshold;
+
+    match samples.iter().position(is_sound) {
+        Some(start) => (start, samples.len() - samples.iter().rev().position(is_sound).unwrap_or(0)),
+        None => (0, 0),
+    }
+}
+
+pub fn fade_in(samples: &mut [i16], length: usize) {
+    let length = length.min(samples.len());
+
+    for (index, sample) in samples.iter_mut().take(length).enumerate() {
+        *sample = to_sample_word(*sample as f64 * index as f64 / length as f64);
+    }
+}
+
+pub fn fade_out(samples: &mut [i16], length: usize) {
+    let length = length.min(samples.len());
+    let fade_start = samples.len() - length;
+
+    for index in 0..length {
+        let sample = &mut samples[fade_start + index];
+        *sample = to_sample_word(*sample as f64 * (length - index - 1) as f64 / length as f64);
+    }
+}
+
+pub fn remove_dc(samples: &mut [i16]) {
+    if samples.is_empty() {
+        return
+    }
+
+    let mean = samples.iter().map(|sample| *sample as f64).sum::<f64>() / samples.len() as f64;
+    for sample in samples.iter_mut() {
+        *sample = to_sample_word(*sample as f64 - mean);
+    }
+}
+
+// windowed sinc interpolation - the cutoff drops to the new Nyquist when going down in rate
+pub fn resample(samples: &[i16], sample_rate: u32, new_sample_rate: u32) -> Vec<i16> {
+    if sample_rate == new_sample_rate || samples.is_empty() {
+        return samples.to_vec()
+    }
+
+    let ratio = new_sample_rate as f64 / sample_rate as f64;
+    let cutoff = ratio.min(1.0);
+    let half_width = RESAMPLER_HALF_WIDTH as f64 / cutoff;
+    let new_length = (samples.len() as f64 * ratio).round() as usize;
+
+    (0..new_length).map(|index| {
+        let centre = index as f64 / ratio;
+        let first = (centre - half_width).ceil().max(0.0) as usize;
+        let last = ((centre + half_width).floor() as usize).min(samples.len() - 1);
+
+        let value: f64 = (first..=last).map(|input| {
+            let distance = input as f64 - centre;
+            samples[input] as f64 * cutoff * sinc(distance * cutoff) * blackman(distance / half_width)
+        }).sum();
+
+        to_sample_word(value)
+    }).collect()
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    }
+    else {
+        let x = x * std::f64::consts::PI;
+        x.sin() / x
+    }
+}
+
+// x runs from -1 to 1 across the window
+fn blackman(x: f64) -> f64 {
+    if x.abs() >= 1.0 {
+        return 0.0
+    }
+
+    let phase = std::f64::consts::PI * (x + 1.0);
+    0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos()
+}
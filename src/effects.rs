@@ -0,0 +1,495 @@
+// Typed view of the 64 byte (unnibbled) effect and reverb parameter blocks of the S3000 effects file.
+// The effect type byte picks the algorithm and so which of the later bytes mean anything.
+
+use std::fmt;
+
+use crate::sampler_name::SamplerName;
+
+pub const EFFECT_BLOCK_SIZE: usize = 64;
+pub const NUMBER_OF_EFFECTS: usize = 50;
+pub const NUMBER_OF_REVERBS: usize = 50;
+pub const NUMBER_OF_ASSIGNMENTS: usize = 128; // one effect and one reverb number per program number
+
+// RequestFXReverb/ResponseFXReverb selectors
+pub const HEADER_SELECTOR: u8 = 0;
+pub const EFFECT_ASSIGNMENTS_SELECTOR: u8 = 1;
+pub const EFFECTS_SELECTOR: u8 = 2;
+pub const REVERB_ASSIGNMENTS_SELECTOR: u8 = 3;
+pub const REVERBS_SELECTOR: u8 = 4;
+pub const HEADER_FILENAME_OFFSET: u16 = 3;
+
+pub type Blocks = Vec<Vec<u8>>; // unnibbled EFFECT_BLOCK_SIZE byte blocks
+
+pub const NAME_OFFSET: usize = 0;
+pub const TYPE_OFFSET: usize = 13;
+pub const OUTPUT_LEVEL_OFFSET: usize = 15;
+pub const OUTPUT_BALANCE_OFFSET: usize = 16;
+pub const STEREO_WIDTH_OFFSET: usize = 17;
+pub const HIGH_FREQUENCY_CUT_OFFSET: usize = 24;
+
+// reverbs use the types below the first effect type
+pub const CHORUS_TYPE: u8 = 6;
+pub const PITCH_SHIFT_TYPE: u8 = 7;
+pub const ECHO_TYPE: u8 = 8;
+pub const DELAY_TYPE: u8 = 9;
+
+const MAXIMUM_LEVEL: f64 = 99.0;
+const MAXIMUM_PAN: f64 = 50.0;
+const MAXIMUM_TUNE: f64 = 50.0; // semitones
+const TUNE_STEPS_PER_SEMITONE: f64 = 256.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EffectCommon {
+    pub output_level: u8,
+    pub output_balance: i8,
+    pub stereo_width: u8,
+    pub high_frequency_cut: u8,
+    name_bytes: Vec<u8>, // kept as read - bytes outside the character map do not survive decoding
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chorus {
+    pub common: EffectCommon,
+    pub modulation_speed: u8,
+    pub modulation_depth: u8,
+    pub feedback_level: u8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Delay {
+    pub common: EffectCommon,
+    pub feedback: u8,
+    pub delay_time: u16,
+    pub lfo_depth: u16,
+    pub lfo_rate: u8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Echo {
+    pub common: EffectCommon,
+    pub delays: [u16; 3],
+    pub feedback_levels: [u8; 3],
+    pub pans: [i8; 3],
+    pub left_extra_delay: u16,
+    pub feedback_damping: u8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PitchShift {
+    pub common: EffectCommon,
+    pub left_tune_offset: i16, // 1/256ths of a semitone
+    pub right_tune_offset: i16,
+    pub left_feedback_level: u8,
+    pub right_feedback_level: u8,
+    pub left_delay_time: u16,
+    pub right_delay_time: u16,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Effect {
+    Chorus(Chorus),
+    Delay(Delay),
+    Echo(Echo),
+    PitchShift(PitchShift),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reverb {
+    pub common: EffectCommon,
+    pub reverb_type: u8,
+    pub pre_delay: u16,
+    pub high_frequency_damping: u8,
+    pub decay_time: u8,
+    pub diffusion: u8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EffectError {
+    WrongSize(usize),
+    UnknownType(u8),
+    UnknownTypeName(String),
+    UnknownParameter(String),
+    OutOfRange(&'static str, f64, f64, f64), // parameter, value, minimum, maximum
+}
+
+impl fmt::Display for EffectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EffectError::WrongSize(size) => write!(f, "an effect block is {} bytes not {}", EFFECT_BLOCK_SIZE, size),
+            EffectError::UnknownType(effect_type) => write!(f, "{} is not an effect type", effect_type),
+            EffectError::UnknownTypeName(effect_type) => write!(f, "{} is not chorus, delay, echo or pitch_shift", effect_type),
+            EffectError::UnknownParameter(parameter) => write!(f, "there is no parameter called {}", parameter),
+            EffectError::OutOfRange(parameter, value, minimum, maximum) => write!(f, "{} must be from {} to {} but was {}", parameter, minimum, maximum, value),
+        }
+    }
+}
+
+impl EffectCommon {
+    fn from_bytes(data: &[u8]) -> Self {
+        Self {
+            output_level: data[OUTPUT_LEVEL_OFFSET],
+            output_balance: data[OUTPUT_BALANCE_OFFSET] as i8,
+            stereo_width: data[STEREO_WIDTH_OFFSET],
+            high_frequency_cut: data[HIGH_FREQUENCY_CUT_OFFSET],
+            name_bytes: data[NAME_OFFSET..(NAME_OFFSET + 12)].to_vec(),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        crate::convert_sampler_sysex_name_to_name(&self.name_bytes).trim_end().to_string()
+    }
+
+    pub fn set_name(&mut self, name: &SamplerName) {
+        self.name_bytes = name.to_sysex();
+    }
+
+    fn write_to(&self, data: &mut [u8]) {
+        data[NAME_OFFSET..(NAME_OFFSET + 12)].copy_from_slice(&self.name_bytes);
+        data[OUTPUT_LEVEL_OFFSET] = self.output_level;
+        data[OUTPUT_BALANCE_OFFSET] = self.output_balance as u8;
+        data[STEREO_WIDTH_OFFSET] = self.stereo_width;
+        data[HIGH_FREQUENCY_CUT_OFFSET] = self.high_frequency_cut;
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("output_level", self.output_level as f64),
+            ("output_balance", self.output_balance as f64),
+            ("stereo_width", self.stereo_width as f64),
+            ("high_frequency_cut", self.high_frequency_cut as f64),
+        ]
+    }
+
+    // false if the parameter is not a common one
+    fn set_parameter(&mut self, parameter: &str, value: f64) -> Result<bool, EffectError> {
+        match parameter {
+            "output_level" => self.output_level = level("output_level", value)?,
+            "output_balance" => self.output_balance = pan("output_balance", value)?,
+            "stereo_width" => self.stereo_width = level("stereo_width", value)?,
+            "high_frequency_cut" => self.high_frequency_cut = level("high_frequency_cut", value)?,
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+}
+
+impl Effect {
+    pub fn from_bytes(data: &[u8]) -> Result<Self, EffectError> {
+        if data.len() != EFFECT_BLOCK_SIZE {
+            return Err(EffectError::WrongSize(data.len()))
+        }
+        let common = EffectCommon::from_bytes(data);
+
+        match data[TYPE_OFFSET] {
+            CHORUS_TYPE => Ok(Effect::Chorus(Chorus {
+                common,
+                modulation_speed: data[36],
+                modulation_depth: data[37],
+                feedback_level: data[38],
+            })),
+            DELAY_TYPE => Ok(Effect::Delay(Delay {
+                common,
+                feedback: data[26],
+                delay_time: read_u16(data, 27),
+                lfo_depth: read_u16(data, 29),
+                lfo_rate: data[31],
+            })),
+            ECHO_TYPE => Ok(Effect::Echo(Echo {
+                common,
+                delays: [read_u16(data, 49), read_u16(data, 51), read_u16(data, 53)],
+                feedback_levels: [data[55], data[56], data[57]],
+                pans: [data[58] as i8, data[59] as i8, data[60] as i8],
+                left_extra_delay: read_u16(data, 61),
+                feedback_damping: data[63],
+            })),
+            PITCH_SHIFT_TYPE => Ok(Effect::PitchShift(PitchShift {
+                common,
+                left_tune_offset: read_u16(data, 39) as i16,
+                right_tune_offset: read_u16(data, 41) as i16,
+                left_feedback_level: data[43],
+                right_feedback_level: data[44],
+                left_delay_time: read_u16(data, 45),
+                right_delay_time: read_u16(data, 47),
+            })),
+            effect_type => Err(EffectError::UnknownType(effect_type)),
+        }
+    }
+
+    // an effect of the given type with the common settings given and every other parameter zero
+    pub fn new(effect_type: &str, common: EffectCommon) -> Result<Self, EffectError> {
+        let mut data = vec![0; EFFECT_BLOCK_SIZE];
+        data[TYPE_OFFSET] = match effect_type {
+            "chorus" => CHORUS_TYPE,
+            "delay" => DELAY_TYPE,
+            "echo" => ECHO_TYPE,
+            "pitch_shift" => PITCH_SHIFT_TYPE,
+            _ => return Err(EffectError::UnknownTypeName(effect_type.to_string())),
+        };
+
+        let mut effect = Self::from_bytes(&data)?;
+        *effect.common_mut() = common;
+
+        Ok(effect)
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Effect::Chorus(_) => "chorus",
+            Effect::Delay(_) => "delay",
+            Effect::Echo(_) => "echo",
+            Effect::PitchShift(_) => "pitch_shift",
+        }
+    }
+
+    pub fn common(&self) -> &EffectCommon {
+        match self {
+            Effect::Chorus(chorus) => &chorus.common,
+            Effect::Delay(delay) => &delay.common,
+            Effect::Echo(echo) => &echo.common,
+            Effect::PitchShift(pitch_shift) => &pitch_shift.common,
+        }
+    }
+
+    pub fn common_mut(&mut self) -> &mut EffectCommon {
+        match self {
+            Effect::Chorus(chorus) => &mut chorus.common,
+            Effect::Delay(delay) => &mut delay.common,
+            Effect::Echo(echo) => &mut echo.common,
+            Effect::PitchShift(pitch_shift) => &mut pitch_shift.common,
+        }
+    }
+
+    // Only the bytes this effect type uses are written, so anything else in the block is kept
+    pub fn write_to(&self, data: &mut [u8]) {
+        self.common().write_to(data);
+
+        match self {
+            Effect::Chorus(chorus) => {
+                data[TYPE_OFFSET] = CHORUS_TYPE;
+                data[36] = chorus.modulation_speed;
+                data[37] = chorus.modulation_depth;
+                data[38] = chorus.feedback_level;
+            }
+            Effect::Delay(delay) => {
+                data[TYPE_OFFSET] = DELAY_TYPE;
+                data[26] = delay.feedback;
+                write_u16(data, 27, delay.delay_time);
+                write_u16(data, 29, delay.lfo_depth);
+                data[31] = delay.lfo_rate;
+            }
+            Effect::Echo(echo) => {
+                data[TYPE_OFFSET] = ECHO_TYPE;
+                for index in 0..3 {
+                    write_u16(data, 49 + index * 2, echo.delays[index]);
+                    data[55 + index] = echo.feedback_levels[index];
+                    data[58 + index] = echo.pans[index] as u8;
+                }
+                write_u16(data, 61, echo.left_extra_delay);
+                data[63] = echo.feedback_damping;
+            }
+            Effect::PitchShift(pitch_shift) => {
+                data[TYPE_OFFSET] = PITCH_SHIFT_TYPE;
+                write_u16(data, 39, pitch_shift.left_tune_offset as u16);
+                write_u16(data, 41, pitch_shift.right_tune_offset as u16);
+                data[43] = pitch_shift.left_feedback_level;
+                data[44] = pitch_shift.right_feedback_level;
+                write_u16(data, 45, pitch_shift.left_delay_time);
+                write_u16(data, 47, pitch_shift.right_delay_time);
+            }
+        }
+    }
+
+    // every numeric parameter by name - tune offsets are in semitones
+    pub fn parameters(&self) -> Vec<(&'static str, f64)> {
+        let mut parameters = self.common().parameters();
+
+        match self {
+            Effect::Chorus(chorus) => parameters.extend(vec![
+                ("modulation_speed", chorus.modulation_speed as f64),
+                ("modulation_depth", chorus.modulation_depth as f64),
+                ("feedback_level", chorus.feedback_level as f64),
+            ]),
+            Effect::Delay(delay) => parameters.extend(vec![
+                ("feedback", delay.feedback as f64),
+                ("delay_time", delay.delay_time as f64),
+                ("lfo_depth", delay.lfo_depth as f64),
+                ("lfo_rate", delay.lfo_rate as f64),
+            ]),
+            Effect::Echo(echo) => parameters.extend(vec![
+                ("delay_1", echo.delays[0] as f64),
+                ("delay_2", echo.delays[1] as f64),
+                ("delay_3", echo.delays[2] as f64),
+                ("feedback_1_level", echo.feedback_levels[0] as f64),
+                ("feedback_2_level", echo.feedback_levels[1] as f64),
+                ("feedback_3_level", echo.feedback_levels[2] as f64),
+                ("pan_1", echo.pans[0] as f64),
+                ("pan_2", echo.pans[1] as f64),
+                ("pan_3", echo.pans[2] as f64),
+                ("left_extra_delay", echo.left_extra_delay as f64),
+                ("feedback_damping", echo.feedback_damping as f64),
+            ]),
+            Effect::PitchShift(pitch_shift) => parameters.extend(vec![
+                ("left_tune_offset", tune_to_semitones(pitch_shift.left_tune_offset)),
+                ("right_tune_offset", tune_to_semitones(pitch_shift.right_tune_offset)),
+                ("left_feedback_level", pitch_shift.left_feedback_level as f64),
+                ("right_feedback_level", pitch_shift.right_feedback_level as f64),
+                ("left_delay_time", pitch_shift.left_delay_time as f64),
+                ("right_delay_time", pitch_shift.right_delay_time as f64),
+            ]),
+        }
+
+        parameters
+    }
+
+    pub fn set_parameter(&mut self, parameter: &str, value: f64) -> Result<(), EffectError> {
+        if self.common_mut().set_parameter(parameter, value)? {
+            return Ok(())
+        }
+
+        match (self, parameter) {
+            (Effect::Chorus(chorus), "modulation_speed") => chorus.modulation_speed = level("modulation_speed", value)?,
+            (Effect::Chorus(chorus), "modulation_depth") => chorus.modulation_depth = level("modulation_depth", value)?,
+            (Effect::Chorus(chorus), "feedback_level") => chorus.feedback_level = level("feedback_level", value)?,
+            (Effect::Delay(delay), "feedback") => delay.feedback = level("feedback", value)?,
+            (Effect::Delay(delay), "delay_time") => delay.delay_time = word("delay_time", value)?,
+            (Effect::Delay(delay), "lfo_depth") => delay.lfo_depth = word("lfo_depth", value)?,
+            (Effect::Delay(delay), "lfo_rate") => delay.lfo_rate = level("lfo_rate", value)?,
+            (Effect::Echo(echo), "delay_1") => echo.delays[0] = word("delay_1", value)?,
+            (Effect::Echo(echo), "delay_2") => echo.delays[1] = word("delay_2", value)?,
+            (Effect::Echo(echo), "delay_3") => echo.delays[2] = word("delay_3", value)?,
+            (Effect::Echo(echo), "feedback_1_level") => echo.feedback_levels[0] = level("feedback_1_level", value)?,
+            (Effect::Echo(echo), "feedback_2_level") => echo.feedback_levels[1] = level("feedback_2_level", value)?,
+            (Effect::Echo(echo), "feedback_3_level") => echo.feedback_levels[2] = level("feedback_3_level", value)?,
+            (Effect::Echo(echo), "pan_1") => echo.pans[0] = pan("pan_1", value)?,
+            (Effect::Echo(echo), "pan_2") => echo.pans[1] = pan("pan_2", value)?,
+            (Effect::Echo(echo), "pan_3") => echo.pans[2] = pan("pan_3", value)?,
+            (Effect::Echo(echo), "left_extra_delay") => echo.left_extra_delay = word("left_extra_delay", value)?,
+            (Effect::Echo(echo), "feedback_damping") => echo.feedback_damping = level("feedback_damping", value)?,
+            (Effect::PitchShift(pitch_shift), "left_tune_offset") => pitch_shift.left_tune_offset = tune("left_tune_offset", value)?,
+            (Effect::PitchShift(pitch_shift), "right_tune_offset") => pitch_shift.right_tune_offset = tune("right_tune_offset", value)?,
+            (Effect::PitchShift(pitch_shift), "left_feedback_level") => pitch_shift.left_feedback_level = level("left_feedback_level", value)?,
+            (Effect::PitchShift(pitch_shift), "right_feedback_level") => pitch_shift.right_feedback_level = level("right_feedback_level", value)?,
+            (Effect::PitchShift(pitch_shift), "left_delay_time") => pitch_shift.left_delay_time = word("left_delay_time", value)?,
+            (Effect::PitchShift(pitch_shift), "right_delay_time") => pitch_shift.right_delay_time = word("right_delay_time", value)?,
+            _ => return Err(EffectError::UnknownParameter(parameter.to_string())),
+        }
+
+        Ok(())
+    }
+}
+
+impl Reverb {
+    pub fn from_bytes(data: &[u8]) -> Result<Self, EffectError> {
+        if data.len() != EFFECT_BLOCK_SIZE {
+            return Err(EffectError::WrongSize(data.len()))
+        }
+        if data[TYPE_OFFSET] >= CHORUS_TYPE {
+            return Err(EffectError::UnknownType(data[TYPE_OFFSET]))
+        }
+
+        Ok(Self {
+            common: EffectCommon::from_bytes(data),
+            reverb_type: data[TYPE_OFFSET],
+            pre_delay: read_u16(data, 21),
+            high_frequency_damping: data[32],
+            decay_time: data[33],
+            diffusion: data[35],
+        })
+    }
+
+    pub fn write_to(&self, data: &mut [u8]) {
+        self.common.write_to(data);
+        data[TYPE_OFFSET] = self.reverb_type;
+        write_u16(data, 21, self.pre_delay);
+        data[32] = self.high_frequency_damping;
+        data[33] = self.decay_time;
+        data[35] = self.diffusion;
+    }
+
+    pub fn parameters(&self) -> Vec<(&'static str, f64)> {
+        let mut parameters = self.common.parameters();
+        parameters.extend(vec![
+            ("reverb_type", self.reverb_type as f64),
+            ("pre_delay", self.pre_delay as f64),
+            ("high_frequency_damping", self.high_frequency_damping as f64),
+            ("decay_time", self.decay_time as f64),
+            ("diffusion", self.diffusion as f64),
+        ]);
+
+        parameters
+    }
+
+    pub fn set_parameter(&mut self, parameter: &str, value: f64) -> Result<(), EffectError> {
+        if self.common.set_parameter(parameter, value)? {
+            return Ok(())
+        }
+
+        match parameter {
+            "reverb_type" => self.reverb_type = in_range("reverb_type", value, 0.0, (CHORUS_TYPE - 1) as f64)? as u8,
+            "pre_delay" => self.pre_delay = word("pre_delay", value)?,
+            "high_frequency_damping" => self.high_frequency_damping = level("high_frequency_damping", value)?,
+            "decay_time" => self.decay_time = level("decay_time", value)?,
+            "diffusion" => self.diffusion = level("diffusion", value)?,
+            _ => return Err(EffectError::UnknownParameter(parameter.to_string())),
+        }
+
+        Ok(())
+    }
+}
+
+fn in_range(parameter: &'static str, value: f64, minimum: f64, maximum: f64) -> Result<f64, EffectError> {
+    if value.is_nan() || value < minimum || value > maximum {
+        return Err(EffectError::OutOfRange(parameter, value, minimum, maximum))
+    }
+
+    Ok(value)
+}
+
+fn level(parameter: &'static str, value: f64) -> Result<u8, EffectError> {
+    Ok(in_range(parameter, value.round(), 0.0, MAXIMUM_LEVEL)? as u8)
+}
+
+fn pan(parameter: &'static str, value: f64) -> Result<i8, EffectError> {
+    Ok(in_range(parameter, value.round(), -MAXIMUM_PAN, MAXIMUM_PAN)? as i8)
+}
+
+fn word(parameter: &'static str, value: f64) -> Result<u16, EffectError> {
+    Ok(in_range(parameter, value.round(), 0.0, u16::MAX as f64)? as u16)
+}
+
+fn tune(parameter: &'static str, semitones: f64) -> Result<i16, EffectError> {
+    Ok((in_range(parameter, semitones, -MAXIMUM_TUNE, MAXIMUM_TUNE)? * TUNE_STEPS_PER_SEMITONE).round() as i16)
+}
+
+// to the nearest cent
+fn tune_to_semitones(tune: i16) -> f64 {
+    (tune as f64 / TUNE_STEPS_PER_SEMITONE * 100.0).round() / 100.0
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    data[offset] as u16 | ((data[offset + 1] as u16) << 8)
+}
+
+fn write_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..(offset + 2)].copy_from_slice(&value.to_le_bytes());
+}
+
+// A bulk read of selector 2 runs on through memory where each effect block is followed by the reverb
+// block of the same number, so NUMBER_OF_EFFECTS + NUMBER_OF_REVERBS blocks from item 0 hold both
+// tables - effects in the even blocks and reverbs in the odd ones.
+pub fn split_interleaved(data: &[u8]) -> Option<(Blocks, Blocks)> {
+    if data.len() != (NUMBER_OF_EFFECTS + NUMBER_OF_REVERBS) * EFFECT_BLOCK_SIZE {
+        return None
+    }
+
+    let blocks: Blocks = data.chunks(EFFECT_BLOCK_SIZE).map(|block| block.to_vec()).collect();
+    let effects = blocks.iter().step_by(2).cloned().collect();
+    let reverbs = blocks.iter().skip(1).step_by(2).cloned().collect();
+
+    Some((effects, reverbs))
+}
+
+pub fn block_name(block: &[u8]) -> String {
+    crate::convert_sampler_sysex_name_to_name(&block[NAME_OFFSET..(NAME_OFFSET + 12)].to_vec())
+}
@@ -0,0 +1,99 @@
+// A portable copy of everything in the S3000's effects file: the header filename, the 50 effect and
+// 50 reverb parameter blocks and the program number to effect and reverb number assignment tables.
+// The blocks are kept as the unnibbled bytes the sampler sends, so parameters not modelled in
+// effects.rs go across machines untouched.
+//
+// Layout: "S3KFX" magic, a version byte, the 12 character filename in ASCII padded with spaces, then
+// the effect blocks, the reverb blocks, the effect assignments and the reverb assignments.
+
+use std::{fs, io};
+
+use crate::effects::{EFFECT_BLOCK_SIZE, NUMBER_OF_ASSIGNMENTS, NUMBER_OF_EFFECTS, NUMBER_OF_REVERBS};
+use crate::sampler_name::SAMPLER_NAME_LENGTH;
+
+const MAGIC: &[u8] = b"S3KFX";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 5 + 1 + SAMPLER_NAME_LENGTH;
+const FILE_SIZE: usize = HEADER_SIZE + (NUMBER_OF_EFFECTS + NUMBER_OF_REVERBS) * EFFECT_BLOCK_SIZE + 2 * NUMBER_OF_ASSIGNMENTS;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FxFile {
+    pub filename: String,
+    pub effects: Vec<Vec<u8>>, // NUMBER_OF_EFFECTS blocks of EFFECT_BLOCK_SIZE bytes
+    pub reverbs: Vec<Vec<u8>>,
+    pub effect_assignments: Vec<u8>, // NUMBER_OF_ASSIGNMENTS effect numbers
+    pub reverb_assignments: Vec<u8>,
+}
+
+impl FxFile {
+    pub fn read(path: &str) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        if data.len() < HEADER_SIZE || &data[0..MAGIC.len()] != MAGIC {
+            return Err(invalid_data("not an effects file"))
+        }
+        if data[MAGIC.len()] != VERSION {
+            return Err(invalid_data(format!("effects file version {} is not supported", data[MAGIC.len()]).as_str()))
+        }
+        if data.len() != FILE_SIZE {
+            return Err(invalid_data(format!("effects file is {} bytes but should be {}", data.len(), FILE_SIZE).as_str()))
+        }
+
+        let filename = String::from_utf8_lossy(&data[(MAGIC.len() + 1)..HEADER_SIZE]).trim_end().to_string();
+        let mut position = HEADER_SIZE;
+        let mut blocks = |count: usize| {
+            let blocks: Vec<Vec<u8>> = data[position..(position + count * EFFECT_BLOCK_SIZE)].chunks(EFFECT_BLOCK_SIZE).map(|block| block.to_vec()).collect();
+            position += count * EFFECT_BLOCK_SIZE;
+            blocks
+        };
+        let effects = blocks(NUMBER_OF_EFFECTS);
+        let reverbs = blocks(NUMBER_OF_REVERBS);
+        let effect_assignments = data[position..(position + NUMBER_OF_ASSIGNMENTS)].to_vec();
+        let reverb_assignments = data[(position + NUMBER_OF_ASSIGNMENTS)..].to_vec();
+
+        Self::new(filename.as_str(), effects, reverbs, effect_assignments, reverb_assignments)
+    }
+
+    // checks every table is the size the sampler expects
+    pub fn new(filename: &str, effects: Vec<Vec<u8>>, reverbs: Vec<Vec<u8>>, effect_assignments: Vec<u8>, reverb_assignments: Vec<u8>) -> io::Result<Self> {
+        if effects.len() != NUMBER_OF_EFFECTS || effects.iter().any(|block| block.len() != EFFECT_BLOCK_SIZE) {
+            return Err(invalid_data(format!("expected {} effect blocks of {} bytes", NUMBER_OF_EFFECTS, EFFECT_BLOCK_SIZE).as_str()))
+        }
+        if reverbs.len() != NUMBER_OF_REVERBS || reverbs.iter().any(|block| block.len() != EFFECT_BLOCK_SIZE) {
+            return Err(invalid_data(format!("expected {} reverb blocks of {} bytes", NUMBER_OF_REVERBS, EFFECT_BLOCK_SIZE).as_str()))
+        }
+        if effect_assignments.len() != NUMBER_OF_ASSIGNMENTS || reverb_assignments.len() != NUMBER_OF_ASSIGNMENTS {
+            return Err(invalid_data(format!("expected {} effect and reverb assignments", NUMBER_OF_ASSIGNMENTS).as_str()))
+        }
+        if effect_assignments.iter().any(|effect| *effect as usize >= NUMBER_OF_EFFECTS) || reverb_assignments.iter().any(|reverb| *reverb as usize >= NUMBER_OF_REVERBS) {
+            return Err(invalid_data("an assignment refers to an effect or reverb that does not exist"))
+        }
+
+        Ok(Self { filename: filename.trim_end().to_string(), effects, reverbs, effect_assignments, reverb_assignments })
+    }
+
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(FILE_SIZE);
+
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        data.extend(format!("{:<width$}", self.filename, width = SAMPLER_NAME_LENGTH).bytes().take(SAMPLER_NAME_LENGTH));
+        for block in self.effects.iter().chain(self.reverbs.iter()) {
+            data.extend_from_slice(block);
+        }
+        data.extend_from_slice(&self.effect_assignments);
+        data.extend_from_slice(&self.reverb_assignments);
+
+        data
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
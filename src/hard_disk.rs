@@ -0,0 +1,48 @@
+// The tree built by crawling every partition and volume of the sampler's hard disk.
+
+use crate::library::{FileKind, LibraryEntry, LibraryLocation, SAMPLER_SOURCE};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileNode {
+    pub name: String,
+    pub kind: FileKind,
+    pub file_type: u8,
+    pub model: u8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VolumeNode {
+    pub volume: u8,
+    pub name: String,
+    pub active: bool,
+    pub volume_type: u8, // 1 for S1000, 3 for S3000
+    pub files: Vec<FileNode>,
+    pub error: Option<String>, // set when the volume could not be selected or read
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartitionNode {
+    pub partition: u8,
+    pub volumes: Vec<VolumeNode>,
+    pub error: Option<String>,
+}
+
+impl VolumeNode {
+    pub fn location(&self, partition: u8) -> LibraryLocation {
+        LibraryLocation {
+            source: SAMPLER_SOURCE.to_string(),
+            partition,
+            volume: self.volume,
+            volume_name: self.name.trim_end().to_string(),
+        }
+    }
+}
+
+pub fn library_entries(location: &LibraryLocation, files: &[FileNode]) -> Vec<LibraryEntry> {
+    files.iter().map(|file| {
+        let mut entry = LibraryEntry::new(location.clone(), file.name.as_str(), file.kind);
+        entry.file_type = Some(file.file_type);
+        entry.model = Some(file.model);
+        entry
+    }).collect()
+}
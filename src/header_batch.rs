@@ -0,0 +1,104 @@
+// Program and keygroup header edits queued while a control is being moved. Each byte is kept by
+// (program, keygroup, offset) so a later edit replaces an earlier one, and once the window after the
+// first queued edit has passed the bytes go out as one write per run of adjacent offsets.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+pub const HEADER_CHANGE_WINDOW: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeaderWrite {
+    pub program: u8,
+    pub keygroup: Option<u8>, // None for the program header
+    pub offset: u8,
+    pub data: Vec<u8>,
+}
+
+pub struct HeaderChangeBatch {
+    window: Duration,
+    first_queued: Option<Instant>,
+    flush_requested: bool,
+    pending: BTreeMap<(u8, Option<u8>), BTreeMap<u16, u8>>, // (program, keygroup) -> offset -> byte
+}
+
+impl HeaderChangeBatch {
+    pub fn new(window: Duration) -> Self {
+        Self { window, first_queued: None, flush_requested: false, pending: BTreeMap::new() }
+    }
+
+    pub fn queue(&mut self, program: u8, keygroup: Option<u8>, offset: u8, data: &[u8], now: Instant) {
+        if data.is_empty() {
+            return
+        }
+
+        let header = self.pending.entry((program, keygroup)).or_default();
+        for (index, byte) in data.iter().enumerate() {
+            header.insert(offset as u16 + index as u16, *byte);
+        }
+        self.first_queued.get_or_insert(now);
+    }
+
+    // a byte queued but not sent yet - what the sampler will hold once the batch goes out
+    pub fn pending_byte(&self, program: u8, keygroup: Option<u8>, offset: u16) -> Option<u8> {
+        self.pending.get(&(program, keygroup)).and_then(|header| header.get(&offset)).copied()
+    }
+
+    // send at the next chance rather than waiting for the window, e.g. when a control is let go
+    pub fn request_flush(&mut self) {
+        self.flush_requested = true;
+    }
+
+    // measured from the first queued edit, so a control that never stops moving still gets sent
+    pub fn is_due(&self, now: Instant) -> bool {
+        match self.first_queued {
+            Some(first_queued) => self.flush_requested || now.duration_since(first_queued) >= self.window,
+            None => false,
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<HeaderWrite> {
+        self.first_queued = None;
+        self.flush_requested = false;
+
+        writes(std::mem::take(&mut self.pending))
+    }
+
+    // just the edits to one program's headers, e.g. before they are read back
+    pub fn take_program(&mut self, program_number: u16) -> Vec<HeaderWrite> {
+        let (taken, kept) = std::mem::take(&mut self.pending).into_iter().partition(|((program, _), _)| *program as u16 == program_number);
+        self.pending = kept;
+        if self.pending.is_empty() {
+            self.first_queued = None;
+            self.flush_requested = false;
+        }
+
+        writes(taken)
+    }
+}
+
+// one write per run of adjacent offsets
+fn writes(pending: BTreeMap<(u8, Option<u8>), BTreeMap<u16, u8>>) -> Vec<HeaderWrite> {
+    let mut writes = vec![];
+
+    for ((program, keygroup), bytes) in pending {
+        let mut current: Option<(u16, Vec<u8>)> = None;
+
+        for (offset, byte) in bytes {
+            match current.as_mut() {
+                Some((start, data)) if *start + data.len() as u16 == offset => data.push(byte),
+                _ => {
+                    if let Some((start, data)) = current.take() {
+                        writes.push(HeaderWrite { program, keygroup, offset: start as u8, data });
+                    }
+                    current = Some((offset, vec![byte]));
+                }
+            }
+        }
+        if let Some((start, data)) = current {
+            writes.push(HeaderWrite { program, keygroup, offset: start as u8, data });
+        }
+    }
+
+    writes
+}
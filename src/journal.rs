@@ -0,0 +1,356 @@
+// Undo/redo history of the changes sent to the sampler this session. Every change keeps the bytes that
+// were there before it was written, so undoing writes them back and redoing writes the new ones again.
+// Sample deletes and uploads are kept as barriers - their sample data is not read back first, so undo
+// stops at them.
+
+use std::{fmt, fs, io};
+
+const FILE_MAGIC: &str = "S3KJOURNAL 1";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JournalTarget {
+    ProgramHeader(u16),
+    KeygroupHeader(u16, u8),
+    SampleHeader(u16),
+    FxReverb(u16, u8), // item number, selector
+    Miscellaneous(u16, u8), // data index, bank number
+    CueList(u16, u8), // entry number, selector
+    TakeList(u16, u8), // entry number, selector
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JournalChange {
+    Bytes { target: JournalTarget, offset: u16, old: Vec<u8>, new: Vec<u8> },
+    S1000Miscellaneous { old: Vec<u8>, new: Vec<u8> }, // in ChangeS1000MiscBytes order
+    NewProgram { program: u16, header: Vec<u8> },
+    DeleteProgram { program: u16, header: Vec<u8>, keygroups: Vec<Vec<u8>> },
+    NewKeygroup { program: u16, keygroup: u8, header: Vec<u8> },
+    NewSample { sample: u16 },
+    DeleteKeygroup { program: u16, keygroup: u8, header: Vec<u8> },
+    Irreversible(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct JournalStep {
+    pub description: String,
+    pub changes: Vec<JournalChange>,
+    coalescing: bool, // a later edit of the same bytes replaces this one's new bytes
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JournalError {
+    NothingToUndo,
+    NothingToRedo,
+    Irreversible(String),
+    UnknownCheckpoint(String),
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JournalError::NothingToUndo => write!(f, "there is nothing to undo"),
+            JournalError::NothingToRedo => write!(f, "there is nothing to redo"),
+            JournalError::Irreversible(description) => write!(f, "{} cannot be undone", description),
+            JournalError::UnknownCheckpoint(name) => write!(f, "there is no checkpoint called {}", name),
+        }
+    }
+}
+
+impl JournalStep {
+    pub fn is_reversible(&self) -> bool {
+        !self.changes.iter().any(|change| matches!(change, JournalChange::Irreversible(_)))
+    }
+}
+
+pub struct Journal {
+    enabled: bool,
+    steps: Vec<JournalStep>,
+    position: usize, // the steps before this are applied, the rest can be redone
+    checkpoints: Vec<(String, usize)>,
+    open_step: Option<JournalStep>,
+    open_depth: usize,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Self { enabled: true, steps: vec![], position: 0, checkpoints: vec![], open_step: None, open_depth: 0 }
+    }
+
+    // reading the old bytes costs a round trip before every write, so it can be switched off
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    // everything recorded until the matching end_step is undone and redone as one step
+    pub fn begin_step(&mut self, description: &str) {
+        if self.open_depth == 0 {
+            self.open_step = Some(JournalStep { description: description.to_string(), changes: vec![], coalescing: false });
+        }
+        self.open_depth += 1;
+    }
+
+    pub fn end_step(&mut self) {
+        self.open_depth = self.open_depth.saturating_sub(1);
+        if self.open_depth == 0 {
+            if let Some(step) = self.open_step.take().filter(|step| !step.changes.is_empty()) {
+                self.push(step);
+            }
+        }
+    }
+
+    pub fn record(&mut self, description: &str, change: JournalChange) {
+        match self.open_step.as_mut() {
+            Some(step) => step.changes.push(change),
+            None => self.push(JournalStep { description: description.to_string(), changes: vec![change], coalescing: false }),
+        }
+    }
+
+    // for edits that arrive many times a second while a control moves - if the last step wrote the
+    // same bytes it takes the new value rather than adding another step
+    pub fn record_coalesced(&mut self, description: &str, change: JournalChange) {
+        if self.open_step.is_none() && !self.checkpoints.iter().any(|(_, position)| *position == self.position) && self.position == self.steps.len() {
+            if let (Some(last), JournalChange::Bytes { target, offset, new, .. }) = (self.steps.last_mut(), &change) {
+                if let (true, [JournalChange::Bytes { target: last_target, offset: last_offset, new: last_new, .. }]) = (last.coalescing, last.changes.as_mut_slice()) {
+                    if last_target == target && last_offset == offset && last_new.len() == new.len() {
+                        *last_new = new.clone();
+                        return
+                    }
+                }
+            }
+        }
+
+        self.record(description, change);
+        if self.open_step.is_none() {
+            if let Some(last) = self.steps.last_mut() {
+                last.coalescing = true;
+            }
+        }
+    }
+
+    fn push(&mut self, step: JournalStep) {
+        // a new change after an undo drops whatever could have been redone
+        self.steps.truncate(self.position);
+        let position = self.position;
+        self.checkpoints.retain(|(_, checkpoint_position)| *checkpoint_position <= position);
+        self.steps.push(step);
+        self.position = self.steps.len();
+    }
+
+    pub fn next_undo(&self) -> Result<&JournalStep, JournalError> {
+        let step = self.position.checked_sub(1).and_then(|index| self.steps.get(index)).ok_or(JournalError::NothingToUndo)?;
+        if !step.is_reversible() {
+            return Err(JournalError::Irreversible(step.description.clone()))
+        }
+
+        Ok(step)
+    }
+
+    pub fn next_redo(&self) -> Result<&JournalStep, JournalError> {
+        self.steps.get(self.position).ok_or(JournalError::NothingToRedo)
+    }
+
+    // called once the step from next_undo or next_redo has been written to the sampler
+    pub fn mark_undone(&mut self) {
+        self.position = self.position.saturating_sub(1);
+        if let Some(step) = self.steps.get_mut(self.position) {
+            step.coalescing = false;
+        }
+    }
+
+    pub fn mark_redone(&mut self) {
+        self.position = (self.position + 1).min(self.steps.len());
+    }
+
+    // naming an existing checkpoint again moves it here
+    pub fn checkpoint(&mut self, name: &str) {
+        self.checkpoints.retain(|(checkpoint_name, _)| checkpoint_name != name);
+        self.checkpoints.push((name.to_string(), self.position));
+    }
+
+    pub fn checkpoint_position(&self, name: &str) -> Result<usize, JournalError> {
+        self.checkpoints.iter().find(|(checkpoint_name, _)| checkpoint_name == name).map(|(_, position)| *position).ok_or_else(|| JournalError::UnknownCheckpoint(name.to_string()))
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn steps(&self) -> &[JournalStep] {
+        &self.steps
+    }
+
+    pub fn checkpoints(&self) -> &[(String, usize)] {
+        &self.checkpoints
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self { enabled: self.enabled, ..Self::new() };
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    // the loaded steps describe the sampler as it was when they were saved
+    pub fn load(&mut self, path: &str) -> io::Result<()> {
+        let loaded = Self::from_text(fs::read_to_string(path)?.as_str())?;
+        *self = Self { enabled: self.enabled, ..loaded };
+
+        Ok(())
+    }
+
+    // one line per step, change or checkpoint - names and descriptions are the rest of their line
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\nposition {}\n", FILE_MAGIC, self.position);
+
+        for (name, position) in self.checkpoints.iter() {
+            text.push_str(format!("checkpoint {} {}\n", position, single_line(name)).as_str());
+        }
+        for step in self.steps.iter() {
+            text.push_str(format!("step {}\n", single_line(step.description.as_str())).as_str());
+            for change in step.changes.iter() {
+                text.push_str(change_line(change).as_str());
+                text.push('\n');
+            }
+        }
+
+        text
+    }
+
+    pub fn from_text(text: &str) -> io::Result<Self> {
+        let mut lines = text.lines();
+        if lines.next() != Some(FILE_MAGIC) {
+            return Err(invalid_data("not a sampler journal"))
+        }
+
+        let mut journal = Self::new();
+        let mut position = 0;
+        for line in lines.filter(|line| !line.is_empty()) {
+            let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+
+            match keyword {
+                "position" => position = parse_number(rest)?,
+                "checkpoint" => {
+                    let (checkpoint_position, name) = rest.split_once(' ').unwrap_or((rest, ""));
+                    journal.checkpoints.push((name.to_string(), parse_number(checkpoint_position)?));
+                }
+                "step" => journal.steps.push(JournalStep { description: rest.to_string(), changes: vec![], coalescing: false }),
+                _ => {
+                    let change = parse_change(keyword, rest)?;
+                    journal.steps.last_mut().ok_or_else(|| invalid_data("a change comes before the first step"))?.changes.push(change);
+                }
+            }
+        }
+
+        if position > journal.steps.len() || journal.checkpoints.iter().any(|(_, checkpoint_position)| *checkpoint_position > journal.steps.len()) {
+            return Err(invalid_data("a position is past the last step"))
+        }
+        journal.position = position;
+
+        Ok(journal)
+    }
+}
+
+fn change_line(change: &JournalChange) -> String {
+    match change {
+        JournalChange::Bytes { target, offset, old, new } => {
+            let (kind, first, second) = match target {
+                JournalTarget::ProgramHeader(program) => ("program_header", *program, 0),
+                JournalTarget::KeygroupHeader(program, keygroup) => ("keygroup_header", *program, *keygroup),
+                JournalTarget::SampleHeader(sample) => ("sample_header", *sample, 0),
+                JournalTarget::FxReverb(item, selector) => ("fx_reverb", *item, *selector),
+                JournalTarget::Miscellaneous(index, bank) => ("miscellaneous", *index, *bank),
+                JournalTarget::CueList(entry, selector) => ("cue_list", *entry, *selector),
+                JournalTarget::TakeList(entry, selector) => ("take_list", *entry, *selector),
+            };
+            format!("bytes {} {} {} {} {} {}", kind, first, second, offset, to_hex(old), to_hex(new))
+        }
+        JournalChange::S1000Miscellaneous { old, new } => format!("s1000_miscellaneous {} {}", to_hex(old), to_hex(new)),
+        JournalChange::NewProgram { program, header } => format!("new_program {} {}", program, to_hex(header)),
+        JournalChange::DeleteProgram { program, header, keygroups } => {
+            let keygroups: Vec<String> = keygroups.iter().map(|keygroup| to_hex(keygroup)).collect();
+            format!("delete_program {} {} {}", program, to_hex(header), if keygroups.is_empty() { "-".to_string() } else { keygroups.join(",") })
+        }
+        JournalChange::NewKeygroup { program, keygroup, header } => format!("new_keygroup {} {} {}", program, keygroup, to_hex(header)),
+        JournalChange::DeleteKeygroup { program, keygroup, header } => format!("delete_keygroup {} {} {}", program, keygroup, to_hex(header)),
+        JournalChange::NewSample { sample } => format!("new_sample {}", sample),
+        JournalChange::Irreversible(description) => format!("irreversible {}", single_line(description)),
+    }
+}
+
+fn parse_change(keyword: &str, rest: &str) -> io::Result<JournalChange> {
+    if keyword == "irreversible" {
+        return Ok(JournalChange::Irreversible(rest.to_string()))
+    }
+
+    let fields: Vec<&str> = rest.split(' ').collect();
+    let field = |index: usize| fields.get(index).copied().ok_or_else(|| invalid_data("a change is missing a field"));
+
+    match keyword {
+        "bytes" => {
+            let (first, second) = (parse_number(field(1)?)?, parse_number(field(2)?)?);
+            let target = match field(0)? {
+                "program_header" => JournalTarget::ProgramHeader(first),
+                "keygroup_header" => JournalTarget::KeygroupHeader(first, second),
+                "sample_header" => JournalTarget::SampleHeader(first),
+                "fx_reverb" => JournalTarget::FxReverb(first, second),
+                "miscellaneous" => JournalTarget::Miscellaneous(first, second),
+                "cue_list" => JournalTarget::CueList(first, second),
+                "take_list" => JournalTarget::TakeList(first, second),
+                _ => return Err(invalid_data("unknown change target")),
+            };
+            Ok(JournalChange::Bytes { target, offset: parse_number(field(3)?)?, old: from_hex(field(4)?)?, new: from_hex(field(5)?)? })
+        }
+        "s1000_miscellaneous" => Ok(JournalChange::S1000Miscellaneous { old: from_hex(field(0)?)?, new: from_hex(field(1)?)? }),
+        "new_program" => Ok(JournalChange::NewProgram { program: parse_number(field(0)?)?, header: from_hex(field(1)?)? }),
+        "delete_program" => {
+            let keygroups = match field(2)? {
+                "-" => vec![],
+                keygroups => keygroups.split(',').map(from_hex).collect::<io::Result<Vec<Vec<u8>>>>()?,
+            };
+            Ok(JournalChange::DeleteProgram { program: parse_number(field(0)?)?, header: from_hex(field(1)?)?, keygroups })
+        }
+        "new_keygroup" => Ok(JournalChange::NewKeygroup { program: parse_number(field(0)?)?, keygroup: parse_number(field(1)?)?, header: from_hex(field(2)?)? }),
+        "delete_keygroup" => Ok(JournalChange::DeleteKeygroup { program: parse_number(field(0)?)?, keygroup: parse_number(field(1)?)?, header: from_hex(field(2)?)? }),
+        "new_sample" => Ok(JournalChange::NewSample { sample: parse_number(field(0)?)? }),
+        _ => Err(invalid_data("unknown change")),
+    }
+}
+
+fn single_line(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+// empty byte strings are written as "-" so the fields stay space separated
+fn to_hex(data: &[u8]) -> String {
+    if data.is_empty() {
+        return "-".to_string()
+    }
+
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> io::Result<Vec<u8>> {
+    if text == "-" {
+        return Ok(vec![])
+    }
+    if !text.len().is_multiple_of(2) {
+        return Err(invalid_data("odd number of hex digits"))
+    }
+
+    // by byte rather than by str slice, which would panic inside a multibyte character
+    let digit = |byte: u8| (byte as char).to_digit(16).ok_or_else(|| invalid_data("bad hex digit"));
+    text.as_bytes().chunks(2).map(|pair| Ok(((digit(pair[0])? << 4) | digit(pair[1])?) as u8)).collect()
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str) -> io::Result<T> {
+    text.parse().map_err(|_| invalid_data("bad number"))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,15 @@
+// Offsets into the 192 byte (unnibbled) keygroup header used when building keygroups in Rust.
+
+pub const NUMBER_OF_ZONES: usize = 4;
+
+pub const ZONE_SAMPLE_NAME_OFFSETS: [usize; NUMBER_OF_ZONES] = [34, 58, 82, 106];
+pub const ZONE_SEMITONE_TUNE_OFFSETS: [usize; NUMBER_OF_ZONES] = [49, 73, 97, 121];
+pub const ZONE_PAN_OFFSETS: [usize; NUMBER_OF_ZONES] = [52, 76, 100, 124];
+
+pub const PAN_LEFT: i8 = -50;
+pub const PAN_RIGHT: i8 = 50;
+
+// -50 to +50 values are sent as a two's complement byte
+pub fn encode_plus_or_minus_fifty(value: i8) -> u8 {
+    value.clamp(-50, 50) as u8
+}
@@ -1,6 +1,5 @@
 use std::{cmp::Ordering, collections::{HashMap, VecDeque}, fmt::Debug, ops::Index, sync::{Arc, Mutex}, time::Duration};
 
-use fundsp::{hacker32::{square_hz, triangle_hz, sine_hz, pulse, saw_hz, U1}, prelude::{An, Pipe, Constant, Sine, PulseWave}, wavetable::WaveSynth};
 use itertools::Itertools;
 use midir::{MidiInput, MidiOutput, MidiOutputConnection, MidiInputConnection, MidiInputPort, MidiOutputPort};
 use neon::prelude::*;
@@ -11,6 +10,35 @@ use log::*;
 #[macro_use]
 extern crate lazy_static;
 
+mod auto_map;
+mod cue_list;
+mod dsp;
+mod effects;
+mod fx_file;
+mod hard_disk;
+mod header_batch;
+mod journal;
+mod keygroup;
+mod library;
+mod loop_finder;
+mod midi_file;
+mod misc_data;
+mod program_builder;
+mod sample_dependencies;
+mod sample_dump;
+mod sample_header;
+mod sample_template;
+mod sampler_name;
+mod stereo;
+mod take_list;
+mod wav;
+mod waveform;
+
+use sample_header::{SampleHeader, SampleLoop};
+use sample_template::{Envelope, SampleTemplate, Waveform};
+use sampler_name::SamplerName;
+use waveform::WaveformOverview;
+
 const SAMPLER_CHAR_MAP: [char; 41] = [ 
     '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 
     ' ', 
@@ -26,11 +54,13 @@ const SAMPLER_IDENTITY: u8 = 0x48;
 const SYSEX_NON_REAL_TIME_CATEGORY: u8 = 0x7E;
 const SAMPLE_DUMP_STANDARD_DATA_PACKET: u8 = 0x02;
 const SAMPLE_DUMP_STANDARD_DATA_ACK: u8 = 0x7F;
+const SAMPLE_DUMP_STANDARD_CANCEL: u8 = 0x7D;
 
 const NUMBER_OF_MIDI_EVENTS_TO_READ: usize = 100000;
 
 const RECEIVE_TIMEOUT: Duration = Duration::from_secs(2);
 const LOAD_SAVE_ENTIRE_VOLUME_RECEIVE_TIMEOUT: Duration = Duration::from_secs(60);
+const SAMPLE_DUMP_PACKET_RECEIVE_TIMEOUT: Duration = Duration::from_secs(10); // maximum wait between sample dump packets
 
 const AKAI_HEADER_SIZE_IN_BYTES: u16 = 192;
 const U16_LSB_TO_AKAI_U8_MASK: u16 = 127;
@@ -41,53 +71,10 @@ const NAME_ENTRY_SIZE_IN_BYTES: u16 = 12;
 const CUE_LIST_SIZE_IN_BYTES: u16 = 128;
 const TAKE_LIST_SIZE_IN_BYTES: u16 = 128;
 const VOLUME_LIST_ENTRY_SIZE_IN_BYTES: u16 = 16;
+const DIRECTORY_ENTRY_SIZE_IN_BYTES: usize = 24;
 
 const MISCELLANEOUS_BYTES_SIZES: [u16; 8] = [1, 2, 4, 5, 6, 12, 16, 8];
 
-struct Oscillator {
-    square: An<Pipe<f32, Constant<U1, f32>, WaveSynth<'static, f32, U1>>>,
-    triangle: An<Pipe<f32, Constant<U1, f32>, WaveSynth<'static, f32, U1>>>,
-    saw: An<Pipe<f32, Constant<U1, f32>, WaveSynth<'static, f32, U1>>>,
-    sine: An<Pipe<f32, Constant<U1, f32>, Sine<f32>>>,
-    pulse: An<PulseWave<f32>>,
-    template: String,
-}
-
-impl Oscillator {
-    fn new(frequency: f32, template: String) -> Self {
-        let mut pulse = pulse();
-
-        // need to set the frequency of the pulse
-
-        Self {
-            square: square_hz(frequency),
-            triangle: triangle_hz(frequency),
-            saw: saw_hz(frequency),
-            sine: sine_hz(frequency),
-            pulse,
-            template,
-        }
-    }
-
-    fn next_sample(&mut self) -> i16 {
-        if self.template == "square" {
-            (self.square.get_mono() * 65534.0 - 32767.0) as i16
-        }
-        else if self.template == "triangle" {
-            (self.triangle.get_mono() * 65534.0 - 32767.0) as i16
-        }
-        else if self.template == "saw" {
-            (self.saw.get_mono() * 65534.0 - 32767.0) as i16
-        }
-        else if self.template == "pulse" {
-            (self.pulse.get_mono() * 65534.0 - 32767.0) as i16
-        }
-        else {
-            (self.sine.get_mono() * 65534.0 - 32767.0) as i16
-        }
-    }
-}
-
 enum S1000SysexFunctionCodes {
     RSTAT,	// <	request S1000 status
     STAT,	// >	S1000 status report
@@ -142,13 +129,16 @@ enum IncomingSamplerEvent {
     RequestProgramHeaderBytes(u16, u16, u16),
     RequestKeygroupHeader(u16, u8),
     RequestSampleHeader(u16),
-    RequestSampleData(u16, u32),
+    RequestSampleData(u16, u32, u32), // sample number, offset of the first sample word, number of sample words
+    CancelSampleData,
     RequestFXReverb(u16, u8, u16, u16), // item number, selector (0 = effects file header, 1 = prog num/effect num assignment table, 2 = effect parameters, 3 = prog num/reverb num, 4 = reverb parameters), number of bytes to get, byte offset
     ResponseFXReverb(u16, u8, u16, Vec<u8>), // item number, selector, offset, data
     RequestCueList(u16, u8, u16, u16), // entry number or 0 for header, selector: 0 - header or 1 - cue event, offset into header, number of bytes of data
+    ResponseCueList(u16, u8, u16, Vec<u8>), // entry number or 0 for header, selector, offset, data
     RequestTakeList(u16, u8, u16, u16), // entry number or 0 for header, selector: 0 - header or 1 - tak list, offset into header, number of bytes of data
+    ResponseTakeList(u16, u8, u16, Vec<u8>), // entry number or 0 for header, selector, offset, data
     RequestMiscellaneousBytes(u16, u8),
-    ResponseMiscellaneousBytes(u16, u8, u32, Option<Vec<u8>>), // data index, bank number, changed value - bank number determines how many bytes it will be mapped to, name data if applicable
+    ResponseMiscellaneousBytes(u16, u8, Vec<u8>), // data index, bank number, value bytes from misc_data::encode
     SelectFloppy,
     SelectHardDrive,
     HardDriveNumberOfPartitions,
@@ -172,15 +162,51 @@ enum IncomingSamplerEvent {
     DeleteSample(u16),
     NewProgram(u16, Vec<u8>),
     NewKeygroup(u16, u8, Vec<u8>),
-    NewSampleFromTemplate(u16, String, Vec<u8>),
+    NewSampleFromTemplate(u16, Vec<u8>, Vec<i16>), // sample number, sample header, rendered sample words
+    UploadSample(u16, Vec<u8>, Vec<i16>), // sample number, sample header, sample words
     NewSample(u16),
     RequestS1000MiscellaneousData,
+    FlushHeaderChanges, // every queued header edit goes before whatever is sent next
     ChangeProgramHeader(u8, u8, Vec<u8>), // program_number, offset into header, vector of changed byte data
     ChangeKeyGroupHeader(u8, u8, u8, Vec<u8>), // program_number, keygroup number, offset into header, vector of changed byte data
     ChangeSampleHeader(u8, u8, Vec<u8>), // sample_number, offset into header, vector of changed byte data
     ChangeS1000MiscBytes(u8, u8, u8, u8, u8, u8), // basic_midi_channel, selected_program_number, midi_play_commands_omni_override, midi_exlusive_channel, basic_channel_omni, midi_program_select_enable
 }
 
+impl IncomingSamplerEvent {
+    // the program whose headers are read, replaced or written directly - any queued edits to them go first
+    fn header_program(&self) -> Option<u16> {
+        match self {
+            IncomingSamplerEvent::RequestProgramHeader(program_number)
+            | IncomingSamplerEvent::RequestProgramHeaderBytes(program_number, _, _)
+            | IncomingSamplerEvent::RequestKeygroupHeader(program_number, _)
+            | IncomingSamplerEvent::DeleteKeygroup(program_number, _)
+            | IncomingSamplerEvent::NewProgram(program_number, _)
+            | IncomingSamplerEvent::NewKeygroup(program_number, _, _) => Some(*program_number),
+            IncomingSamplerEvent::ChangeProgramHeader(program_number, _, _)
+            | IncomingSamplerEvent::ChangeKeyGroupHeader(program_number, _, _, _) => Some(*program_number as u16),
+            _ => None,
+        }
+    }
+
+    // deleting a program renumbers the ones above it and loading replaces them all, so every queued
+    // edit has to go first - as it does before a save so the volume holds what the editor shows
+    fn needs_all_header_changes(&self) -> bool {
+        match self {
+            IncomingSamplerEvent::DeleteProgram(_)
+            | IncomingSamplerEvent::ClearMemoryAndLoadFromSelectedVolume(_)
+            | IncomingSamplerEvent::LoadFromSelectedVolume(_)
+            | IncomingSamplerEvent::ClearVolumeAndSaveMemoryToSelectedVolume(_)
+            | IncomingSamplerEvent::SaveMemoryToSelectedVolume(_)
+            | IncomingSamplerEvent::FlushHeaderChanges => true,
+            IncomingSamplerEvent::ResponseMiscellaneousBytes(data_index, data_bank_number, _) => {
+                matches!(misc_data::find_at(*data_bank_number, *data_index).map(|entry| entry.name), Some("LODVOL") | Some("CLRLOD"))
+            }
+            _ => false,
+        }
+    }
+}
+
 #[derive(Clone)]
 enum OutgoingSamplerEvent {
     ProgramHeader(Vec<u8>),
@@ -192,16 +218,17 @@ enum OutgoingSamplerEvent {
     CueListName(String),
     TakeList(Vec<u8>),
     TakeListName(String),
-    MiscellaneousBytes(u16, Option<String>),
+    MiscellaneousBytes(misc_data::MiscValue),
     VolumeList(String, Option<String>, bool, u8), // name, error msg?, active, volume type - 1 for S1000, 3 for S3000 
     HardDiskDirEntry(String, Option<String>),
-    HardDiskDirEntries(Vec<DirectoryEntry>, Option<String>),
+    HardDiskDirEntries(Vec<DirectoryEntry>, bool, Option<String>), // entries, end of directory reached, error
     ResidentProgramNames(Vec<String>, Option<String>),
     ResidentSampleNames(Vec<String>, Option<String>),
     StatusReport(HashMap<String, i32>, Option<String>),
     S1000MiscellaneousData(HashMap<String, i32>, Option<String>),
     S1000CommandReply(bool),
     SampleData(Vec<u16>),
+    SampleDataProgress(i32, i32), // packets received, packets expected
     HardDriveNumberOfPartitions(u8),
     HardDriveSelectedPartition(u8),
     HardDrivePartitionNumberOfVolumes(u8),
@@ -251,6 +278,16 @@ struct OutgoingCommChannels {
     rx: Receiver<OutgoingEvent>,
 }
 
+// filled straight from the JS thread so edits coalesce without waiting for the event loop
+lazy_static! {
+    static ref HEADER_CHANGES: Mutex<header_batch::HeaderChangeBatch> = Mutex::new(header_batch::HeaderChangeBatch::new(header_batch::HEADER_CHANGE_WINDOW));
+}
+
+// every change sent this session, with the bytes it replaced
+lazy_static! {
+    static ref JOURNAL: Mutex<journal::Journal> = Mutex::new(journal::Journal::new());
+}
+
 lazy_static! {
     static ref OUT_GOING_COMM_CHANNELS: OutgoingCommChannels = {
         let (tx, rx) = unbounded::<OutgoingEvent>();
@@ -732,18 +769,82 @@ impl SampleSysexMessageHandler for SampleSysexMiscellaneousBytesMessageHandler {
     }
 
     fn handle(&self, message: &Vec<u8>, sender: &Sender<OutgoingEvent>) {
-        let data_index_data = &message[5..7];
-        let data_index = data_index_data[0] | (data_index_data[1] << 4);
+        let data_index = message[5] as u16 | ((message[6] as u16) << 7);
         let data_bank_number = message[7];
-        let number_of_bytes_of_data = message[10] | (message[11] << 4);
+        let number_of_bytes_of_data = message[10] as usize | ((message[11] as usize) << 7);
 
         info!("message length={}, data_index={}, data bank number={}", message.len(), data_index, data_bank_number);
-        
+
         let range_start = 12;
-        let range_end = range_start + (number_of_bytes_of_data as usize * 2);
-        let record = &message[range_start..range_end];
+        let range_end = (range_start + number_of_bytes_of_data * 2).min(message.len().saturating_sub(1));
+        let unnibbled_record = misc_data::from_nibbles(&message[range_start..range_end]);
 
-        let mut string_buf = format!("range_start={}, range_end={} - ", range_start, range_end);
+        let mut string_buf = format!("range_start={}, range_end={} - Unnibbled: ", range_start, range_end);
+        for value in unnibbled_record.iter() {
+            string_buf.push_str(format!("{}, ", value).as_str());
+        }
+        info!("{}", string_buf.as_str());
+
+        let value = match misc_data::decode(data_bank_number, &unnibbled_record) {
+            Ok(value) => value,
+            Err(error) => {
+                info!("{}: {}", self.name(), error);
+                return
+            }
+        };
+
+        let _ = match (data_bank_number, data_index) {
+            (1, 1) => sender.send(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDriveNumberOfPartitions(unnibbled_record[0]))),
+            (1, 2) => sender.send(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDriveSelectedPartition(unnibbled_record[0]))),
+            (1, 3) => sender.send(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDrivePartitionNumberOfVolumes(unnibbled_record[0]))),
+            (1, 4) => sender.send(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDrivePartitionSelectedVolume(unnibbled_record[0]))),
+            _ => sender.send(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::MiscellaneousBytes(value))),
+        };
+    }
+
+    fn name(&self) -> String {
+        String::from("SampleSysexMiscellaneousBytesMessageHandler")
+    }
+}
+
+// A directory listing can arrive over several replies, each with its own header. The handler is told
+// how many entries were asked for and keeps taking replies until it has them all or the directory ends.
+struct SampleSysexHardDiskDirectoryEntriesMessageHandler {
+    expected_number_of_entries: usize,
+    number_of_entries_received: usize,
+}
+
+impl SampleSysexHardDiskDirectoryEntriesMessageHandler {
+    pub fn new() -> Self {
+        Self {
+            expected_number_of_entries: 0,
+            number_of_entries_received: 0,
+        }
+    }
+
+    fn set_expected_number_of_entries(&mut self, expected_number_of_entries: usize) {
+        self.expected_number_of_entries = expected_number_of_entries;
+        self.number_of_entries_received = 0;
+    }
+
+    fn is_receiving(&self) -> bool {
+        self.number_of_entries_received < self.expected_number_of_entries
+    }
+
+    fn handle_mut(&mut self, message: &[u8], sender: &Sender<OutgoingEvent>) {
+        let entry_number = message[5] as u16 | ((message[6] as u16) << 7);
+        let selector = message[7];
+        let declared_number_of_bytes = message[10] as usize | ((message[11] as usize) << 7);
+        // 12 header bytes and the EOX, with every byte sent as two nibbles
+        let number_of_bytes_received = message.len().saturating_sub(13) / 2;
+        let number_of_bytes = declared_number_of_bytes.min(number_of_bytes_received);
+
+        info!("message length={}, entry_number={}, selector={}, declared_number_of_bytes={}", message.len(), entry_number, selector, declared_number_of_bytes);
+        if number_of_bytes_received < declared_number_of_bytes {
+            info!("{}: only {} of the {} bytes declared were received.", self.name(), number_of_bytes_received, declared_number_of_bytes);
+        }
+
+        let record = &message[12..(12 + number_of_bytes * 2)];
         let mut unnibbled_record = vec![];
         let mut unnibbled_value: u8 = 0;
         for (nibble_index, nibble) in record.iter().enumerate() {
@@ -755,40 +856,44 @@ impl SampleSysexMessageHandler for SampleSysexMiscellaneousBytesMessageHandler {
             }
         }
 
-        string_buf.push_str("Unnibbled: ");
-        for value in unnibbled_record.iter() {
-            string_buf.push_str(format!("{}, ", value).as_str());
-        }
-        info!("{}", string_buf.as_str());
+        let mut entries = vec![];
+        let mut end_of_directory = number_of_bytes == 0;
 
+        for entry_data in unnibbled_record.chunks_exact(DIRECTORY_ENTRY_SIZE_IN_BYTES) {
+            if is_end_of_directory_entry(entry_data) {
+                info!("{}: end of directory after entry {}.", self.name(), entry_number as usize + entries.len());
+                end_of_directory = true;
+                break
+            }
 
-        if data_bank_number == 1 {
-            let _ = match data_index {
-                1 => sender.send(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDriveNumberOfPartitions(unnibbled_record[0]))),
-                2 => sender.send(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDriveSelectedPartition(unnibbled_record[0]))),
-                3 => sender.send(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDrivePartitionNumberOfVolumes(unnibbled_record[0]))),
-                4 => sender.send(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDrivePartitionSelectedVolume(unnibbled_record[0]))),
-                _ => sender.send(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::MiscellaneousBytes(unnibbled_record[0] as u16, None))),
+            let entry = DirectoryEntry {
+                file_name: convert_sampler_sysex_name_to_name(&entry_data.to_vec()),
+                file_type: entry_data[16],
+                model: entry_data[15],
             };
-        }
-        else if data_bank_number == 2 {
-            let _ = sender.send(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::MiscellaneousBytes(unnibbled_record[0] as u16 | ((unnibbled_record[1] as u16) << 7), None)));
-        }
-        else if data_bank_number == 6 {
-            let name = convert_sampler_sysex_name_to_name(&unnibbled_record);
-            info!("{}", name.as_str());
-            let _ = sender.send(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::MiscellaneousBytes(0, Some(name))));
+            info!("{} {}", entry_number as usize + entries.len(), entry.file_name.as_str());
+
+            entries.push(entry);
         }
 
-        ()
-    }
+        self.number_of_entries_received += entries.len();
+        if end_of_directory {
+            self.expected_number_of_entries = 0;
+            self.number_of_entries_received = 0;
+        }
 
-    fn name(&self) -> String {
-        String::from("SampleSysexMiscellaneousBytesMessageHandler")
+        let _ = sender.send(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDiskDirEntries(entries, end_of_directory, None)));
     }
 }
 
-struct SampleSysexHardDiskDirectoryEntriesMessageHandler;
+// Entries past the end of a directory come back with no file type or a blank name
+fn is_end_of_directory_entry(entry_data: &[u8]) -> bool {
+    let name_data = &entry_data[..(NAME_ENTRY_SIZE_IN_BYTES as usize)];
+
+    entry_data[16] == 0
+        || name_data.iter().all(|byte| *byte == 0)
+        || name_data.iter().all(|byte| *byte == 10 /* space */)
+}
 
 impl SampleSysexMessageHandler for SampleSysexHardDiskDirectoryEntriesMessageHandler {
     fn can_handle(&self, message: &Vec<u8>) -> bool {
@@ -809,74 +914,16 @@ impl SampleSysexMessageHandler for SampleSysexHardDiskDirectoryEntriesMessageHan
                 info!("{}: Sysex sampler identity incorrect.", self.name());
                 return false
             }
-            // else if (index + 1) == message.len() && *sysex_byte != EOX  {
-            //     info!("{}: Sysex is not terminated properly.", self.name());
-            //     return false
-            // }
-        }
-
-        true
-    }
-
-    fn handle(&self, message: &Vec<u8>, sender: &Sender<OutgoingEvent>) {
-        let entry_number_data = &message[5..7];
-        let entry_number = entry_number_data[0] | (entry_number_data[1] << 7);
-        let selector = message[7];
-        let number_of_bytes_data = &message[10..12];
-
-        info!("number_of_bytes_data[0]={}, number_of_bytes_data[1]={}", number_of_bytes_data[0], number_of_bytes_data[1]);
-
-        let number_of_bytes_data = (number_of_bytes_data[0] as usize) | ((number_of_bytes_data[1] << 7) as usize);
-        let number_of_bytes_data = message.len() - 13;
-
-        info!("message length={}, entry_number={}, selector={}, number_of_bytes_data={}", message.len(), entry_number, selector, number_of_bytes_data);
-        
-        let range_start = 12;
-        let range_end = range_start + (message.len() - 13);
-        let record = &message[range_start..range_end];
-
-        let mut string_buf = format!("range_start={}, range_end={} - ", range_start, range_end);
-        let mut unnibbled_record = vec![];
-        let mut unnibbled_value: u8 = 0;
-        for (nibble_index, nibble) in record.iter().enumerate() {
-            string_buf.push_str(format!("{}, ", nibble).as_str());
-
-            if nibble_index % 2 == 0 {
-                unnibbled_value = *nibble;
-            }
-            else {
-                unnibbled_record.push(unnibbled_value | (*nibble << 4));
+            else if (index + 1) == message.len() && *sysex_byte != EOX  {
+                info!("{}: Sysex is not terminated properly.", self.name());
+                return false
             }
         }
-        info!("{}", string_buf.as_str());
-
-        string_buf.clear();
-        string_buf.push_str("Unnibbled: ");
-        for value in unnibbled_record.iter() {
-            string_buf.push_str(format!("{}, ", value).as_str());
-        }
-        info!("{}", string_buf.as_str());
-
-        let mut entries = vec![];
-
-        for entry_index in (0..(number_of_bytes_data / 2)).step_by(24) {
-            let name = convert_sampler_sysex_name_to_name(&unnibbled_record[entry_index..(entry_index + 24)].to_vec());
-            info!("{} {}", entry_index / 24, name.as_str());    
 
-            // let end_of_files_file_name = "000000000000".to_string();
-            // if name.cmp(&end_of_files_file_name) == Ordering::Equal {
-            //     break;
-            // }
-
-            let entry = DirectoryEntry {
-                file_name: name,
-                file_type: unnibbled_record[entry_index + 16],
-                model: unnibbled_record[entry_index + 15],
-            };
+        message.len() >= 13
+    }
 
-            entries.push(entry);
-        }
-        let _ = sender.send(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDiskDirEntries(entries, None)));
+    fn handle(&self, _message: &Vec<u8>, _sender: &Sender<OutgoingEvent>) {
     }
 
     fn name(&self) -> String {
@@ -1357,6 +1404,7 @@ struct SampleSysexSampleDumpPacketMessageHandler {
     sample_dump_data_packet_count: i32,
     expected_sample_dump_data_packet_count: i32,
     sample_data: Vec<u16>,
+    request_generation: u32,
 }
 
 impl SampleSysexSampleDumpPacketMessageHandler {
@@ -1365,13 +1413,24 @@ impl SampleSysexSampleDumpPacketMessageHandler {
             sample_dump_data_packet_count: 0,
             expected_sample_dump_data_packet_count: 0,
             sample_data: vec![],
+            request_generation: 0,
         }
     }
 
-    fn set_expected_sample_dump_data_packet_count(&mut self, expected_sample_dump_data_packet_count: i32) {
+    fn set_expected_sample_dump_data_packet_count(&mut self, request_generation: u32, expected_sample_dump_data_packet_count: i32) {
+        if self.request_generation != request_generation {
+            // a new request or a cancel - throw away anything from a previous dump, even one of the same size
+            self.request_generation = request_generation;
+            self.sample_dump_data_packet_count = 0;
+            self.sample_data.clear();
+        }
         self.expected_sample_dump_data_packet_count = expected_sample_dump_data_packet_count;
     }
 
+    fn is_receiving(&self) -> bool {
+        self.expected_sample_dump_data_packet_count > 0
+    }
+
     fn handle_mut(&mut self, message: &Vec<u8>, sender: &Sender<OutgoingEvent>) {
         info!("SampleSysexSampleDumpPacketMessageHandler: handling sample dump packet...: expected_sample_dump_data_packet_count={}, sample_dump_data_packet_count={}", 
             self.expected_sample_dump_data_packet_count,
@@ -1403,6 +1462,8 @@ impl SampleSysexSampleDumpPacketMessageHandler {
 
             }
 
+            let _ = sender.send(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::SampleDataProgress(self.sample_dump_data_packet_count, self.expected_sample_dump_data_packet_count)));
+
             if self.expected_sample_dump_data_packet_count == self.sample_dump_data_packet_count {
                 // send a message to the client
                 let _ = sender.send(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::SampleData(self.sample_data.clone())));
@@ -1450,6 +1511,8 @@ impl SampleSysexMessageHandler for SampleSysexSampleDumpPacketMessageHandler {
 struct SampleSysexMessageProcessor {
     handlers: Vec<Box<dyn SampleSysexMessageHandler>>,
     sample_dump_packet_message_handler: SampleSysexSampleDumpPacketMessageHandler,
+    hard_disk_directory_entries_message_handler: SampleSysexHardDiskDirectoryEntriesMessageHandler,
+    partial_message: Vec<u8>, // the start of a sysex message that has not been terminated yet
 }
 
 unsafe impl Send for SampleSysexMessageProcessor {
@@ -1464,7 +1527,6 @@ impl SampleSysexMessageProcessor {
             Box::new(SampleSysexResidentSamplesMessageHandler),
             Box::new(SampleSysexStatusReportMessageHandler),
             Box::new(SampleSysexVolumeListMessageHandler),
-            Box::new(SampleSysexHardDiskDirectoryEntriesMessageHandler),
             Box::new(SampleSysexS1000MiscellaneousDataMessageHandler),
             Box::new(SampleSysexMiscellaneousBytesMessageHandler),
             Box::new(SampleSysexS1000CommandReplyMessageHandler),
@@ -1478,6 +1540,32 @@ impl SampleSysexMessageProcessor {
         Self {
             handlers,
             sample_dump_packet_message_handler: SampleSysexSampleDumpPacketMessageHandler::new(),
+            hard_disk_directory_entries_message_handler: SampleSysexHardDiskDirectoryEntriesMessageHandler::new(),
+            partial_message: vec![],
+        }
+    }
+
+    // Long sysex messages can be delivered in pieces. Returns the whole message once its EOX has
+    // arrived, or None while still waiting for the rest of it.
+    fn assemble(&mut self, message: &[u8]) -> Option<Vec<u8>> {
+        if message.first() == Some(&START_OF_SYSTEM_EXCLUSIVE) {
+            if !self.partial_message.is_empty() {
+                info!("Discarding {} bytes of an unterminated sysex message.", self.partial_message.len());
+            }
+            self.partial_message = message.to_vec();
+        }
+        else if !self.partial_message.is_empty() {
+            self.partial_message.extend_from_slice(message);
+        }
+        else {
+            return Some(message.to_vec())
+        }
+
+        if self.partial_message.last() == Some(&EOX) {
+            Some(std::mem::take(&mut self.partial_message))
+        }
+        else {
+            None
         }
     }
 
@@ -1490,6 +1578,12 @@ impl SampleSysexMessageProcessor {
             }
         }
 
+        if self.hard_disk_directory_entries_message_handler.can_handle(message) {
+            info!("Found sampler sysex message handler: {}", self.hard_disk_directory_entries_message_handler.name());
+            self.hard_disk_directory_entries_message_handler.handle_mut(message, sender);
+            return true
+        }
+
         if self.sample_dump_packet_message_handler.can_handle(message) {
             info!("Found sampler sample dump data packet sysex message handler: {}", self.sample_dump_packet_message_handler.name());
             self.sample_dump_packet_message_handler.handle_mut(message, sender);
@@ -1506,6 +1600,14 @@ impl SampleSysexMessageProcessor {
     fn sample_dump_packet_message_handler_mut(&mut self) -> &mut SampleSysexSampleDumpPacketMessageHandler {
         &mut self.sample_dump_packet_message_handler
     }
+
+    fn hard_disk_directory_entries_message_handler(&self) -> &SampleSysexHardDiskDirectoryEntriesMessageHandler {
+        &self.hard_disk_directory_entries_message_handler
+    }
+
+    fn hard_disk_directory_entries_message_handler_mut(&mut self) -> &mut SampleSysexHardDiskDirectoryEntriesMessageHandler {
+        &mut self.hard_disk_directory_entries_message_handler
+    }
 }
 
 fn list_midi_input_ports(mut cx: FunctionContext) -> JsResult<JsArray> {
@@ -1611,15 +1713,7 @@ fn sampler_delete_program(mut cx: FunctionContext) -> JsResult<JsBoolean> {
     info!("Entered sampler_delete_program...");
     if let Ok(program_number) = cx.argument::<JsNumber>(0) {
         let program_number = program_number.value(&mut cx) as u16;
-        let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::DeleteProgram(program_number)));
-        
-        if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-            if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                    return Ok(cx.boolean(success))
-                }
-            }
-        }
+        return Ok(cx.boolean(send_sampler_change(IncomingSamplerEvent::DeleteProgram(program_number))))
     }
                     
     return Ok(cx.boolean(false))
@@ -1631,37 +1725,162 @@ fn sampler_delete_keygroup(mut cx: FunctionContext) -> JsResult<JsBoolean> {
         let program_number = program_number.value(&mut cx) as u16;
         if let Ok(keygroup_number) = cx.argument::<JsNumber>(1) {
             let keygroup_number = keygroup_number.value(&mut cx) as u8;
-            let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::DeleteKeygroup(program_number, keygroup_number)));
-        
-            if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-                if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                    if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                        return Ok(cx.boolean(success))
-                    }
-                }
-            }
+            return Ok(cx.boolean(send_sampler_change(IncomingSamplerEvent::DeleteKeygroup(program_number, keygroup_number))))
         }
     }
                     
     return Ok(cx.boolean(false))
 }
 
-fn sampler_delete_sample(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+// sampler_delete_sample(sample number, [mode]) - mode is "refuse" (the default), "warn" or "cascade", see
+// sample_dependencies::DeleteMode. Returns {deleted, refused, references: [{program, program_name, keygroup, zone}]}.
+fn sampler_delete_sample(mut cx: FunctionContext) -> JsResult<JsObject> {
     info!("Entered sampler_delete_sample...");
+    let mut deleted = false;
+    let mut refused = false;
+    let mut references = vec![];
+
     if let Ok(sample_number) = cx.argument::<JsNumber>(0) {
         let sample_number = sample_number.value(&mut cx) as u16;
-        let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::DeleteSample(sample_number)));
-        
-        if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-            if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                    return Ok(cx.boolean(success))
+        let mode = match cx.argument_opt(1) {
+            Some(mode) => match mode.downcast::<JsString, FunctionContext>(&mut cx) {
+                Ok(mode) => sample_dependencies::DeleteMode::from_name(mode.value(&mut cx).as_str()),
+                Err(_) => None,
+            },
+            None => Some(sample_dependencies::DeleteMode::Refuse),
+        };
+
+        match mode {
+            Some(mode) => match journal_step(format!("delete sample {}", sample_number).as_str(), || delete_sample(sample_number, mode)) {
+                Ok((sample_deleted, sample_references)) => {
+                    deleted = sample_deleted;
+                    refused = !sample_deleted;
+                    references = sample_references;
                 }
+                Err(error) => info!("sampler_delete_sample: {}", error),
+            },
+            None => info!("sampler_delete_sample: the mode must be refuse, warn or cascade."),
+        }
+    }
+
+    let result = cx.empty_object();
+    let deleted = cx.boolean(deleted);
+    result.set(&mut cx, "deleted", deleted)?;
+    let refused = cx.boolean(refused);
+    result.set(&mut cx, "refused", refused)?;
+    let references = zone_references_array(&mut cx, &references)?;
+    result.set(&mut cx, "references", references)?;
+
+    Ok(result)
+}
+
+// every keygroup of every resident program
+fn read_sample_dependencies() -> Result<sample_dependencies::SampleDependencies, String> {
+    let program_names = request_resident_program_names().ok_or("could not read the resident program names")?;
+    let mut dependencies = sample_dependencies::SampleDependencies::new();
+
+    for (program_number, program_name) in program_names.iter().enumerate() {
+        let program_number = program_number as u16;
+        let header = request_program_header(program_number).ok_or_else(|| format!("could not read program {}", program_number))?;
+        let number_of_keygroups = header.get(program_builder::NUMBER_OF_KEYGROUPS_OFFSET).copied().unwrap_or(0);
+
+        for keygroup_number in 0..number_of_keygroups {
+            let keygroup_header = request_keygroup_header(program_number, keygroup_number).ok_or_else(|| format!("could not read program {} keygroup {}", program_number, keygroup_number))?;
+            dependencies.add_keygroup(program_number, program_name, keygroup_number, &keygroup_header);
+        }
+    }
+
+    Ok(dependencies)
+}
+
+fn request_sample_references(sample_number: u16) -> Result<(String, Vec<sample_dependencies::ZoneReference>), String> {
+    let sample_names = request_resident_sample_names().ok_or("could not read the resident sample names")?;
+    let sample_name = sample_names.get(sample_number as usize).ok_or_else(|| format!("there is no sample {}", sample_number))?;
+    let references = read_sample_dependencies()?.references(sample_name).to_vec();
+
+    Ok((sample_name.trim_end().to_string(), references))
+}
+
+// the zones are left with a blank sample name, which the sampler treats as an empty zone
+fn clear_zone_references(references: &[sample_dependencies::ZoneReference]) -> bool {
+    let blank_name = convert_name_to_sampler_sysex_name(String::new());
+
+    references.iter().all(|reference| change_keygroup_header(reference.program as u8, reference.keygroup, keygroup::ZONE_SAMPLE_NAME_OFFSETS[reference.zone] as u8, blank_name.clone()))
+}
+
+// whether the sample was deleted and the zones that use it - refusing is not an error
+fn delete_sample(sample_number: u16, mode: sample_dependencies::DeleteMode) -> Result<(bool, Vec<sample_dependencies::ZoneReference>), String> {
+    let (sample_name, references) = request_sample_references(sample_number)?;
+
+    match mode {
+        sample_dependencies::DeleteMode::Refuse if !references.is_empty() => {
+            info!("delete_sample: {} is used by {} keygroup zones.", sample_name, references.len());
+            return Ok((false, references))
+        }
+        sample_dependencies::DeleteMode::Cascade if !clear_zone_references(&references) => return Err(format!("the keygroup zones using {} were not all cleared", sample_name)),
+        _ => (),
+    }
+
+    // only journaled once the sampler has deleted it, so a failed delete leaves just the cleared zones to undo
+    if send_sampler_change(IncomingSamplerEvent::DeleteSample(sample_number)) {
+        waveform::forget_overview(&sample_name);
+        Ok((true, references))
+    }
+    else {
+        Err(format!("{} was not deleted", sample_name))
+    }
+}
+
+fn zone_references_array<'a>(cx: &mut FunctionContext<'a>, references: &[sample_dependencies::ZoneReference]) -> JsResult<'a, JsArray> {
+    let array = cx.empty_array();
+
+    for (index, reference) in references.iter().enumerate() {
+        let object = cx.empty_object();
+        let program = cx.number(reference.program as f64);
+        object.set(cx, "program", program)?;
+        let program_name = cx.string(reference.program_name.as_str());
+        object.set(cx, "program_name", program_name)?;
+        let keygroup = cx.number(reference.keygroup as f64);
+        object.set(cx, "keygroup", keygroup)?;
+        let zone = cx.number(reference.zone as f64);
+        object.set(cx, "zone", zone)?;
+        array.set(cx, index as u32, object)?;
+    }
+
+    Ok(array)
+}
+
+// [{ program, program_name, keygroup, zone }] - the keygroup zones that play the sample
+fn sampler_sample_references(mut cx: FunctionContext) -> JsResult<JsArray> {
+    info!("Entered sampler_sample_references...");
+    if let Ok(sample_number) = cx.argument::<JsNumber>(0) {
+        let sample_number = sample_number.value(&mut cx) as u16;
+        match request_sample_references(sample_number) {
+            Ok((_, references)) => return zone_references_array(&mut cx, &references),
+            Err(error) => info!("sampler_sample_references: {}", error),
+        }
+    }
+
+    Ok(cx.empty_array())
+}
+
+// { sample name: [{ program, program_name, keygroup, zone }] } for every sample any keygroup zone names,
+// resident or not
+fn sampler_sample_dependencies(mut cx: FunctionContext) -> JsResult<JsObject> {
+    info!("Entered sampler_sample_dependencies...");
+    let object = cx.empty_object();
+
+    match read_sample_dependencies() {
+        Ok(dependencies) => {
+            for (sample_name, references) in dependencies.iter() {
+                let references = zone_references_array(&mut cx, references)?;
+                object.set(&mut cx, sample_name.as_str(), references)?;
             }
         }
+        Err(error) => info!("sampler_sample_dependencies: {}", error),
     }
-                    
-    return Ok(cx.boolean(false))
+
+    Ok(object)
 }
 
 fn sampler_new_program(mut cx: FunctionContext) -> JsResult<JsBoolean> {
@@ -1687,14 +1906,7 @@ fn sampler_new_program(mut cx: FunctionContext) -> JsResult<JsBoolean> {
             info!("sampler_new_program: sysex_payload length={}", sysex_payload.len());
             
             if sysex_payload.len() == 192 {
-                let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::NewProgram(program_number, sysex_payload)));
-                if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-                    if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                        if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                            return Ok(cx.boolean(success))
-                        }
-                    }
-                }
+                return Ok(cx.boolean(send_sampler_change(IncomingSamplerEvent::NewProgram(program_number, sysex_payload))))
             }
         }
     }
@@ -1702,6 +1914,115 @@ fn sampler_new_program(mut cx: FunctionContext) -> JsResult<JsBoolean> {
     return Ok(cx.boolean(false))
 }
 
+fn number_property(cx: &mut FunctionContext, object: Handle<JsObject>, key: &str) -> NeonResult<Option<f64>> {
+    Ok(object.get_value(cx, key)?
+        .downcast::<JsNumber, FunctionContext>(cx)
+        .ok()
+        .map(|value| value.value(cx)))
+}
+
+fn string_property(cx: &mut FunctionContext, object: Handle<JsObject>, key: &str) -> NeonResult<Option<String>> {
+    Ok(object.get_value(cx, key)?
+        .downcast::<JsString, FunctionContext>(cx)
+        .ok()
+        .map(|value| value.value(cx)))
+}
+
+// every option is optional apart from the cycle of a single_cycle template - anything missing keeps the template default
+fn read_sample_template_options(cx: &mut FunctionContext, options: Handle<JsObject>, template: &str, sample_template: &mut SampleTemplate) -> NeonResult<bool> {
+    match template {
+        "single_cycle" => match read_single_cycle(cx, options)? {
+            Some(cycle) => sample_template.waveform = Waveform::SingleCycle(cycle),
+            None => {
+                info!("read_sample_template_options: single_cycle needs samples or a file with at least two values.");
+                return Ok(false)
+            }
+        },
+        // only an additive waveform is built from harmonics - any given with another template are ignored
+        "additive" => {
+            if let Ok(harmonics) = options.get_value(cx, "harmonics")?.downcast::<JsArray, FunctionContext>(cx) {
+                let mut amplitudes = vec![];
+                for harmonic in harmonics.to_vec(cx)?.iter() {
+                    if let Ok(amplitude) = harmonic.downcast::<JsNumber, FunctionContext>(cx) {
+                        amplitudes.push(amplitude.value(cx));
+                    }
+                }
+                sample_template.waveform = Waveform::Additive(amplitudes);
+            }
+        }
+        _ => (),
+    }
+
+    if let Some(root_note) = number_property(cx, options, "root_note")? {
+        sample_template.set_root_note(root_note as u8);
+    }
+    if let Some(frequency) = number_property(cx, options, "frequency")? {
+        sample_template.frequency = frequency;
+    }
+    if let Some(cycles) = number_property(cx, options, "cycles")? {
+        sample_template.cycles = cycles as usize;
+    }
+    if let Some(sample_rate) = number_property(cx, options, "sample_rate")? {
+        sample_template.sample_rate = sample_rate as u32;
+    }
+    if let Some(amplitude) = number_property(cx, options, "amplitude")? {
+        sample_template.amplitude = amplitude;
+    }
+    if let Some(pulse_width) = number_property(cx, options, "pulse_width")? {
+        sample_template.pulse_width = pulse_width;
+    }
+    if let Some(voices) = number_property(cx, options, "voices")? {
+        sample_template.voices = voices as usize;
+    }
+    if let Some(detune) = number_property(cx, options, "detune")? {
+        sample_template.detune = detune;
+    }
+
+    // a duration turns the sample into an ADSR shaped one shot
+    if let Some(duration) = number_property(cx, options, "duration")? {
+        sample_template.envelope = Some(Envelope {
+            attack: number_property(cx, options, "attack")?.unwrap_or(0.0),
+            decay: number_property(cx, options, "decay")?.unwrap_or(0.0),
+            sustain: number_property(cx, options, "sustain")?.unwrap_or(1.0),
+            release: number_property(cx, options, "release")?.unwrap_or(0.0),
+            duration,
+        });
+    }
+
+    Ok(true)
+}
+
+// a single cycle comes from raw values in "samples" or from a WAV file in "file" - either a
+// wavetable, where "frame" picks the cycle, or a single cycle file such as those from AKWF
+fn read_single_cycle(cx: &mut FunctionContext, options: Handle<JsObject>) -> NeonResult<Option<Vec<f64>>> {
+    if let Ok(samples) = options.get_value(cx, "samples")?.downcast::<JsArray, FunctionContext>(cx) {
+        let mut cycle = vec![];
+        for sample in samples.to_vec(cx)?.iter() {
+            if let Ok(sample) = sample.downcast::<JsNumber, FunctionContext>(cx) {
+                cycle.push(sample.value(cx));
+            }
+        }
+
+        return Ok(if cycle.len() >= 2 { Some(cycle) } else { None })
+    }
+
+    if let Ok(file) = options.get_value(cx, "file")?.downcast::<JsString, FunctionContext>(cx) {
+        let file = file.value(cx);
+        let frame = number_property(cx, options, "frame")?.unwrap_or(0.0) as usize;
+        let cycle_length = number_property(cx, options, "cycle_length")?.map(|cycle_length| cycle_length as usize);
+
+        match wav::WavFile::read(file.as_str()) {
+            Ok(wav_file) => {
+                info!("read_single_cycle: {} has {} samples at {}Hz.", file, wav_file.len(), wav_file.sample_rate);
+                return Ok(sample_template::wavetable_frame(&wav_file.mono(), cycle_length, frame))
+            }
+            Err(error) => info!("read_single_cycle: could not read {}: {}", file, error),
+        }
+    }
+
+    Ok(None)
+}
+
 fn sampler_new_sample_from_template(mut cx: FunctionContext) -> JsResult<JsBoolean> {
     info!("Entered sampler_new_sample_from_template...");
     if let Ok(sample_number) = cx.argument::<JsNumber>(0) {
@@ -1730,15 +2051,44 @@ fn sampler_new_sample_from_template(mut cx: FunctionContext) -> JsResult<JsBoole
                 info!("sampler_new_sample_from_template: sysex_payload length={}", sysex_payload.len());
                 
                 if sysex_payload.len() == 192 {
-                    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::NewSampleFromTemplate(sample_number, template, sysex_payload)));
-                    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-                        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                            if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                                return Ok(cx.boolean(success))
+                    let mut sample_template = match Waveform::new(template.as_str(), vec![]) {
+                        Ok(waveform) => SampleTemplate::new(waveform),
+                        Err(error) => {
+                            info!("sampler_new_sample_from_template: {}", error);
+                            return Ok(cx.boolean(false))
+                        }
+                    };
+
+                    if let Some(header) = SampleHeader::from_bytes(&sysex_payload) {
+                        if dsp::SUPPORTED_SAMPLE_RATES.contains(&(header.sample_rate as u32)) {
+                            sample_template.sample_rate = header.sample_rate as u32;
+                        }
+                    }
+
+                    if let Some(options) = cx.argument_opt(3) {
+                        if let Ok(options) = options.downcast::<JsObject, FunctionContext>(&mut cx) {
+                            if !read_sample_template_options(&mut cx, options, template.as_str(), &mut sample_template)? {
+                                info!("sampler_new_sample_from_template: invalid template options.");
+                                return Ok(cx.boolean(false))
                             }
                         }
                     }
-                }
+
+                    if let Err(error) = sample_template.validate() {
+                        info!("sampler_new_sample_from_template: {}", error);
+                        return Ok(cx.boolean(false))
+                    }
+
+                    info!("sampler_new_sample_from_template: {:?}", sample_template);
+
+                    let samples = sample_template.render();
+                    sample_header::set_sample_length(&mut sysex_payload, samples.len() as u32);
+                    sample_header::set_sample_rate(&mut sysex_payload, sample_template.sample_rate as u16);
+                    sample_header::set_original_pitch(&mut sysex_payload, sample_template.root_note());
+                    sample_header::set_loops(&mut sysex_payload, &sample_template.sample_loop().into_iter().collect::<Vec<SampleLoop>>());
+
+                    return Ok(cx.boolean(send_sampler_change(IncomingSamplerEvent::NewSampleFromTemplate(sample_number, sysex_payload, samples))))
+                }
             }
         }
     }
@@ -1771,14 +2121,7 @@ fn sampler_new_keygroup(mut cx: FunctionContext) -> JsResult<JsBoolean> {
                 info!("sampler_new_keygroup: sysex_payload length={}", sysex_payload.len());
                 
                 if sysex_payload.len() == 192 {
-                    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::NewKeygroup(program_number, keygroup_number, sysex_payload)));
-                    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-                        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                            if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                                return Ok(cx.boolean(success))
-                            }
-                        }
-                    }
+                    return Ok(cx.boolean(send_sampler_change(IncomingSamplerEvent::NewKeygroup(program_number, keygroup_number, sysex_payload))))
                 }
             }
     
@@ -1792,15 +2135,7 @@ fn sampler_new_sample(mut cx: FunctionContext) -> JsResult<JsBoolean> {
     info!("Entered sampler_new_sample...");
     if let Ok(sample_number) = cx.argument::<JsNumber>(0) {
         let sample_number = sample_number.value(&mut cx) as u16;
-        let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::NewSample(sample_number)));
-        
-        if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-            if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                    return Ok(cx.boolean(success))
-                }
-            }
-        }
+        return Ok(cx.boolean(send_sampler_change(IncomingSamplerEvent::NewSample(sample_number))))
     }
                     
     return Ok(cx.boolean(false))
@@ -1879,19 +2214,9 @@ fn sampler_change_program_header(mut cx: FunctionContext) -> JsResult<JsBoolean>
                     }
                 }
 
-                let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::ChangeProgramHeader(program_number, program_header_offset, changed_program_header_data)));
-
-                if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-                    if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                        if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                            info!("sampler_change_program_header: S1000CommandReply={}", success);
-                            return Ok(cx.boolean(success))
-                        }
-                    }
-                }
-                else {
-                    info!("sampler_change_program_header: receive timeout.");
-                }
+                let success = send_sampler_change(IncomingSamplerEvent::ChangeProgramHeader(program_number, program_header_offset, changed_program_header_data));
+                info!("sampler_change_program_header: S1000CommandReply={}", success);
+                return Ok(cx.boolean(success))
             }
         }
     }
@@ -1966,15 +2291,8 @@ fn sampler_change_keygroup_header(mut cx: FunctionContext) -> JsResult<JsBoolean
                         }
                     }
 
-                    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(
-                        IncomingSamplerEvent::ChangeKeyGroupHeader(program_number, keygroup_number, keygroup_header_offset, changed_keygroup_header_data)));
-                    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-                        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                            if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                                return Ok(cx.boolean(success))
-                            }
-                        }
-                    }
+                    return Ok(cx.boolean(send_sampler_change(
+                        IncomingSamplerEvent::ChangeKeyGroupHeader(program_number, keygroup_number, keygroup_header_offset, changed_keygroup_header_data))))
                 }
             }
         }
@@ -2013,6 +2331,215 @@ fn sampler_request_sample_header(mut cx: FunctionContext) -> JsResult<JsArray> {
     return Ok(cx.empty_array())
 }
 
+fn request_sample_header(sample_number: u16) -> Option<Vec<u8>> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestSampleHeader(sample_number)));
+
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::SampleHeader(data))) => Some(data),
+        _ => None,
+    }
+}
+
+fn sampler_request_sample_waveform(mut cx: FunctionContext) -> JsResult<JsObject> {
+    info!("Entered sampler_request_sample_waveform...");
+    let waveform = cx.empty_object();
+
+    if let Ok(sample_number) = cx.argument::<JsNumber>(0) {
+        let sample_number = sample_number.value(&mut cx) as u16;
+        let maximum_number_of_peaks = match cx.argument_opt(1) {
+            Some(value) => match value.downcast::<JsNumber, FunctionContext>(&mut cx) {
+                Ok(value) => value.value(&mut cx) as usize,
+                Err(_) => waveform::DEFAULT_MAXIMUM_NUMBER_OF_PEAKS,
+            },
+            None => waveform::DEFAULT_MAXIMUM_NUMBER_OF_PEAKS,
+        };
+
+        if let Some(header) = request_sample_header(sample_number).and_then(|data| SampleHeader::from_bytes(&data)) {
+            let overview = match waveform::cached_overview(&header) {
+                Some(overview) => Some(overview),
+                None => request_sample_data(sample_number, 0, header.sample_length, &mut |_, _| true)
+                    .map(|samples| samples.iter().map(|sample| *sample as i16).collect::<Vec<i16>>())
+                    .map(|samples| waveform::cache_overview(&header, WaveformOverview::new(&header, &samples))),
+            };
+
+            if let Some(overview) = overview {
+                let level_index = overview.level_for_width(maximum_number_of_peaks);
+                let level = &overview.levels[level_index];
+
+                let levels = cx.empty_array();
+                for (index, level) in overview.levels.iter().enumerate() {
+                    let samples_per_peak = cx.number(level.samples_per_peak as f64);
+                    let _ = levels.set(&mut cx, index as u32, samples_per_peak);
+                }
+
+                let min = cx.empty_array();
+                let max = cx.empty_array();
+                let rms = cx.empty_array();
+                for index in 0..level.len() {
+                    let value = cx.number(level.min[index]);
+                    let _ = min.set(&mut cx, index as u32, value);
+                    let value = cx.number(level.max[index]);
+                    let _ = max.set(&mut cx, index as u32, value);
+                    let value = cx.number(level.rms[index]);
+                    let _ = rms.set(&mut cx, index as u32, value);
+                }
+
+                let loops = cx.empty_array();
+                for (index, overlay) in overview.loops.iter().enumerate() {
+                    let js_loop = cx.empty_object();
+                    let start = cx.number(overlay.start);
+                    let end = cx.number(overlay.end);
+                    let dwell_time = cx.number(overlay.dwell_time);
+                    let _ = js_loop.set(&mut cx, "start", start);
+                    let _ = js_loop.set(&mut cx, "end", end);
+                    let _ = js_loop.set(&mut cx, "dwell_time", dwell_time);
+                    let _ = loops.set(&mut cx, index as u32, js_loop);
+                }
+
+                let name = cx.string(header.name.as_str());
+                let sample_length = cx.number(overview.sample_length);
+                let sample_rate = cx.number(overview.sample_rate);
+                let js_level = cx.number(level_index as f64);
+                let samples_per_peak = cx.number(level.samples_per_peak as f64);
+
+                let _ = waveform.set(&mut cx, "name", name);
+                let _ = waveform.set(&mut cx, "sample_length", sample_length);
+                let _ = waveform.set(&mut cx, "sample_rate", sample_rate);
+                let _ = waveform.set(&mut cx, "levels", levels);
+                let _ = waveform.set(&mut cx, "level", js_level);
+                let _ = waveform.set(&mut cx, "samples_per_peak", samples_per_peak);
+                let _ = waveform.set(&mut cx, "min", min);
+                let _ = waveform.set(&mut cx, "max", max);
+                let _ = waveform.set(&mut cx, "rms", rms);
+                let _ = waveform.set(&mut cx, "loops", loops);
+
+                return Ok(waveform)
+            }
+        }
+    }
+
+    info!("sampler_request_sample_waveform: problem getting the sample header or data.");
+    let error = cx.string("Could not compute the waveform.");
+    let _ = waveform.set(&mut cx, "error", error);
+
+    Ok(waveform)
+}
+
+fn sampler_clear_waveform_cache(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_clear_waveform_cache...");
+    waveform::clear_cache();
+
+    Ok(cx.boolean(true))
+}
+
+fn change_sample_header(sample_number: u8, sample_header_offset: u8, data: Vec<u8>) -> bool {
+    send_sampler_change(
+        IncomingSamplerEvent::ChangeSampleHeader(sample_number, sample_header_offset, data))
+}
+
+fn sampler_find_sample_loops(mut cx: FunctionContext) -> JsResult<JsArray> {
+    info!("Entered sampler_find_sample_loops...");
+    let loops = cx.empty_array();
+
+    if let (Ok(sample_number), Ok(region_start), Ok(region_end)) = (cx.argument::<JsNumber>(0), cx.argument::<JsNumber>(1), cx.argument::<JsNumber>(2)) {
+        let sample_number = sample_number.value(&mut cx) as u16;
+        let region_start = region_start.value(&mut cx) as u32;
+        let region_end = region_end.value(&mut cx) as u32;
+        let number_of_candidates = match cx.argument_opt(3) {
+            Some(value) => match value.downcast::<JsNumber, FunctionContext>(&mut cx) {
+                Ok(value) => value.value(&mut cx) as usize,
+                Err(_) => loop_finder::DEFAULT_NUMBER_OF_CANDIDATES,
+            },
+            None => loop_finder::DEFAULT_NUMBER_OF_CANDIDATES,
+        };
+
+        if let Some(header) = request_sample_header(sample_number).and_then(|data| SampleHeader::from_bytes(&data)) {
+            let region_end = region_end.min(header.sample_length);
+
+            if region_start >= region_end {
+                info!("sampler_find_sample_loops: the loop region is empty.");
+                return Ok(loops)
+            }
+
+            if let Some(samples) = request_sample_data(sample_number, region_start, region_end - region_start, &mut |_, _| true) {
+                let samples: Vec<i16> = samples.iter().map(|sample| *sample as i16).collect();
+                let (period, candidates) = loop_finder::find_loops(&samples, header.sample_rate as u32, number_of_candidates);
+
+                for (index, candidate) in candidates.iter().enumerate() {
+                    let js_candidate = cx.empty_object();
+                    let start = cx.number(region_start + candidate.start);
+                    let end = cx.number(region_start + candidate.end);
+                    let length = cx.number(candidate.length());
+                    let discontinuity = cx.number(candidate.discontinuity);
+                    let _ = js_candidate.set(&mut cx, "start", start);
+                    let _ = js_candidate.set(&mut cx, "end", end);
+                    let _ = js_candidate.set(&mut cx, "length", length);
+                    let _ = js_candidate.set(&mut cx, "discontinuity", discontinuity);
+
+                    if let Some(period) = period {
+                        let period = cx.number(period);
+                        let _ = js_candidate.set(&mut cx, "period", period);
+                    }
+
+                    let _ = loops.set(&mut cx, index as u32, js_candidate);
+                }
+            }
+        }
+    }
+
+    Ok(loops)
+}
+
+// loop numbers start at 0, the end is the first sample after the loop
+fn sampler_set_sample_loop(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_set_sample_loop...");
+
+    if let (Ok(sample_number), Ok(loop_number), Ok(start), Ok(end)) = (cx.argument::<JsNumber>(0), cx.argument::<JsNumber>(1), cx.argument::<JsNumber>(2), cx.argument::<JsNumber>(3)) {
+        let sample_number = sample_number.value(&mut cx) as u16;
+        let loop_number = loop_number.value(&mut cx) as usize;
+        let start = start.value(&mut cx) as u32;
+        let end = end.value(&mut cx) as u32;
+        let dwell_time = match cx.argument_opt(4) {
+            Some(value) => value.downcast::<JsNumber, FunctionContext>(&mut cx).ok().map(|value| value.value(&mut cx) as u16),
+            None => None,
+        };
+
+        if loop_number >= sample_header::NUMBER_OF_LOOPS || start >= end {
+            info!("sampler_set_sample_loop: invalid loop number or loop points.");
+            return Ok(cx.boolean(false))
+        }
+
+        if let Some(header) = request_sample_header(sample_number).and_then(|data| SampleHeader::from_bytes(&data)) {
+            if end > header.sample_length {
+                info!("sampler_set_sample_loop: the loop ends after the end of the sample.");
+                return Ok(cx.boolean(false))
+            }
+
+            let existing_loop = &header.loops[loop_number];
+            let sample_loop = SampleLoop {
+                at: end,
+                length: end - start,
+                length_fraction: 0,
+                dwell_time: dwell_time.unwrap_or(if existing_loop.is_used() { existing_loop.dwell_time } else { sample_header::LOOP_DWELL_TIME_HOLD }),
+            };
+
+            if !change_sample_header(sample_number as u8, sample_header::loop_offset(loop_number) as u8, sample_loop.to_bytes()) {
+                info!("sampler_set_sample_loop: failed to change the loop.");
+                return Ok(cx.boolean(false))
+            }
+
+            if (header.number_of_loops as usize) <= loop_number {
+                let number_of_loops = vec![loop_number as u8 + 1];
+                return Ok(cx.boolean(change_sample_header(sample_number as u8, sample_header::NUMBER_OF_LOOPS_OFFSET as u8, number_of_loops)))
+            }
+
+            return Ok(cx.boolean(true))
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
 fn sampler_change_sample_header(mut cx: FunctionContext) -> JsResult<JsBoolean> {
     info!("Entered sampler_change_sample_header...");
     if let Ok(sample_number) = cx.argument::<JsNumber>(0) {
@@ -2032,15 +2559,8 @@ fn sampler_change_sample_header(mut cx: FunctionContext) -> JsResult<JsBoolean>
                     }
                 }
 
-                let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(
-                    IncomingSamplerEvent::ChangeSampleHeader(sample_number, sample_header_offset, changed_sample_header_data)));
-                if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-                    if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                        if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                            return Ok(cx.boolean(success))
-                    }
-                    }
-                }
+                return Ok(cx.boolean(send_sampler_change(
+                    IncomingSamplerEvent::ChangeSampleHeader(sample_number, sample_header_offset, changed_sample_header_data))))
             }
         }
     }
@@ -2078,8 +2598,8 @@ fn sampler_change_s1000_misc_bytes(mut cx: FunctionContext) -> JsResult<JsBoolea
                         if let Ok(midi_exclusive_channel) = cx.argument::<JsNumber>(5) {
                             let midi_exclusive_channel = midi_exclusive_channel.value(&mut cx) as u8;
             
-                            let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(
-                            IncomingSamplerEvent::ChangeS1000MiscBytes(basic_midi_channel, selected_program_number, midi_play_commands_omni_override, midi_exclusive_channel, basic_channel_omni, midi_program_select_enable)));
+                            send_unanswered_sampler_change(
+                            IncomingSamplerEvent::ChangeS1000MiscBytes(basic_midi_channel, selected_program_number, midi_play_commands_omni_override, midi_exclusive_channel, basic_channel_omni, midi_program_select_enable));
                             // if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
                             //     if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
                             //         if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
@@ -2203,98 +2723,140 @@ fn sampler_effect_header_filename_update(mut cx: FunctionContext) -> JsResult<Js
 
     if let Ok(effect_filename) = cx.argument::<JsString>(0) {
         let effect_filename = effect_filename.value(&mut cx);
-        let s3000_filename = convert_name_to_sampler_sysex_name(effect_filename);
-        let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::ResponseFXReverb(0, 0, 3, s3000_filename)));
+        let s3000_filename = match validated_name(effect_filename.as_str()) {
+            Ok(effect_filename) => effect_filename.to_sysex(),
+            Err(error) => {
+                info!("sampler_effect_header_filename_update: {}", error);
+                return Ok(cx.boolean(false))
+            }
+        };
+        return Ok(cx.boolean(send_sampler_change(IncomingSamplerEvent::ResponseFXReverb(0, 0, 3, s3000_filename))));
+    }
 
-        if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-            if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                    return Ok(cx.boolean(success));
-                }
+    Ok(cx.boolean(false))
+}
+
+// both tables come back from one read - see effects::split_interleaved
+fn request_all_effect_blocks() -> Option<(effects::Blocks, effects::Blocks)> {
+    let all_blocks_size = (effects::NUMBER_OF_EFFECTS + effects::NUMBER_OF_REVERBS) * effects::EFFECT_BLOCK_SIZE;
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestFXReverb(0, effects::EFFECTS_SELECTOR, all_blocks_size as u16, 0)));
+
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(LOAD_SAVE_ENTIRE_VOLUME_RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::FXReverb(data))) => {
+            let blocks = effects::split_interleaved(&data);
+            if blocks.is_none() {
+                info!("request_all_effect_blocks: expected {} bytes but got {}.", all_blocks_size, data.len());
             }
+            blocks
         }
+        _ => None,
     }
+}
 
-    Ok(cx.boolean(false))
+fn block_names_array<'a>(cx: &mut FunctionContext<'a>, blocks: &[Vec<u8>]) -> Handle<'a, JsArray> {
+    let names = cx.empty_array();
+
+    for (index, block) in blocks.iter().enumerate() {
+        let name = cx.string(effects::block_name(block));
+        let _ = names.set(cx, index as u32, name);
+    }
+
+    names
 }
 
 fn sampler_effects_list(mut cx: FunctionContext) -> JsResult<JsArray> {
     info!("Entered sampler_effects_list...");
-    let effect_params_block_size: usize = 64;
-    let all_effect_blocks_size = (50 /* # effects */ + 50 /* number of reverbs */) * effect_params_block_size; // looks like the data is interleaved
-    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestFXReverb(0, 2, all_effect_blocks_size as u16, 0)));
-    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(LOAD_SAVE_ENTIRE_VOLUME_RECEIVE_TIMEOUT.clone()) {
-        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-            if let OutgoingSamplerEvent::FXReverb(byte_data) = sampler_event {
-                info!("byte_data.len()={}", byte_data.len());
+    match request_all_effect_blocks() {
+        Some((effect_blocks, _)) => Ok(block_names_array(&mut cx, &effect_blocks)),
+        None => Ok(cx.empty_array()),
+    }
+}
 
-                if byte_data.len() == all_effect_blocks_size {
-                    let effect_names = cx.empty_array();
+fn sampler_reverbs_list(mut cx: FunctionContext) -> JsResult<JsArray> {
+    info!("Entered sampler_reverbs_list...");
+    match request_all_effect_blocks() {
+        Some((_, reverb_blocks)) => Ok(block_names_array(&mut cx, &reverb_blocks)),
+        None => Ok(cx.empty_array()),
+    }
+}
 
-                    let mut name_index = 0;
-                    for index in (0..all_effect_blocks_size).step_by(effect_params_block_size * 2) {
-                        let mut effect_name_data = vec![];
-                        for letter_position in 0..12 {
-                            effect_name_data.push(byte_data[index + letter_position]);
-                        }
-                        
-                        let effect_name = convert_sampler_sysex_name_to_name(&effect_name_data);
-                        let js_effect_name = cx.string(effect_name);
-                        let _ = effect_names.set(&mut cx, name_index, js_effect_name);
-                        name_index += 1;
-                    }
+// {effects: [...], reverbs: [...]} in the form sampler_get_effect and sampler_get_reverb return - a block
+// with a type that is not understood just has its name and type "unknown"
+fn sampler_effects_and_reverbs(mut cx: FunctionContext) -> JsResult<JsObject> {
+    info!("Entered sampler_effects_and_reverbs...");
+    let result = cx.empty_object();
+
+    if let Some((effect_blocks, reverb_blocks)) = request_all_effect_blocks() {
+        let js_effects = cx.empty_array();
+        for (index, block) in effect_blocks.iter().enumerate() {
+            let js_effect = match effects::Effect::from_bytes(block) {
+                Ok(effect) => effect_parameters_object(&mut cx, effect.common().name().as_str(), effect.type_name(), &effect.parameters()),
+                Err(_) => effect_parameters_object(&mut cx, effects::block_name(block).trim_end(), "unknown", &[]),
+            };
+            let _ = js_effects.set(&mut cx, index as u32, js_effect);
+        }
 
-                    return Ok(effect_names);
-                }
-                else {
-                    info!("byte_data is not {}", all_effect_blocks_size);
-                }
-            }
+        let js_reverbs = cx.empty_array();
+        for (index, block) in reverb_blocks.iter().enumerate() {
+            let js_reverb = match effects::Reverb::from_bytes(block) {
+                Ok(reverb) => effect_parameters_object(&mut cx, reverb.common.name().as_str(), "reverb", &reverb.parameters()),
+                Err(_) => effect_parameters_object(&mut cx, effects::block_name(block).trim_end(), "unknown", &[]),
+            };
+            let _ = js_reverbs.set(&mut cx, index as u32, js_reverb);
         }
+
+        let _ = result.set(&mut cx, "effects", js_effects);
+        let _ = result.set(&mut cx, "reverbs", js_reverbs);
     }
 
-    Ok(cx.empty_array())
+    Ok(result)
 }
 
-fn sampler_reverbs_list(mut cx: FunctionContext) -> JsResult<JsArray> {
-    info!("Entered sampler_reverbs_list...");
-    let reverb_params_block_size: usize = 64;
-    let all_reverb_blocks_size = (50 /* # effects */ + 50 /* number of reverbs */) * reverb_params_block_size; // looks like the data is interleaved
-    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestFXReverb(0, 4, all_reverb_blocks_size as u16, 0)));
+fn request_effect_block_part(selector: u8, number: u16, offset: u16, number_of_bytes: u16) -> Option<Vec<u8>> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestFXReverb(number, selector, number_of_bytes, offset)));
 
-    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(LOAD_SAVE_ENTIRE_VOLUME_RECEIVE_TIMEOUT.clone()) {
-        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-            if let OutgoingSamplerEvent::FXReverb(byte_data) = sampler_event {
-                info!("byte_data.len()={}", byte_data.len());
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::FXReverb(data))) if data.len() == number_of_bytes as usize => Some(data),
+        _ => None,
+    }
+}
 
-                if byte_data.len() == all_reverb_blocks_size {
-                    let reverb_names = cx.empty_array();
+// (number, offset, number of bytes) - reads just part of one block
+fn sampler_effect_block_part(mut cx: FunctionContext, selector: u8) -> JsResult<JsArray> {
+    if let (Ok(number), Ok(offset), Ok(number_of_bytes)) = (cx.argument::<JsNumber>(0), cx.argument::<JsNumber>(1), cx.argument::<JsNumber>(2)) {
+        let number = number.value(&mut cx) as u16;
+        let offset = offset.value(&mut cx) as usize;
+        let number_of_bytes = number_of_bytes.value(&mut cx) as usize;
 
-                    let mut name_index = 0;
-                    for index in (0..all_reverb_blocks_size).step_by(reverb_params_block_size * 2) {
-                        let mut reverb_name_data = vec![];
-                        for letter_position in 0..12 {
-                            reverb_name_data.push(byte_data[index + letter_position]);
-                        }
-                        
-                        let reverb_name = convert_sampler_sysex_name_to_name(&reverb_name_data);
-                        let js_reverb_name = cx.string(reverb_name);
-                        let _ = reverb_names.set(&mut cx, name_index, js_reverb_name);
-                        name_index += 1;
-                    }
+        if offset + number_of_bytes > effects::EFFECT_BLOCK_SIZE || number_of_bytes == 0 {
+            info!("sampler_effect_block_part: {} bytes from {} is outside the {} byte block.", number_of_bytes, offset, effects::EFFECT_BLOCK_SIZE);
+            return Ok(cx.empty_array())
+        }
 
-                    return Ok(reverb_names);
-                }
-                else {
-                    info!("byte_data is not {}", all_reverb_blocks_size);
-                }
+        if let Some(data) = request_effect_block_part(selector, number, offset as u16, number_of_bytes as u16) {
+            let js_data = cx.empty_array();
+            for (index, byte) in data.iter().enumerate() {
+                let js_byte = cx.number(*byte);
+                let _ = js_data.set(&mut cx, index as u32, js_byte);
             }
+
+            return Ok(js_data)
         }
     }
 
     Ok(cx.empty_array())
 }
 
+fn sampler_effect_part(cx: FunctionContext) -> JsResult<JsArray> {
+    info!("Entered sampler_effect_part...");
+    sampler_effect_block_part(cx, effects::EFFECTS_SELECTOR)
+}
+
+fn sampler_reverb_part(cx: FunctionContext) -> JsResult<JsArray> {
+    info!("Entered sampler_reverb_part...");
+    sampler_effect_block_part(cx, effects::REVERBS_SELECTOR)
+}
+
 fn sampler_effect(mut cx: FunctionContext) -> JsResult<JsArray> {
     info!("Entered sampler_effect...");
 
@@ -2383,15 +2945,7 @@ fn sampler_effect_update(mut cx: FunctionContext) -> JsResult<JsBoolean> {
                 }
 
                 // item_number, selector, offset, data
-                let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::ResponseFXReverb(effect_number, 2, 0, changed_data)));
-
-                if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-                    if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                        if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                            return Ok(cx.boolean(success));
-                        }
-                    }
-                }
+                return Ok(cx.boolean(send_sampler_change(IncomingSamplerEvent::ResponseFXReverb(effect_number, 2, 0, changed_data))));
             }
         }
     }
@@ -2417,15 +2971,7 @@ fn sampler_reverb_update(mut cx: FunctionContext) -> JsResult<JsBoolean> {
                 }
 
                 // item number, selector, offset, data
-                let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::ResponseFXReverb(reverb_number, 4, 0, changed_data)));
-
-                if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-                    if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                        if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                            return Ok(cx.boolean(success));
-                        }
-                    }
-                }
+                return Ok(cx.boolean(send_sampler_change(IncomingSamplerEvent::ResponseFXReverb(reverb_number, 4, 0, changed_data))));
             }
         }
     }
@@ -2453,15 +2999,7 @@ fn sampler_effect_update_part(mut cx: FunctionContext) -> JsResult<JsBoolean> {
                     }
 
                     // item_number, selector, offset, data
-                    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::ResponseFXReverb(effect_number, 2, offset, changed_data)));
-
-                    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-                        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                            if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                                return Ok(cx.boolean(success));
-                            }
-                        }
-                    }
+                    return Ok(cx.boolean(send_sampler_change(IncomingSamplerEvent::ResponseFXReverb(effect_number, 2, offset, changed_data))));
                 }
             }
         }
@@ -2490,15 +3028,7 @@ fn sampler_reverb_update_part(mut cx: FunctionContext) -> JsResult<JsBoolean> {
                     }
 
                     // item number, selector, offset, data
-                    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::ResponseFXReverb(reverb_number, 4, offset, changed_data)));
-
-                    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-                        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                            if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                                return Ok(cx.boolean(success));
-                            }
-                        }
-                    }
+                    return Ok(cx.boolean(send_sampler_change(IncomingSamplerEvent::ResponseFXReverb(reverb_number, 4, offset, changed_data))));
                 }
             }
         }
@@ -2575,14 +3105,7 @@ fn sampler_program_effect_assignment(mut cx: FunctionContext) -> JsResult<JsBool
         if let Ok(effect_number) = cx.argument::<JsNumber>(1) {
             let effect_number = effect_number.value(&mut cx) as u8;
     
-            let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::ResponseFXReverb(program_number, 1, 0, vec![effect_number])));
-            if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-                if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                    if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                        return Ok(cx.boolean(success))
-                    }
-                }
-            }
+            return Ok(cx.boolean(send_sampler_change(IncomingSamplerEvent::ResponseFXReverb(program_number, 1, 0, vec![effect_number]))))
         }
     }
 
@@ -2597,14 +3120,7 @@ fn sampler_program_reverb_assignment(mut cx: FunctionContext) -> JsResult<JsBool
         if let Ok(reverb_number) = cx.argument::<JsNumber>(1) {
             let reverb_number = reverb_number.value(&mut cx) as u8;
     
-            let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::ResponseFXReverb(program_number, 3, 0, vec![reverb_number])));
-            if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-                if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                    if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                        return Ok(cx.boolean(success))
-                    }
-                }
-            }
+            return Ok(cx.boolean(send_sampler_change(IncomingSamplerEvent::ResponseFXReverb(program_number, 3, 0, vec![reverb_number]))))
         }
     }
 
@@ -2710,23 +3226,14 @@ fn sampler_save_memory_to_new_volume(mut cx: FunctionContext) -> JsResult<JsBool
             info!("Received reply for VONDSK request");
             if let OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDrivePartitionNumberOfVolumes(value)) = msg {
                 info!("Received data from VONDSK reply: {}", value);
-                let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::ResponseMiscellaneousBytes(4, 1, value as u32, None)));
-                info!("Sent SELVOL request");
-                if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-                    info!("Received SELVOL reply");
-                    if let OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::S1000CommandReply(success)) = msg {
-                        info!("SELVOL success={}", success);
-                        if success {
-                            let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::SaveMemoryToSelectedVolume(save_type)));
-                            if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(LOAD_SAVE_ENTIRE_VOLUME_RECEIVE_TIMEOUT.clone()) {
-                                if let OutgoingEvent::SamplerEvent(s1000_command_reply) = msg {
-                                    if let OutgoingSamplerEvent::S1000CommandReply(success) = s1000_command_reply {
-                                        return Ok(cx.boolean(success))
-                                    }
-                                }
-                            }
-                        }
-                    }
+                let success = send_sampler_change(IncomingSamplerEvent::ResponseMiscellaneousBytes(4, 1, vec![value]));
+                info!("SELVOL success={}", success);
+                if success {
+                    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::SaveMemoryToSelectedVolume(save_type)));
+                    return Ok(cx.boolean(matches!(
+                        OUT_GOING_COMM_CHANNELS.rx.recv_timeout(LOAD_SAVE_ENTIRE_VOLUME_RECEIVE_TIMEOUT),
+                        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::S1000CommandReply(true)))
+                    )))
                 }
             }
         }
@@ -2785,122 +3292,700 @@ struct DirectoryEntry {
     model: u8,
 }
 
+// selector, [start index], [number of entries] - without a number of entries the directory is paged
+// through to its end
 fn sampler_hard_disk_directory_entries(mut cx: FunctionContext) -> JsResult<JsArray> {
     info!("Entered sampler_hard_disk_directory_entries...");
     if let Ok(entry_type) = cx.argument::<JsNumber>(0) {
         let entry_type = entry_type.value(&mut cx) as u8;
+        let start_index = match cx.argument_opt(1) {
+            Some(value) => match value.downcast::<JsNumber, FunctionContext>(&mut cx) {
+                Ok(value) => value.value(&mut cx) as u16,
+                Err(_) => 0,
+            },
+            None => 0,
+        };
+        let number_of_entries_to_get = match cx.argument_opt(2) {
+            Some(value) => match value.downcast::<JsNumber, FunctionContext>(&mut cx) {
+                Ok(value) => Some(value.value(&mut cx) as u16),
+                Err(_) => None,
+            },
+            None => None,
+        };
+
+        info!("entry_type={}, start_index={}, number_of_entries_to_get={:?}", entry_type, start_index, number_of_entries_to_get);
+
+        let entries_data = match number_of_entries_to_get {
+            Some(number_of_entries_to_get) => request_hard_disk_directory_entries(entry_type, start_index, number_of_entries_to_get).map(|(entries, _)| entries),
+            None => request_all_hard_disk_directory_entries(entry_type, start_index),
+        };
+
+        if let Some(entries_data) = entries_data {
+            let entries = cx.empty_array();
+
+            for (index, entry_data) in entries_data.iter().enumerate() {
+                let entry = cx.empty_object();
+                let name = cx.string(entry_data.file_name.clone());
+                let model = cx.number(entry_data.model as f64);
+                let file_type = cx.number(entry_data.file_type);
+
+                let _ = entry.set(&mut cx, "model", model);
+                let _ = entry.set(&mut cx, "file_type", file_type);
+                let _ = entry.set(&mut cx, "name", name);
+
+                let _ = entries.set(&mut cx, index as u32, entry);
+            }
+
+            return Ok(entries)
+        }
+    }
 
-        if let Ok(start_index) = cx.argument::<JsNumber>(1) {
-            let start_index = start_index.value(&mut cx) as u16;
-
-            if let Ok(number_of_entries_to_get) = cx.argument::<JsNumber>(2) {
-                let number_of_entries_to_get = number_of_entries_to_get.value(&mut cx) as u16;
-
-                info!("entry_type={}, start_index={}, number_of_entries_to_get={}", entry_type, start_index, number_of_entries_to_get);
+    Ok(cx.empty_array())
+}
 
-                let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestHardDiskDirEntries(entry_type, start_index, number_of_entries_to_get)));
-                if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(LOAD_SAVE_ENTIRE_VOLUME_RECEIVE_TIMEOUT.clone()) {
-                    if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                        if let OutgoingSamplerEvent::HardDiskDirEntries(entries_data, error) = sampler_event {
-                            let entries = cx.empty_array();
-
-                            for (index, entry_data) in entries_data.iter().enumerate() {
-                                let entry = cx.empty_object();
-                                let name = cx.string(entry_data.file_name.clone());
-                                let model = cx.number(entry_data.model as f64);
-                                let file_type = cx.number(entry_data.file_type);
-                
-                                let _ = entry.set(&mut cx, "model", model);
-                                let _ = entry.set(&mut cx, "file_type", file_type);
-                                let _ = entry.set(&mut cx, "name", name);
+// Entries from start_index on and whether the end of the directory was reached. A reply that is split
+// over several messages is put back together, waiting a short time for each message after the first.
+fn request_hard_disk_directory_entries(selector: u8, start_index: u16, number_of_entries: u16) -> Option<(Vec<DirectoryEntry>, bool)> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestHardDiskDirEntries(selector, start_index, number_of_entries)));
 
-                                let _ = entries.set(&mut cx, index as u32, entry);
-                            }
+    let (mut entries, mut end_of_directory) = match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(LOAD_SAVE_ENTIRE_VOLUME_RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDiskDirEntries(entries, end_of_directory, None))) => (entries, end_of_directory),
+        _ => return None,
+    };
 
-                            return Ok(entries)
-                        }
-                    }
-                }
+    while !end_of_directory && entries.len() < number_of_entries as usize {
+        match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+            Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDiskDirEntries(more_entries, more_end_of_directory, None))) => {
+                entries.extend(more_entries);
+                end_of_directory = more_end_of_directory;
+            }
+            _ => {
+                // nothing more is coming - a short reply means the directory has run out
+                info!("request_hard_disk_directory_entries: {} of {} entries received.", entries.len(), number_of_entries);
+                end_of_directory = true;
             }
         }
     }
 
-    Ok(cx.empty_array())
+    Some((entries, end_of_directory))
 }
 
-fn sampler_request_resident_program_names(mut cx: FunctionContext) -> JsResult<JsArray> {
-    info!("Entered sampler_request_resident_program_names...");
-    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestResidentProgramNames));
-    let resident_program_names = cx.empty_array();
+// Pages through a directory from start_index until the sampler says it has run out
+fn request_all_hard_disk_directory_entries(selector: u8, start_index: u16) -> Option<Vec<DirectoryEntry>> {
+    let mut entries = vec![];
+    let mut page_start_index = start_index;
 
-    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-            if let OutgoingSamplerEvent::ResidentProgramNames(program_names, error) = sampler_event {
-                if error == None {
-                    for (index, name) in program_names.iter().enumerate() {
-                        let js_name = cx.string(name);
-                        let _ = resident_program_names.set(&mut cx, index as u32, js_name);
-                    }
-                }
-            }
+    loop {
+        let (page, end_of_directory) = request_hard_disk_directory_entries(selector, page_start_index, DIRECTORY_PAGE_SIZE)?;
+        let number_in_page = page.len();
+
+        entries.extend(page);
+        if end_of_directory || number_in_page < DIRECTORY_PAGE_SIZE as usize {
+            break
         }
+        page_start_index += DIRECTORY_PAGE_SIZE;
     }
 
-    Ok(resident_program_names)
+    Some(entries)
 }
 
-fn sampler_request_resident_sample_names(mut cx: FunctionContext) -> JsResult<JsArray> {
-    info!("Entered sampler_request_resident_sample_names...");
-    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestResidentSampleNames));
-    let resident_sample_names = cx.empty_array();
+fn request_selected_partition() -> Option<u8> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::HardDriveSelectedPartition));
 
-    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-            if let OutgoingSamplerEvent::ResidentSampleNames(sample_names, error) = sampler_event {
-                if error == None {
-                    for (index, name) in sample_names.iter().enumerate() {
-                        let js_name = cx.string(name);
-                        let _ = resident_sample_names.set(&mut cx, index as u32, js_name);
-                    }
-                }
-            }
-        }
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDriveSelectedPartition(partition))) => Some(partition),
+        _ => None,
     }
+}
 
-    Ok(resident_sample_names)
+fn request_selected_volume() -> Option<u8> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::HardDrivePartitionSelectedVolume));
+
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDrivePartitionSelectedVolume(volume))) => Some(volume),
+        _ => None,
+    }
 }
 
-fn sampler_status_report(mut cx: FunctionContext) -> JsResult<JsObject> {
-    info!("Entered sampler_status_report...");
-    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::StatusReport));
-    let sampler_status_report = cx.empty_object();
+// name, active and volume type of a volume in the selected partition
+fn request_volume_list_entry(volume: u16) -> Option<(String, bool, u8)> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestVolumeList(volume)));
 
-    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-            if let OutgoingSamplerEvent::StatusReport(status_data, error) = sampler_event {
-                if error == None {
-                    let keys = status_data.keys().sorted();
-                    for name in keys.into_iter() {
-                        if let Some(value) = status_data.get(name) {
-                            let js_name = cx.string(name);
-                            let js_value = cx.number(*value);
-                            let _ = sampler_status_report.set(&mut cx, js_name, js_value);
-                        }
-                    }
-                }
-            }
-        }
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::VolumeList(name, None, active, volume_type))) => Some((name, active, volume_type)),
+        _ => None,
     }
+}
 
-    Ok(sampler_status_report)
+fn request_volume_name(volume: u16) -> Option<String> {
+    request_volume_list_entry(volume).map(|(name, _, _)| name)
 }
 
-fn sampler_s1000_miscellaneous_data(mut cx: FunctionContext) -> JsResult<JsObject> {
-    info!("Entered sampler_s1000_miscellaneous_data...");
-    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestS1000MiscellaneousData));
-    let sampler_status_report = cx.empty_object();
-    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
+fn request_number_of_partitions() -> Option<u8> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::HardDriveNumberOfPartitions));
+
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDriveNumberOfPartitions(number_of_partitions))) => Some(number_of_partitions),
+        _ => None,
+    }
+}
+
+fn request_number_of_volumes() -> Option<u8> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::HardDrivePartitionNumberOfVolumes));
+
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDrivePartitionNumberOfVolumes(number_of_volumes))) => Some(number_of_volumes),
+        _ => None,
+    }
+}
+
+fn select_partition(partition: u8) -> bool {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::SelectHardDrivePartition(partition)));
+
+    matches!(
+        OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT),
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::S1000CommandReply(true)))
+    )
+}
+
+fn select_volume(volume: u8) -> bool {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::SelectHardDriveVolume(volume)));
+
+    matches!(
+        OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT),
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::S1000CommandReply(true)))
+    )
+}
+
+fn request_resident_program_names() -> Option<Vec<String>> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestResidentProgramNames));
+
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::ResidentProgramNames(program_names, None))) => Some(program_names),
+        _ => None,
+    }
+}
+
+fn request_program_header(program_number: u16) -> Option<Vec<u8>> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestProgramHeader(program_number)));
+
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::ProgramHeader(data))) => Some(data),
+        _ => None,
+    }
+}
+
+// the partition and volume currently selected on the sampler's hard disk
+fn selected_library_location() -> Option<library::LibraryLocation> {
+    let partition = request_selected_partition()?;
+    let volume = request_selected_volume()?;
+    let volume_name = request_volume_name(volume as u16)?;
+
+    Some(library::LibraryLocation { source: library::SAMPLER_SOURCE.to_string(), partition, volume, volume_name: volume_name.trim_end().to_string() })
+}
+
+const DIRECTORY_PAGE_SIZE: u16 = 16;
+const DIRECTORY_SELECTORS: [u8; 6] = [1, 2, 3, 4, 5, 6]; // programs, samples, cue lists, take lists, effects, drum inputs
+
+// every file of every kind in the selected volume
+fn request_volume_directory() -> Option<Vec<hard_disk::FileNode>> {
+    let mut files = vec![];
+
+    for selector in DIRECTORY_SELECTORS.iter() {
+        let entries = request_all_hard_disk_directory_entries(*selector, 0)?;
+
+        files.extend(entries.into_iter().map(|entry| hard_disk::FileNode {
+            name: entry.file_name.trim_end().to_string(),
+            kind: library::FileKind::from_selector(*selector),
+            file_type: entry.file_type,
+            model: entry.model,
+        }));
+    }
+
+    Some(files)
+}
+
+// Walks every partition and volume. The progress callback is told the partitions done so far and can
+// stop the crawl early. Whatever happens the original partition and volume are selected again.
+fn crawl_hard_disk(progress: &mut dyn FnMut(i32, i32) -> bool) -> Option<Vec<hard_disk::PartitionNode>> {
+    let original_partition = request_selected_partition()?;
+    let original_volume = request_selected_volume()?;
+    let number_of_partitions = request_number_of_partitions()?;
+    let mut partitions = vec![];
+
+    for partition in 0..number_of_partitions {
+        if !progress(partition as i32, number_of_partitions as i32) {
+            info!("crawl_hard_disk: stopped after {} of {} partitions.", partition, number_of_partitions);
+            break
+        }
+
+        partitions.push(crawl_partition(partition));
+    }
+    if partitions.len() == number_of_partitions as usize {
+        progress(number_of_partitions as i32, number_of_partitions as i32);
+    }
+
+    if !select_partition(original_partition) || !select_volume(original_volume) {
+        info!("crawl_hard_disk: could not select partition {} volume {} again.", original_partition, original_volume);
+    }
+
+    Some(partitions)
+}
+
+fn crawl_partition(partition: u8) -> hard_disk::PartitionNode {
+    let mut partition_node = hard_disk::PartitionNode { partition, volumes: vec![], error: None };
+
+    if !select_partition(partition) {
+        partition_node.error = Some("the partition could not be selected".to_string());
+        return partition_node
+    }
+
+    match request_number_of_volumes() {
+        Some(number_of_volumes) => partition_node.volumes = (0..number_of_volumes).map(crawl_volume).collect(),
+        None => partition_node.error = Some("the number of volumes could not be read".to_string()),
+    }
+
+    partition_node
+}
+
+fn crawl_volume(volume: u8) -> hard_disk::VolumeNode {
+    let (name, active, volume_type) = request_volume_list_entry(volume as u16).unwrap_or_default();
+    let mut volume_node = hard_disk::VolumeNode { volume, name: name.trim_end().to_string(), active, volume_type, files: vec![], error: None };
+
+    if !select_volume(volume) {
+        volume_node.error = Some("the volume could not be selected".to_string());
+        return volume_node
+    }
+
+    match request_volume_directory() {
+        Some(files) => volume_node.files = files,
+        None => volume_node.error = Some("the directory entries could not be read".to_string()),
+    }
+
+    volume_node
+}
+
+fn hard_disk_tree_array<'a>(cx: &mut FunctionContext<'a>, partitions: &[hard_disk::PartitionNode]) -> JsResult<'a, JsArray> {
+    let js_partitions = cx.empty_array();
+
+    for (partition_index, partition) in partitions.iter().enumerate() {
+        let js_partition = cx.empty_object();
+        let partition_number = cx.number(partition.partition);
+        let _ = js_partition.set(cx, "partition", partition_number);
+        if let Some(error) = partition.error.as_ref() {
+            let error = cx.string(error);
+            let _ = js_partition.set(cx, "error", error);
+        }
+
+        let js_volumes = cx.empty_array();
+        for (volume_index, volume) in partition.volumes.iter().enumerate() {
+            let js_volume = cx.empty_object();
+            let volume_number = cx.number(volume.volume);
+            let name = cx.string(volume.name.as_str());
+            let active = cx.boolean(volume.active);
+            let volume_type = cx.number(volume.volume_type);
+            let _ = js_volume.set(cx, "volume", volume_number);
+            let _ = js_volume.set(cx, "name", name);
+            let _ = js_volume.set(cx, "active", active);
+            let _ = js_volume.set(cx, "type", volume_type);
+            if let Some(error) = volume.error.as_ref() {
+                let error = cx.string(error);
+                let _ = js_volume.set(cx, "error", error);
+            }
+
+            let js_files = cx.empty_array();
+            for (file_index, file) in volume.files.iter().enumerate() {
+                let js_file = cx.empty_object();
+                let name = cx.string(file.name.as_str());
+                let kind = cx.string(file.kind.name());
+                let file_type = cx.number(file.file_type);
+                let model = cx.number(file.model);
+                let _ = js_file.set(cx, "name", name);
+                let _ = js_file.set(cx, "kind", kind);
+                let _ = js_file.set(cx, "file_type", file_type);
+                let _ = js_file.set(cx, "model", model);
+                let _ = js_files.set(cx, file_index as u32, js_file);
+            }
+            let _ = js_volume.set(cx, "files", js_files);
+            let _ = js_volumes.set(cx, volume_index as u32, js_volume);
+        }
+        let _ = js_partition.set(cx, "volumes", js_volumes);
+        let _ = js_partitions.set(cx, partition_index as u32, js_partition);
+    }
+
+    Ok(js_partitions)
+}
+
+// [{partition, volumes: [{volume, name, active, type, files: [{name, kind, file_type, model}]}]}] - with
+// record_in_library every volume read is also recorded in the open library
+fn sampler_crawl_hard_disk(mut cx: FunctionContext) -> JsResult<JsArray> {
+    info!("Entered sampler_crawl_hard_disk...");
+    let record_in_library = match cx.argument_opt(0) {
+        Some(value) => match value.downcast::<JsBoolean, FunctionContext>(&mut cx) {
+            Ok(value) => value.value(&mut cx),
+            Err(_) => false,
+        },
+        None => false,
+    };
+    let progress_callback = progress_callback_argument(&mut cx, 1);
+
+    let mut progress = |partitions_done: i32, number_of_partitions: i32| {
+        call_progress_callback(&mut cx, progress_callback, partitions_done, number_of_partitions)
+    };
+
+    let partitions = match crawl_hard_disk(&mut progress) {
+        Some(partitions) => partitions,
+        None => {
+            info!("sampler_crawl_hard_disk: could not read the hard disk selection.");
+            return Ok(cx.empty_array())
+        }
+    };
+
+    if record_in_library {
+        let result = library::with_library(|library| {
+            for partition in partitions.iter() {
+                for volume in partition.volumes.iter().filter(|volume| volume.error.is_none()) {
+                    let location = volume.location(partition.partition);
+                    library.record_volume(&location, &hard_disk::library_entries(&location, &volume.files))?;
+                }
+            }
+            Ok(())
+        });
+
+        match result {
+            Some(Err(error)) => info!("sampler_crawl_hard_disk: {}", error),
+            None => info!("sampler_crawl_hard_disk: no library is open."),
+            _ => (),
+        }
+    }
+
+    hard_disk_tree_array(&mut cx, &partitions)
+}
+
+fn library_entry_object<'a>(cx: &mut FunctionContext<'a>, entry: &library::LibraryEntry) -> JsResult<'a, JsObject> {
+    let object = cx.empty_object();
+
+    let source = cx.string(entry.location.source.as_str());
+    let partition = cx.number(entry.location.partition);
+    let volume = cx.number(entry.location.volume);
+    let volume_name = cx.string(entry.location.volume_name.as_str());
+    let name = cx.string(entry.name.as_str());
+    let kind = cx.string(entry.kind.name());
+    let last_seen = cx.number(entry.last_seen as f64);
+    let _ = object.set(cx, "source", source);
+    let _ = object.set(cx, "partition", partition);
+    let _ = object.set(cx, "volume", volume);
+    let _ = object.set(cx, "volume_name", volume_name);
+    let _ = object.set(cx, "name", name);
+    let _ = object.set(cx, "kind", kind);
+    let _ = object.set(cx, "last_seen", last_seen);
+
+    let numbers = [
+        ("file_type", entry.file_type.map(|value| value as f64)),
+        ("model", entry.model.map(|value| value as f64)),
+        ("sample_rate", entry.sample_rate.map(|value| value as f64)),
+        ("sample_length", entry.sample_length.map(|value| value as f64)),
+        ("original_pitch", entry.original_pitch.map(|value| value as f64)),
+        ("number_of_loops", entry.number_of_loops.map(|value| value as f64)),
+        ("number_of_keygroups", entry.number_of_keygroups.map(|value| value as f64)),
+    ];
+    for (key, value) in numbers.iter() {
+        if let Some(value) = value {
+            let value = cx.number(*value);
+            let _ = object.set(cx, *key, value);
+        }
+    }
+
+    if let Some(fingerprint) = entry.fingerprint.as_ref() {
+        let fingerprint = cx.string(fingerprint.as_str());
+        let _ = object.set(cx, "fingerprint", fingerprint);
+    }
+
+    Ok(object)
+}
+
+fn sampler_library_open(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_library_open...");
+
+    if let Ok(path) = cx.argument::<JsString>(0) {
+        let path = path.value(&mut cx);
+
+        match library::open_library(path.as_str()) {
+            Ok(_) => return Ok(cx.boolean(true)),
+            Err(error) => info!("sampler_library_open: could not open {}: {}", path, error),
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
+// records the files in the selected volume - with rescan anything no longer there is dropped
+fn sampler_library_index_volume(mut cx: FunctionContext) -> JsResult<JsNumber> {
+    info!("Entered sampler_library_index_volume...");
+    let rescan = match cx.argument_opt(0) {
+        Some(value) => match value.downcast::<JsBoolean, FunctionContext>(&mut cx) {
+            Ok(value) => value.value(&mut cx),
+            Err(_) => false,
+        },
+        None => false,
+    };
+
+    let location = match selected_library_location() {
+        Some(location) => location,
+        None => {
+            info!("sampler_library_index_volume: could not read the selected partition and volume.");
+            return Ok(cx.number(-1))
+        }
+    };
+    let directory = match request_volume_directory() {
+        Some(directory) => directory,
+        None => {
+            info!("sampler_library_index_volume: could not read the directory entries.");
+            return Ok(cx.number(-1))
+        }
+    };
+
+    let entries = hard_disk::library_entries(&location, &directory);
+
+    let result = library::with_library(|library| {
+        if rescan {
+            library.record_volume(&location, &entries)
+        }
+        else {
+            library.record_all(&entries)
+        }
+    });
+
+    match result {
+        Some(Ok(_)) => Ok(cx.number(entries.len() as f64)),
+        Some(Err(error)) => {
+            info!("sampler_library_index_volume: {}", error);
+            Ok(cx.number(-1))
+        }
+        None => {
+            info!("sampler_library_index_volume: no library is open.");
+            Ok(cx.number(-1))
+        }
+    }
+}
+
+// records the header fields of everything in memory under the memory location, replacing whatever was
+// resident last time - fingerprints need every sample downloading so they are optional
+fn sampler_library_index_resident(mut cx: FunctionContext) -> JsResult<JsNumber> {
+    info!("Entered sampler_library_index_resident...");
+    let with_fingerprints = match cx.argument_opt(0) {
+        Some(value) => match value.downcast::<JsBoolean, FunctionContext>(&mut cx) {
+            Ok(value) => value.value(&mut cx),
+            Err(_) => false,
+        },
+        None => false,
+    };
+    let progress_callback = progress_callback_argument(&mut cx, 1);
+
+    let location = library::LibraryLocation::memory();
+    let mut entries = vec![];
+
+    for (program_number, program_name) in request_resident_program_names().unwrap_or_default().iter().enumerate() {
+        if let Some(data) = request_program_header(program_number as u16) {
+            entries.push(library::LibraryEntry::program(location.clone(), program_name.as_str(), &data));
+        }
+    }
+
+    let sample_names = request_resident_sample_names().unwrap_or_default();
+    for sample_number in 0..sample_names.len() {
+        let sample_number = sample_number as u16;
+        let entry = if with_fingerprints {
+            let mut progress = |packets_received: i32, packets_expected: i32| {
+                call_progress_callback(&mut cx, progress_callback, packets_received, packets_expected)
+            };
+            download_sample(sample_number, &mut progress).and_then(|(data, _, samples)| {
+                library::LibraryEntry::sample(location.clone(), &data, Some(library::audio_fingerprint(&samples)))
+            })
+        }
+        else {
+            request_sample_header(sample_number).and_then(|data| library::LibraryEntry::sample(location.clone(), &data, None))
+        };
+
+        match entry {
+            Some(entry) => entries.push(entry),
+            None => info!("sampler_library_index_resident: could not read sample {}.", sample_number),
+        }
+    }
+
+    match library::with_library(|library| library.record_volume(&location, &entries)) {
+        Some(Ok(_)) => Ok(cx.number(entries.len() as f64)),
+        Some(Err(error)) => {
+            info!("sampler_library_index_resident: {}", error);
+            Ok(cx.number(-1))
+        }
+        None => {
+            info!("sampler_library_index_resident: no library is open.");
+            Ok(cx.number(-1))
+        }
+    }
+}
+
+// entries read from somewhere else, e.g. a disk image - the same fields sampler_library_find returns,
+// with the kind worked out from file_type when it is missing
+fn sampler_library_add_entries(mut cx: FunctionContext) -> JsResult<JsNumber> {
+    info!("Entered sampler_library_add_entries...");
+    let mut entries = vec![];
+
+    if let Ok(js_entries) = cx.argument::<JsArray>(0) {
+        for js_entry in js_entries.to_vec(&mut cx)?.iter() {
+            let js_entry = match js_entry.downcast::<JsObject, FunctionContext>(&mut cx) {
+                Ok(js_entry) => js_entry,
+                Err(_) => continue,
+            };
+
+            let file_type = number_property(&mut cx, js_entry, "file_type")?.map(|value| value as u8);
+            let kind = string_property(&mut cx, js_entry, "kind")?.map(|kind| library::FileKind::new(kind.as_str())).or_else(|| file_type.map(library::FileKind::from_file_type));
+            let (source, name, kind) = match (string_property(&mut cx, js_entry, "source")?, string_property(&mut cx, js_entry, "name")?, kind) {
+                (Some(source), Some(name), Some(kind)) => (source, name, kind),
+                _ => {
+                    info!("sampler_library_add_entries: entries need a source, a name and a kind or file_type.");
+                    return Ok(cx.number(-1))
+                }
+            };
+            let location = library::LibraryLocation {
+                source,
+                partition: number_property(&mut cx, js_entry, "partition")?.unwrap_or(0.0) as u8,
+                volume: number_property(&mut cx, js_entry, "volume")?.unwrap_or(0.0) as u8,
+                volume_name: string_property(&mut cx, js_entry, "volume_name")?.unwrap_or_default(),
+            };
+
+            let mut entry = library::LibraryEntry::new(location, name.as_str(), kind);
+            entry.file_type = file_type;
+            entry.model = number_property(&mut cx, js_entry, "model")?.map(|value| value as u8);
+            entry.sample_rate = number_property(&mut cx, js_entry, "sample_rate")?.map(|value| value as u32);
+            entry.sample_length = number_property(&mut cx, js_entry, "sample_length")?.map(|value| value as u32);
+            entry.original_pitch = number_property(&mut cx, js_entry, "original_pitch")?.map(|value| value as u8);
+            entry.number_of_loops = number_property(&mut cx, js_entry, "number_of_loops")?.map(|value| value as u8);
+            entry.number_of_keygroups = number_property(&mut cx, js_entry, "number_of_keygroups")?.map(|value| value as u8);
+            entry.fingerprint = string_property(&mut cx, js_entry, "fingerprint")?;
+
+            entries.push(entry);
+        }
+    }
+
+    match library::with_library(|library| library.record_all(&entries)) {
+        Some(Ok(_)) => Ok(cx.number(entries.len() as f64)),
+        Some(Err(error)) => {
+            info!("sampler_library_add_entries: {}", error);
+            Ok(cx.number(-1))
+        }
+        None => {
+            info!("sampler_library_add_entries: no library is open.");
+            Ok(cx.number(-1))
+        }
+    }
+}
+
+// the query is {name, kind, source, partition, volume, sample_rate, fingerprint} - all optional, '*' is a wildcard in name
+fn sampler_library_find(mut cx: FunctionContext) -> JsResult<JsArray> {
+    info!("Entered sampler_library_find...");
+    let results = cx.empty_array();
+    let mut query = library::LibraryQuery::default();
+
+    if let Some(value) = cx.argument_opt(0) {
+        if let Ok(js_query) = value.downcast::<JsObject, FunctionContext>(&mut cx) {
+            query.name = string_property(&mut cx, js_query, "name")?;
+            query.kind = string_property(&mut cx, js_query, "kind")?.map(|kind| library::FileKind::new(kind.as_str()));
+            query.source = string_property(&mut cx, js_query, "source")?;
+            query.partition = number_property(&mut cx, js_query, "partition")?.map(|value| value as u8);
+            query.volume = number_property(&mut cx, js_query, "volume")?.map(|value| value as u8);
+            query.sample_rate = number_property(&mut cx, js_query, "sample_rate")?.map(|value| value as u32);
+            query.fingerprint = string_property(&mut cx, js_query, "fingerprint")?;
+        }
+    }
+
+    match library::with_library(|library| library.find(&query)) {
+        Some(Ok(entries)) => {
+            for (index, entry) in entries.iter().enumerate() {
+                let object = library_entry_object(&mut cx, entry)?;
+                let _ = results.set(&mut cx, index as u32, object);
+            }
+        }
+        Some(Err(error)) => info!("sampler_library_find: {}", error),
+        None => info!("sampler_library_find: no library is open."),
+    }
+
+    Ok(results)
+}
+
+fn sampler_request_resident_program_names(mut cx: FunctionContext) -> JsResult<JsArray> {
+    info!("Entered sampler_request_resident_program_names...");
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestResidentProgramNames));
+    let resident_program_names = cx.empty_array();
+
+    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
+        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
+            if let OutgoingSamplerEvent::ResidentProgramNames(program_names, error) = sampler_event {
+                if error == None {
+                    for (index, name) in program_names.iter().enumerate() {
+                        let js_name = cx.string(name);
+                        let _ = resident_program_names.set(&mut cx, index as u32, js_name);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(resident_program_names)
+}
+
+fn sampler_request_resident_sample_names(mut cx: FunctionContext) -> JsResult<JsArray> {
+    info!("Entered sampler_request_resident_sample_names...");
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestResidentSampleNames));
+    let resident_sample_names = cx.empty_array();
+
+    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
+        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
+            if let OutgoingSamplerEvent::ResidentSampleNames(sample_names, error) = sampler_event {
+                if error == None {
+                    for (index, name) in sample_names.iter().enumerate() {
+                        let js_name = cx.string(name);
+                        let _ = resident_sample_names.set(&mut cx, index as u32, js_name);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(resident_sample_names)
+}
+
+fn sampler_status_report(mut cx: FunctionContext) -> JsResult<JsObject> {
+    info!("Entered sampler_status_report...");
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::StatusReport));
+    let sampler_status_report = cx.empty_object();
+
+    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
+        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
+            if let OutgoingSamplerEvent::StatusReport(status_data, error) = sampler_event {
+                if error == None {
+                    let keys = status_data.keys().sorted();
+                    for name in keys.into_iter() {
+                        if let Some(value) = status_data.get(name) {
+                            let js_name = cx.string(name);
+                            let js_value = cx.number(*value);
+                            let _ = sampler_status_report.set(&mut cx, js_name, js_value);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(sampler_status_report)
+}
+
+fn sampler_s1000_miscellaneous_data(mut cx: FunctionContext) -> JsResult<JsObject> {
+    info!("Entered sampler_s1000_miscellaneous_data...");
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestS1000MiscellaneousData));
+    let sampler_status_report = cx.empty_object();
+    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
+        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
             if let OutgoingSamplerEvent::S1000MiscellaneousData(misc_data, error) = sampler_event {
                 if error == None {
                     for (name, value) in misc_data.iter() {
@@ -2925,9 +4010,15 @@ fn sampler_request_miscellaneous_bytes(mut cx: FunctionContext) -> JsResult<JsNu
             let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestMiscellaneousBytes(data_index, data_bank_number)));
             if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
                 if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                    if let OutgoingSamplerEvent::MiscellaneousBytes(value, _) = sampler_event {
+                    if let OutgoingSamplerEvent::MiscellaneousBytes(misc_data::MiscValue::Number(value)) = sampler_event {
                         return Ok(cx.number(value as f64))
                     }
+                    else if let OutgoingSamplerEvent::HardDriveNumberOfPartitions(value)
+                        | OutgoingSamplerEvent::HardDriveSelectedPartition(value)
+                        | OutgoingSamplerEvent::HardDrivePartitionNumberOfVolumes(value)
+                        | OutgoingSamplerEvent::HardDrivePartitionSelectedVolume(value) = sampler_event {
+                        return Ok(cx.number(value))
+                    }
                 }
             }
         }
@@ -2940,126 +4031,2134 @@ fn sampler_request_miscellaneous_bytes_name(mut cx: FunctionContext) -> JsResult
     info!("Entered sampler_request_miscellaneous_bytes_name...");
     if let Ok(data_index) = cx.argument::<JsNumber>(0) {
         let data_index = data_index.value(&mut cx) as u16;
-        let data_bank_number = 6; // 12 byte name values data bank
+        let data_bank_number = misc_data::NAME_BANK;
         let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestMiscellaneousBytes(data_index, data_bank_number)));
-        if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-            if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                if let OutgoingSamplerEvent::MiscellaneousBytes(_, name) = sampler_event {
-                    if let Some(name) = name {
+        if let Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::MiscellaneousBytes(misc_data::MiscValue::Name(name)))) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+            return Ok(cx.string(name))
+        }
+    }
+
+    Ok(cx.string(""))
+}
+
+fn sampler_request_miscellaneous_bytes_update_name(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_request_miscellaneous_bytes_update_name...");
+    if let Ok(data_index) = cx.argument::<JsNumber>(0) {
+        let data_index = data_index.value(&mut cx) as u16;
+
+        if let Ok(name) = cx.argument::<JsString>(1) {
+            let name = name.value(&mut cx);
+            let data_bank_number = 6; // 12 byte name values data bank
+            let sampler_name = match validated_name(name.as_str()) {
+                Ok(name) => name.to_sysex(),
+                Err(error) => {
+                    info!("sampler_request_miscellaneous_bytes_update_name: {}", error);
+                    return Ok(cx.boolean(false))
+                }
+            };
+
+            return Ok(cx.boolean(send_sampler_change(IncomingSamplerEvent::ResponseMiscellaneousBytes(data_index, data_bank_number, sampler_name))))
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
+fn sampler_request_cuelist_file_name(mut cx: FunctionContext) -> JsResult<JsString> {
+    info!("Entered sampler_request_cuelist_file_name...");
+    let data_index = 0; // always 0 for the header
+    let selector = 0; // header
+    let offset = 3; // 12 byte name offset
+    let number_of_bytes_of_data = 12;
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestCueList(data_index, selector, offset, number_of_bytes_of_data)));
+    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
+        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
+            if let OutgoingSamplerEvent::CueListName(name) = sampler_event {
+                return Ok(cx.string(name))
+            }
+        }
+    }
+
+    Ok(cx.string(""))
+}
+
+fn sampler_request_take_list_file_name(mut cx: FunctionContext) -> JsResult<JsString> {
+    info!("Entered sampler_request_take_list_file_name...");
+    let data_index = 0; // always 0 for the header
+    let selector = 0; // header
+    let offset = 3; // 12 byte name offset
+    let number_of_bytes_of_data = 12;
+        let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestTakeList(data_index, selector, offset, number_of_bytes_of_data)));
+    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
+        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
+            if let OutgoingSamplerEvent::TakeListName(name) = sampler_event {
+                return Ok(cx.string(name))
+            }
+        }
+    }
+
+    Ok(cx.string(""))
+}
+
+fn request_fx_file_name() -> Option<String> {
+    let data_index = 0; // always 0 for the header
+    let number_of_bytes_of_data = 12;
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestFXReverb(data_index, effects::HEADER_SELECTOR, number_of_bytes_of_data, effects::HEADER_FILENAME_OFFSET)));
+
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::FXReverbFilename(name))) => Some(name),
+        _ => None,
+    }
+}
+
+fn sampler_request_fx_file_name(mut cx: FunctionContext) -> JsResult<JsString> {
+    info!("Entered sampler_request_fx_file_name...");
+
+    Ok(cx.string(request_fx_file_name().unwrap_or_default()))
+}
+
+fn sampler_request_miscellaneous_bytes_update(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_request_miscellaneous_bytes_update...");
+    if let Ok(data_index) = cx.argument::<JsNumber>(0) {
+        let data_index = data_index.value(&mut cx) as u16;
+        if let Ok(data_bank_number) = cx.argument::<JsNumber>(1) {
+            let data_bank_number = data_bank_number.value(&mut cx) as u8;
+            if let Ok(changed_value) = cx.argument::<JsNumber>(2) {
+                let changed_value = changed_value.value(&mut cx) as u64;
+                let data = match misc_data::encode(data_bank_number, &misc_data::MiscValue::Number(changed_value)) {
+                    Ok(data) => data,
+                    Err(error) => {
+                        info!("sampler_request_miscellaneous_bytes_update: {}", error);
+                        return Ok(cx.boolean(false))
+                    }
+                };
+
+                return Ok(cx.boolean(send_sampler_change(IncomingSamplerEvent::ResponseMiscellaneousBytes(data_index, data_bank_number, data))));
+            }
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
+
+fn request_misc_value(data_index: u16, data_bank_number: u8) -> Option<misc_data::MiscValue> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestMiscellaneousBytes(data_index, data_bank_number)));
+
+    // the hard disk entries of bank 1 come back as their own events
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::MiscellaneousBytes(value))) => Some(value),
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDriveNumberOfPartitions(value)))
+        | Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDriveSelectedPartition(value)))
+        | Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDrivePartitionNumberOfVolumes(value)))
+        | Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::HardDrivePartitionSelectedVolume(value))) => Some(misc_data::MiscValue::Number(value as u64)),
+        _ => None,
+    }
+}
+
+fn get_misc(name: &str) -> Result<Option<misc_data::MiscValue>, misc_data::MiscDataError> {
+    let entry = misc_data::lookup(name)?;
+    entry.check_readable()?;
+
+    Ok(request_misc_value(entry.index, entry.bank))
+}
+
+fn set_misc(name: &str, value: &misc_data::MiscValue) -> Result<bool, misc_data::MiscDataError> {
+    let entry = misc_data::lookup(name)?;
+    entry.check_value(value)?;
+    let data = misc_data::encode(entry.bank, value)?;
+
+    Ok(send_sampler_change(IncomingSamplerEvent::ResponseMiscellaneousBytes(entry.index, entry.bank, data)))
+}
+
+// [{name, bank, index, width, minimum, maximum, access, description}]
+fn sampler_misc_data_registry(mut cx: FunctionContext) -> JsResult<JsArray> {
+    let entries = cx.empty_array();
+
+    for (index, entry) in misc_data::MISC_DATA.iter().enumerate() {
+        let js_entry = cx.empty_object();
+        let name = cx.string(entry.name);
+        let bank = cx.number(entry.bank);
+        let data_index = cx.number(entry.index);
+        let width = cx.number(entry.width());
+        let minimum = cx.number(entry.minimum as f64);
+        let maximum = cx.number(entry.maximum as f64);
+        let access = cx.string(entry.access.name());
+        let description = cx.string(entry.description);
+
+        let _ = js_entry.set(&mut cx, "name", name);
+        let _ = js_entry.set(&mut cx, "bank", bank);
+        let _ = js_entry.set(&mut cx, "index", data_index);
+        let _ = js_entry.set(&mut cx, "width", width);
+        let _ = js_entry.set(&mut cx, "minimum", minimum);
+        let _ = js_entry.set(&mut cx, "maximum", maximum);
+        let _ = js_entry.set(&mut cx, "access", access);
+        let _ = js_entry.set(&mut cx, "description", description);
+        let _ = entries.set(&mut cx, index as u32, js_entry);
+    }
+
+    Ok(entries)
+}
+
+// e.g. sampler_get_misc("SELVOL") - -1 if the name is unknown, cannot be read, is not a number or the sampler does not answer
+fn sampler_get_misc(mut cx: FunctionContext) -> JsResult<JsNumber> {
+    info!("Entered sampler_get_misc...");
+    if let Ok(name) = cx.argument::<JsString>(0) {
+        let name = name.value(&mut cx);
+
+        match get_misc(name.as_str()) {
+            Ok(Some(misc_data::MiscValue::Number(value))) => return Ok(cx.number(value as f64)),
+            Ok(Some(value)) => info!("sampler_get_misc: {} is not a number - {}", name, value),
+            Ok(None) => info!("sampler_get_misc: no reply for {}.", name),
+            Err(error) => info!("sampler_get_misc: {}", error),
+        }
+    }
+
+    Ok(cx.number(-1))
+}
+
+fn sampler_set_misc(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_set_misc...");
+    if let (Ok(name), Ok(value)) = (cx.argument::<JsString>(0), cx.argument::<JsNumber>(1)) {
+        let name = name.value(&mut cx);
+        let value = value.value(&mut cx);
+
+        if value < 0.0 || value.fract() != 0.0 {
+            info!("sampler_set_misc: {} is not a whole number.", value);
+            return Ok(cx.boolean(false))
+        }
+
+        match set_misc(name.as_str(), &misc_data::MiscValue::Number(value as u64)) {
+            Ok(success) => return Ok(cx.boolean(success)),
+            Err(error) => info!("sampler_set_misc: {}", error),
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
+fn sampler_get_misc_name(mut cx: FunctionContext) -> JsResult<JsString> {
+    info!("Entered sampler_get_misc_name...");
+    if let Ok(name) = cx.argument::<JsString>(0) {
+        let name = name.value(&mut cx);
+
+        match get_misc(name.as_str()) {
+            Ok(Some(misc_data::MiscValue::Name(value))) => return Ok(cx.string(value)),
+            Ok(Some(value)) => info!("sampler_get_misc_name: {} is not a name - {}", name, value),
+            Ok(None) => info!("sampler_get_misc_name: no reply for {}.", name),
+            Err(error) => info!("sampler_get_misc_name: {}", error),
+        }
+    }
+
+    Ok(cx.string(""))
+}
+
+fn sampler_set_misc_name(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_set_misc_name...");
+    if let (Ok(name), Ok(value)) = (cx.argument::<JsString>(0), cx.argument::<JsString>(1)) {
+        let name = name.value(&mut cx);
+        let value = sampler_name::transliterate(value.value(&mut cx).as_str());
+
+        match set_misc(name.as_str(), &misc_data::MiscValue::Name(value)) {
+            Ok(success) => return Ok(cx.boolean(success)),
+            Err(error) => info!("sampler_set_misc_name: {}", error),
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
+// selector 2 for effects, 4 for reverbs
+fn request_effect_block(selector: u8, number: u16) -> Option<Vec<u8>> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestFXReverb(number, selector, effects::EFFECT_BLOCK_SIZE as u16, 0)));
+
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(LOAD_SAVE_ENTIRE_VOLUME_RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::FXReverb(data))) if data.len() == effects::EFFECT_BLOCK_SIZE => Some(data),
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::FXReverb(data))) => {
+            info!("request_effect_block: expected {} bytes but got {}.", effects::EFFECT_BLOCK_SIZE, data.len());
+            None
+        }
+        _ => None,
+    }
+}
+
+fn send_effect_block(selector: u8, number: u16, data: Vec<u8>) -> bool {
+    send_sampler_change(IncomingSamplerEvent::ResponseFXReverb(number, selector, 0, data))
+}
+
+fn effect_parameters_object<'a>(cx: &mut FunctionContext<'a>, name: &str, effect_type: &str, parameters: &[(&'static str, f64)]) -> Handle<'a, JsObject> {
+    let object = cx.empty_object();
+    let js_name = cx.string(name);
+    let js_type = cx.string(effect_type);
+    let _ = object.set(cx, "name", js_name);
+    let _ = object.set(cx, "type", js_type);
+
+    for (parameter, value) in parameters.iter() {
+        let js_value = cx.number(*value);
+        let _ = object.set(cx, *parameter, js_value);
+    }
+
+    object
+}
+
+// the parameters named in the object - anything it does not mention is left as it was
+fn apply_effect_parameters(cx: &mut FunctionContext, object: Handle<JsObject>, parameters: &[(&'static str, f64)], set_parameter: &mut dyn FnMut(&str, f64) -> Result<(), effects::EffectError>) -> NeonResult<Result<(), effects::EffectError>> {
+    for (parameter, _) in parameters.iter() {
+        if let Some(value) = number_property(cx, object, parameter)? {
+            if let Err(error) = set_parameter(parameter, value) {
+                return Ok(Err(error))
+            }
+        }
+    }
+
+    Ok(Ok(()))
+}
+
+fn effect_name_property(cx: &mut FunctionContext, object: Handle<JsObject>) -> NeonResult<Result<Option<SamplerName>, sampler_name::SamplerNameError>> {
+    Ok(match string_property(cx, object, "name")? {
+        Some(name) => validated_name(name.as_str()).map(Some),
+        None => Ok(None),
+    })
+}
+
+// {name, type, ...parameters} - type is chorus, delay, echo or pitch_shift, tune offsets are in semitones
+fn sampler_get_effect(mut cx: FunctionContext) -> JsResult<JsObject> {
+    info!("Entered sampler_get_effect...");
+    if let Ok(effect_number) = cx.argument::<JsNumber>(0) {
+        let effect_number = effect_number.value(&mut cx) as u16;
+
+        if let Some(data) = request_effect_block(effects::EFFECTS_SELECTOR, effect_number) {
+            match effects::Effect::from_bytes(&data) {
+                Ok(effect) => return Ok(effect_parameters_object(&mut cx, effect.common().name().as_str(), effect.type_name(), &effect.parameters())),
+                Err(error) => info!("sampler_get_effect: {}", error),
+            }
+        }
+    }
+
+    Ok(cx.empty_object())
+}
+
+// giving a different type switches the effect's algorithm, keeping its name and common parameters
+fn sampler_set_effect(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_set_effect...");
+    if let (Ok(effect_number), Ok(object)) = (cx.argument::<JsNumber>(0), cx.argument::<JsObject>(1)) {
+        let effect_number = effect_number.value(&mut cx) as u16;
+
+        if let Some(mut data) = request_effect_block(effects::EFFECTS_SELECTOR, effect_number) {
+            let mut effect = match effects::Effect::from_bytes(&data) {
+                Ok(effect) => effect,
+                Err(error) => {
+                    info!("sampler_set_effect: {}", error);
+                    return Ok(cx.boolean(false))
+                }
+            };
+
+            if let Some(effect_type) = string_property(&mut cx, object, "type")? {
+                if effect_type != effect.type_name() {
+                    effect = match effects::Effect::new(effect_type.as_str(), effect.common().clone()) {
+                        Ok(effect) => effect,
+                        Err(error) => {
+                            info!("sampler_set_effect: {}", error);
+                            return Ok(cx.boolean(false))
+                        }
+                    };
+                }
+            }
+            match effect_name_property(&mut cx, object)? {
+                Ok(Some(name)) => effect.common_mut().set_name(&name),
+                Ok(None) => (),
+                Err(error) => {
+                    info!("sampler_set_effect: {}", error);
+                    return Ok(cx.boolean(false))
+                }
+            }
+
+            let parameters = effect.parameters();
+            if let Err(error) = apply_effect_parameters(&mut cx, object, &parameters, &mut |parameter, value| effect.set_parameter(parameter, value))? {
+                info!("sampler_set_effect: {}", error);
+                return Ok(cx.boolean(false))
+            }
+
+            effect.write_to(&mut data);
+            return Ok(cx.boolean(send_effect_block(effects::EFFECTS_SELECTOR, effect_number, data)))
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
+// {name, type: "reverb", reverb_type, ...parameters}
+fn sampler_get_reverb(mut cx: FunctionContext) -> JsResult<JsObject> {
+    info!("Entered sampler_get_reverb...");
+    if let Ok(reverb_number) = cx.argument::<JsNumber>(0) {
+        let reverb_number = reverb_number.value(&mut cx) as u16;
+
+        if let Some(data) = request_effect_block(effects::REVERBS_SELECTOR, reverb_number) {
+            match effects::Reverb::from_bytes(&data) {
+                Ok(reverb) => return Ok(effect_parameters_object(&mut cx, reverb.common.name().as_str(), "reverb", &reverb.parameters())),
+                Err(error) => info!("sampler_get_reverb: {}", error),
+            }
+        }
+    }
+
+    Ok(cx.empty_object())
+}
+
+fn sampler_set_reverb(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_set_reverb...");
+    if let (Ok(reverb_number), Ok(object)) = (cx.argument::<JsNumber>(0), cx.argument::<JsObject>(1)) {
+        let reverb_number = reverb_number.value(&mut cx) as u16;
+
+        if let Some(mut data) = request_effect_block(effects::REVERBS_SELECTOR, reverb_number) {
+            let mut reverb = match effects::Reverb::from_bytes(&data) {
+                Ok(reverb) => reverb,
+                Err(error) => {
+                    info!("sampler_set_reverb: {}", error);
+                    return Ok(cx.boolean(false))
+                }
+            };
+
+            match effect_name_property(&mut cx, object)? {
+                Ok(Some(name)) => reverb.common.set_name(&name),
+                Ok(None) => (),
+                Err(error) => {
+                    info!("sampler_set_reverb: {}", error);
+                    return Ok(cx.boolean(false))
+                }
+            }
+
+            let parameters = reverb.parameters();
+            if let Err(error) = apply_effect_parameters(&mut cx, object, &parameters, &mut |parameter, value| reverb.set_parameter(parameter, value))? {
+                info!("sampler_set_reverb: {}", error);
+                return Ok(cx.boolean(false))
+            }
+
+            reverb.write_to(&mut data);
+            return Ok(cx.boolean(send_effect_block(effects::REVERBS_SELECTOR, reverb_number, data)))
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
+// selector 1 for the program number to effect number table, 3 for reverbs
+fn request_assignment_table(selector: u8) -> Option<Vec<u8>> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestFXReverb(0, selector, effects::NUMBER_OF_ASSIGNMENTS as u16, 0)));
+
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::FXReverb(data))) if data.len() == effects::NUMBER_OF_ASSIGNMENTS => Some(data),
+        _ => None,
+    }
+}
+
+fn send_assignment_table(selector: u8, assignments: Vec<u8>) -> bool {
+    send_sampler_change(IncomingSamplerEvent::ResponseFXReverb(0, selector, 0, assignments))
+}
+
+const FX_FILE_STEPS: i32 = (effects::NUMBER_OF_EFFECTS + effects::NUMBER_OF_REVERBS + 3) as i32; // blocks, filename and the two tables
+
+// progress is (steps done, FX_FILE_STEPS) and can return false to cancel
+fn read_fx_file(progress: &mut dyn FnMut(i32, i32) -> bool) -> Result<fx_file::FxFile, String> {
+    let filename = request_fx_file_name().ok_or("no reply for the effects file name")?;
+    let mut steps_done = 1;
+    let mut blocks = |selector: u8, count: usize| -> Result<Vec<Vec<u8>>, String> {
+        let mut blocks = vec![];
+        for number in 0..count {
+            blocks.push(request_effect_block(selector, number as u16).ok_or(format!("no reply for block {} of selector {}", number, selector))?);
+            steps_done += 1;
+            if !progress(steps_done, FX_FILE_STEPS) {
+                return Err("cancelled".to_string())
+            }
+        }
+        Ok(blocks)
+    };
+    let effects = blocks(effects::EFFECTS_SELECTOR, effects::NUMBER_OF_EFFECTS)?;
+    let reverbs = blocks(effects::REVERBS_SELECTOR, effects::NUMBER_OF_REVERBS)?;
+    let effect_assignments = request_assignment_table(effects::EFFECT_ASSIGNMENTS_SELECTOR).ok_or("no reply for the effect assignments")?;
+    let reverb_assignments = request_assignment_table(effects::REVERB_ASSIGNMENTS_SELECTOR).ok_or("no reply for the reverb assignments")?;
+    progress(FX_FILE_STEPS, FX_FILE_STEPS);
+
+    fx_file::FxFile::new(filename.as_str(), effects, reverbs, effect_assignments, reverb_assignments).map_err(|error| error.to_string())
+}
+
+// the filename is checked before anything is sent so a bad file changes nothing
+fn write_fx_file(fx_file: &fx_file::FxFile, progress: &mut dyn FnMut(i32, i32) -> bool) -> Result<(), String> {
+    let filename = validated_name(fx_file.filename.as_str()).map_err(|error| error.to_string())?;
+
+    if !send_sampler_change(IncomingSamplerEvent::ResponseFXReverb(0, effects::HEADER_SELECTOR, effects::HEADER_FILENAME_OFFSET, filename.to_sysex())) {
+        return Err("the effects file name was not accepted".to_string())
+    }
+
+    let mut steps_done = 1;
+    let blocks = fx_file.effects.iter().enumerate().map(|(number, block)| (effects::EFFECTS_SELECTOR, number, block))
+        .chain(fx_file.reverbs.iter().enumerate().map(|(number, block)| (effects::REVERBS_SELECTOR, number, block)));
+    for (selector, number, block) in blocks {
+        if !send_effect_block(selector, number as u16, block.clone()) {
+            return Err(format!("block {} of selector {} was not accepted", number, selector))
+        }
+        steps_done += 1;
+        if !progress(steps_done, FX_FILE_STEPS) {
+            return Err("cancelled".to_string())
+        }
+    }
+
+    if !send_assignment_table(effects::EFFECT_ASSIGNMENTS_SELECTOR, fx_file.effect_assignments.clone()) {
+        return Err("the effect assignments were not accepted".to_string())
+    }
+    if !send_assignment_table(effects::REVERB_ASSIGNMENTS_SELECTOR, fx_file.reverb_assignments.clone()) {
+        return Err("the reverb assignments were not accepted".to_string())
+    }
+    progress(FX_FILE_STEPS, FX_FILE_STEPS);
+
+    Ok(())
+}
+
+// sampler_export_fx_file(path, [progress]) - saves the effects file in memory
+fn sampler_export_fx_file(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_export_fx_file...");
+    if let Ok(path) = cx.argument::<JsString>(0) {
+        let path = path.value(&mut cx);
+        let progress_callback = progress_callback_argument(&mut cx, 1);
+        let mut progress = |steps_done: i32, number_of_steps: i32| {
+            call_progress_callback(&mut cx, progress_callback, steps_done, number_of_steps)
+        };
+
+        match read_fx_file(&mut progress) {
+            Ok(fx_file) => match fx_file.write(path.as_str()) {
+                Ok(_) => return Ok(cx.boolean(true)),
+                Err(error) => info!("sampler_export_fx_file: {}: {}", path, error),
+            },
+            Err(error) => info!("sampler_export_fx_file: {}", error),
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
+// sampler_import_fx_file(path, [progress]) - replaces the effects file in memory
+fn sampler_import_fx_file(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_import_fx_file...");
+    if let Ok(path) = cx.argument::<JsString>(0) {
+        let path = path.value(&mut cx);
+        let fx_file = match fx_file::FxFile::read(path.as_str()) {
+            Ok(fx_file) => fx_file,
+            Err(error) => {
+                info!("sampler_import_fx_file: {}: {}", path, error);
+                return Ok(cx.boolean(false))
+            }
+        };
+        let progress_callback = progress_callback_argument(&mut cx, 1);
+        let mut progress = |steps_done: i32, number_of_steps: i32| {
+            call_progress_callback(&mut cx, progress_callback, steps_done, number_of_steps)
+        };
+
+        match journal_step("import fx file", || write_fx_file(&fx_file, &mut progress)) {
+            Ok(_) => return Ok(cx.boolean(true)),
+            Err(error) => info!("sampler_import_fx_file: {}", error),
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
+fn request_cue_list_bytes(entry: u16, selector: u8, offset: u16, number_of_bytes: usize) -> Option<Vec<u8>> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestCueList(entry, selector, offset, number_of_bytes as u16)));
+
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::CueList(data))) if data.len() == number_of_bytes => Some(data),
+        _ => None,
+    }
+}
+
+fn send_cue_list_bytes(entry: u16, selector: u8, offset: u16, data: Vec<u8>) -> bool {
+    send_sampler_change(IncomingSamplerEvent::ResponseCueList(entry, selector, offset, data))
+}
+
+fn read_cue_list() -> Result<cue_list::CueList, String> {
+    let header = request_cue_list_bytes(0, cue_list::HEADER_SELECTOR, 0, cue_list::HEADER_SIZE).ok_or("no reply for the cue list header")?;
+    let mut list = cue_list::CueList::from_header(&header).map_err(|error| error.to_string())?;
+
+    for entry in 0..cue_list::CueList::number_of_events_in_header(&header) {
+        let data = request_cue_list_bytes(entry as u16, cue_list::EVENT_SELECTOR, 0, cue_list::EVENT_SIZE).ok_or(format!("no reply for cue event {}", entry))?;
+        list.events.push(cue_list::CueEvent::from_bytes(&data).map_err(|error| format!("cue event {}: {}", entry, error))?);
+    }
+
+    Ok(list)
+}
+
+// events before first_changed are already on the sampler - the header goes last so the event count
+// only changes once the events are there
+fn write_cue_list(list: &cue_list::CueList, first_changed: usize) -> Result<(), String> {
+    for (entry, event) in list.events.iter().enumerate().skip(first_changed) {
+        if !send_cue_list_bytes(entry as u16, cue_list::EVENT_SELECTOR, 0, event.to_bytes()) {
+            return Err(format!("cue event {} was not accepted", entry))
+        }
+    }
+
+    if !send_cue_list_bytes(0, cue_list::HEADER_SELECTOR, 0, list.header_bytes()) {
+        return Err("the cue list header was not accepted".to_string())
+    }
+
+    Ok(())
+}
+
+fn cue_event_object<'a>(cx: &mut FunctionContext<'a>, event: &cue_list::CueEvent) -> Handle<'a, JsObject> {
+    let object = cx.empty_object();
+    let event_type = cx.string(event.event_type.name());
+    let time = cx.string(event.time.to_string());
+    let _ = object.set(cx, "type", event_type);
+    let _ = object.set(cx, "time", time);
+
+    for (key, value) in [
+        ("hours", event.time.hours as u32),
+        ("minutes", event.time.minutes as u32),
+        ("seconds", event.time.seconds as u32),
+        ("frames", event.time.frames as u32),
+        ("subframes", event.time.subframes as u32),
+        ("number", event.number as u32),
+        ("midi_channel", event.midi_channel as u32),
+        ("key", event.key as u32),
+        ("velocity", event.velocity as u32),
+    ].iter() {
+        let js_value = cx.number(*value);
+        let _ = object.set(cx, *key, js_value);
+    }
+
+    object
+}
+
+// fields missing from the object are taken from the event being edited, or are zero for a new one
+fn cue_event_from_object(cx: &mut FunctionContext, object: Handle<JsObject>, existing: Option<&cue_list::CueEvent>) -> NeonResult<Result<cue_list::CueEvent, String>> {
+    let event_type = match string_property(cx, object, "type")? {
+        Some(name) => match cue_list::CueEventType::from_name(name.as_str()) {
+            Some(event_type) => event_type,
+            None => return Ok(Err(format!("{} is not a cue event type", name))),
+        },
+        None => match existing {
+            Some(existing) => existing.event_type,
+            None => return Ok(Err("a new cue event needs a type".to_string())),
+        },
+    };
+    let mut event = match existing {
+        Some(existing) => existing.clone(),
+        None => cue_list::CueEvent::new(cue_list::CueTime::default(), event_type, 0, 0, 0, 0),
+    };
+    event.event_type = event_type;
+
+    let byte = |cx: &mut FunctionContext, key: &str, value: &mut u8| -> NeonResult<()> {
+        if let Some(number) = number_property(cx, object, key)? {
+            *value = number.clamp(0.0, 255.0) as u8;
+        }
+        Ok(())
+    };
+    byte(cx, "hours", &mut event.time.hours)?;
+    byte(cx, "minutes", &mut event.time.minutes)?;
+    byte(cx, "seconds", &mut event.time.seconds)?;
+    byte(cx, "frames", &mut event.time.frames)?;
+    byte(cx, "subframes", &mut event.time.subframes)?;
+    byte(cx, "midi_channel", &mut event.midi_channel)?;
+    byte(cx, "key", &mut event.key)?;
+    byte(cx, "velocity", &mut event.velocity)?;
+    if let Some(number) = number_property(cx, object, "number")? {
+        event.number = number.clamp(0.0, u16::MAX as f64) as u16;
+    }
+
+    Ok(Ok(event))
+}
+
+// {name, frame_rate, events: [{type, time, hours, minutes, seconds, frames, subframes, number, midi_channel, key, velocity}]}
+fn sampler_cue_list(mut cx: FunctionContext) -> JsResult<JsObject> {
+    info!("Entered sampler_cue_list...");
+    let result = cx.empty_object();
+
+    match read_cue_list() {
+        Ok(list) => {
+            let name = cx.string(list.name().as_str());
+            let frame_rate = cx.number(list.frame_rate.frames_per_second());
+            let events = cx.empty_array();
+            for (index, event) in list.events.iter().enumerate() {
+                let js_event = cue_event_object(&mut cx, event);
+                let _ = events.set(&mut cx, index as u32, js_event);
+            }
+
+            let _ = result.set(&mut cx, "name", name);
+            let _ = result.set(&mut cx, "frame_rate", frame_rate);
+            let _ = result.set(&mut cx, "events", events);
+        }
+        Err(error) => info!("sampler_cue_list: {}", error),
+    }
+
+    Ok(result)
+}
+
+// reads the cue list, applies the edit and writes back the events from the first one that changed
+fn edit_cue_list(name: &str, edit: &mut dyn FnMut(&mut cue_list::CueList) -> Result<usize, String>) -> bool {
+    let result = journal_step(name, || read_cue_list().and_then(|mut list| {
+        let first_changed = edit(&mut list)?;
+        write_cue_list(&list, first_changed)
+    }));
+
+    match result {
+        Ok(_) => true,
+        Err(error) => {
+            info!("{}: {}", name, error);
+            false
+        }
+    }
+}
+
+// sampler_cue_list_add(event, [index]) - at the end when no index is given
+fn sampler_cue_list_add(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_cue_list_add...");
+    if let Ok(object) = cx.argument::<JsObject>(0) {
+        let index = match cx.argument_opt(1) {
+            Some(index) => index.downcast::<JsNumber, FunctionContext>(&mut cx).ok().map(|index| index.value(&mut cx) as usize),
+            None => None,
+        };
+        let event = match cue_event_from_object(&mut cx, object, None)? {
+            Ok(event) => event,
+            Err(error) => {
+                info!("sampler_cue_list_add: {}", error);
+                return Ok(cx.boolean(false))
+            }
+        };
+
+        let success = edit_cue_list("sampler_cue_list_add", &mut |list| list.add(event.clone(), index).map_err(|error| error.to_string()));
+        return Ok(cx.boolean(success))
+    }
+
+    Ok(cx.boolean(false))
+}
+
+// sampler_cue_list_edit(index, changes) - only the fields given are changed
+fn sampler_cue_list_edit(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_cue_list_edit...");
+    if let (Ok(index), Ok(object)) = (cx.argument::<JsNumber>(0), cx.argument::<JsObject>(1)) {
+        let index = index.value(&mut cx) as usize;
+        let list = match read_cue_list() {
+            Ok(list) => list,
+            Err(error) => {
+                info!("sampler_cue_list_edit: {}", error);
+                return Ok(cx.boolean(false))
+            }
+        };
+        let existing = match list.events.get(index) {
+            Some(existing) => existing.clone(),
+            None => {
+                info!("sampler_cue_list_edit: {}", cue_list::CueListError::NoSuchEvent(index));
+                return Ok(cx.boolean(false))
+            }
+        };
+        let event = match cue_event_from_object(&mut cx, object, Some(&existing))? {
+            Ok(event) => event,
+            Err(error) => {
+                info!("sampler_cue_list_edit: {}", error);
+                return Ok(cx.boolean(false))
+            }
+        };
+
+        let mut list = list;
+        let result = journal_step("sampler_cue_list_edit", || list.edit(index, event).map_err(|error| error.to_string()).and_then(|_| write_cue_list(&list, index)));
+        if let Err(error) = result.as_ref() {
+            info!("sampler_cue_list_edit: {}", error);
+        }
+        return Ok(cx.boolean(result.is_ok()))
+    }
+
+    Ok(cx.boolean(false))
+}
+
+fn sampler_cue_list_delete(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_cue_list_delete...");
+    if let Ok(index) = cx.argument::<JsNumber>(0) {
+        let index = index.value(&mut cx) as usize;
+
+        let success = edit_cue_list("sampler_cue_list_delete", &mut |list| list.delete(index).map(|_| index).map_err(|error| error.to_string()));
+        return Ok(cx.boolean(success))
+    }
+
+    Ok(cx.boolean(false))
+}
+
+// sampler_cue_list_move(from, to)
+fn sampler_cue_list_move(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_cue_list_move...");
+    if let (Ok(from), Ok(to)) = (cx.argument::<JsNumber>(0), cx.argument::<JsNumber>(1)) {
+        let from = from.value(&mut cx) as usize;
+        let to = to.value(&mut cx) as usize;
+
+        let success = edit_cue_list("sampler_cue_list_move", &mut |list| list.move_event(from, to).map(|_| from.min(to)).map_err(|error| error.to_string()));
+        return Ok(cx.boolean(success))
+    }
+
+    Ok(cx.boolean(false))
+}
+
+fn sampler_cue_list_sort(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_cue_list_sort...");
+    let success = edit_cue_list("sampler_cue_list_sort", &mut |list| {
+        list.sort_by_time();
+        Ok(0)
+    });
+
+    Ok(cx.boolean(success))
+}
+
+fn request_take_list_bytes(entry: u16, selector: u8, offset: u16, number_of_bytes: usize) -> Option<Vec<u8>> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestTakeList(entry, selector, offset, number_of_bytes as u16)));
+
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::TakeList(data))) if data.len() == number_of_bytes => Some(data),
+        _ => None,
+    }
+}
+
+fn request_take(take_number: u16) -> Result<take_list::Take, String> {
+    let data = request_take_list_bytes(take_number, take_list::TAKE_SELECTOR, 0, take_list::TAKE_SIZE).ok_or(format!("no reply for take {}", take_number))?;
+
+    take_list::Take::from_bytes(&data).map_err(|error| error.to_string())
+}
+
+// the take list name and its takes
+fn read_take_list() -> Result<(String, Vec<take_list::Take>), String> {
+    let header = request_take_list_bytes(0, take_list::HEADER_SELECTOR, 0, take_list::HEADER_SIZE).ok_or("no reply for the take list header")?;
+    let name = take_list::take_list_name(&header).map_err(|error| error.to_string())?;
+    let takes = (0..take_list::number_of_takes_in_header(&header)).map(|take_number| request_take(take_number as u16)).collect::<Result<Vec<_>, _>>()?;
+
+    Ok((name, takes))
+}
+
+fn take_object<'a>(cx: &mut FunctionContext<'a>, take: &take_list::Take) -> Handle<'a, JsObject> {
+    let object = cx.empty_object();
+    let name = cx.string(take.name().as_str());
+    let _ = object.set(cx, "name", name);
+
+    for (key, value) in [
+        ("sample", take.sample as f64),
+        ("start", take.start as f64),
+        ("end", take.end as f64),
+        ("length", take.length() as f64),
+        ("level", take.level as f64),
+        ("pan", take.pan as f64),
+    ].iter() {
+        let js_value = cx.number(*value);
+        let _ = object.set(cx, *key, js_value);
+    }
+
+    object
+}
+
+// {name, takes: [{name, sample, start, end, length, level, pan}]}
+fn sampler_take_list(mut cx: FunctionContext) -> JsResult<JsObject> {
+    info!("Entered sampler_take_list...");
+    let result = cx.empty_object();
+
+    match read_take_list() {
+        Ok((name, takes)) => {
+            let name = cx.string(name);
+            let js_takes = cx.empty_array();
+            for (index, take) in takes.iter().enumerate() {
+                let js_take = take_object(&mut cx, take);
+                let _ = js_takes.set(&mut cx, index as u32, js_take);
+            }
+
+            let _ = result.set(&mut cx, "name", name);
+            let _ = result.set(&mut cx, "takes", js_takes);
+        }
+        Err(error) => info!("sampler_take_list: {}", error),
+    }
+
+    Ok(result)
+}
+
+fn sampler_take(mut cx: FunctionContext) -> JsResult<JsObject> {
+    info!("Entered sampler_take...");
+    if let Ok(take_number) = cx.argument::<JsNumber>(0) {
+        let take_number = take_number.value(&mut cx) as u16;
+
+        match request_take(take_number) {
+            Ok(take) => return Ok(take_object(&mut cx, &take)),
+            Err(error) => info!("sampler_take: {}", error),
+        }
+    }
+
+    Ok(cx.empty_object())
+}
+
+// sampler_take_update(take number, changes) - only the fields given are changed
+fn sampler_take_update(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_take_update...");
+    if let (Ok(take_number), Ok(object)) = (cx.argument::<JsNumber>(0), cx.argument::<JsObject>(1)) {
+        let take_number = take_number.value(&mut cx) as u16;
+        let mut take = match request_take(take_number) {
+            Ok(take) => take,
+            Err(error) => {
+                info!("sampler_take_update: {}", error);
+                return Ok(cx.boolean(false))
+            }
+        };
+
+        if let Some(name) = string_property(&mut cx, object, "name")? {
+            match validated_name(name.as_str()) {
+                Ok(name) => take.set_name(&name),
+                Err(error) => {
+                    info!("sampler_take_update: {}", error);
+                    return Ok(cx.boolean(false))
+                }
+            }
+        }
+        if let Some(sample) = number_property(&mut cx, object, "sample")? {
+            take.sample = sample.clamp(0.0, u16::MAX as f64) as u16;
+        }
+        if let Some(start) = number_property(&mut cx, object, "start")? {
+            take.start = start.clamp(0.0, u32::MAX as f64) as u32;
+        }
+        if let Some(end) = number_property(&mut cx, object, "end")? {
+            take.end = end.clamp(0.0, u32::MAX as f64) as u32;
+        }
+        if let Some(level) = number_property(&mut cx, object, "level")? {
+            take.level = level.clamp(0.0, 255.0) as u8;
+        }
+        if let Some(pan) = number_property(&mut cx, object, "pan")? {
+            take.pan = pan.clamp(-128.0, 127.0) as i8;
+        }
+        if let Err(error) = take.validate() {
+            info!("sampler_take_update: {}", error);
+            return Ok(cx.boolean(false))
+        }
+
+        return Ok(cx.boolean(send_sampler_change(IncomingSamplerEvent::ResponseTakeList(take_number, take_list::TAKE_SELECTOR, 0, take.to_bytes()))))
+    }
+
+    Ok(cx.boolean(false))
+}
+
+// sampler_take_list_export(path, "csv" or "json", [sample rate]) - the sample rate adds times in seconds
+fn sampler_take_list_export(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_take_list_export...");
+    if let (Ok(path), Ok(format)) = (cx.argument::<JsString>(0), cx.argument::<JsString>(1)) {
+        let path = path.value(&mut cx);
+        let format = format.value(&mut cx).to_lowercase();
+        let sample_rate = match cx.argument_opt(2) {
+            Some(sample_rate) => sample_rate.downcast::<JsNumber, FunctionContext>(&mut cx).ok().map(|sample_rate| sample_rate.value(&mut cx) as u32),
+            None => None,
+        };
+
+        let result = read_take_list()
+            .and_then(|(_, takes)| take_list::edit_decision_list(&takes, sample_rate, format.as_str()).map_err(|error| error.to_string()))
+            .and_then(|edit_decision_list| std::fs::write(path.as_str(), edit_decision_list).map_err(|error| format!("{}: {}", path, error)));
+
+        match result {
+            Ok(_) => return Ok(cx.boolean(true)),
+            Err(error) => info!("sampler_take_list_export: {}", error),
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
+// sampler_cue_list_export_midi_file(path)
+fn sampler_cue_list_export_midi_file(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_cue_list_export_midi_file...");
+    if let Ok(path) = cx.argument::<JsString>(0) {
+        let path = path.value(&mut cx);
+
+        let result = read_cue_list().and_then(|list| midi_file::write(path.as_str(), &list.to_midi_messages()).map_err(|error| format!("{}: {}", path, error)));
+        match result {
+            Ok(_) => return Ok(cx.boolean(true)),
+            Err(error) => info!("sampler_cue_list_export_midi_file: {}", error),
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
+// sampler_cue_list_import_midi_file(path) - replaces the cue list's events, keeping its name and frame rate
+fn sampler_cue_list_import_midi_file(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_cue_list_import_midi_file...");
+    if let Ok(path) = cx.argument::<JsString>(0) {
+        let path = path.value(&mut cx);
+        let messages = match midi_file::read(path.as_str()) {
+            Ok(messages) => messages,
+            Err(error) => {
+                info!("sampler_cue_list_import_midi_file: {}: {}", path, error);
+                return Ok(cx.boolean(false))
+            }
+        };
+
+        let success = edit_cue_list("sampler_cue_list_import_midi_file", &mut |list| {
+            let skipped = list.set_events_from_midi(&messages).map_err(|error| error.to_string())?;
+            if skipped > 0 {
+                info!("sampler_cue_list_import_midi_file: skipped {} markers that are not take events.", skipped);
+            }
+            Ok(0)
+        });
+        return Ok(cx.boolean(success))
+    }
+
+    Ok(cx.boolean(false))
+}
+
+// the same message as ChangeProgramHeader or ChangeKeyGroupHeader
+fn header_change_message(write: &header_batch::HeaderWrite) -> Vec<u8> {
+    let mut message = vec![
+        START_OF_SYSTEM_EXCLUSIVE,
+        SAMPLER_MANUFACTURER_CODE,
+        0x00,
+        match write.keygroup {
+            Some(_) => S3000SysexFunctionCodes::ResponseKeygroupHeader as u8,
+            None => S3000SysexFunctionCodes::ResponseProgramHeader as u8,
+        },
+        SAMPLER_IDENTITY,
+        (write.program as u16 & U16_LSB_TO_AKAI_U8_MASK) as u8,
+        (write.program as u16 >> U16_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT) as u8,
+        write.keygroup.unwrap_or(0),
+        (write.offset as u16 & U16_LSB_TO_AKAI_U8_MASK) as u8,
+        (write.offset as u16 >> U16_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT) as u8,
+        (write.data.len() as u16 & U16_LSB_TO_AKAI_U8_MASK) as u8,
+        (write.data.len() as u16 >> U16_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT) as u8,
+    ];
+
+    // nibbled header data being sent
+    for value in write.data.iter() {
+        message.push(value & 15); // lsb first
+        message.push(value >> 4); // msb last
+    }
+    message.push(EOX);
+
+    message
+}
+
+// the sampler's replies to these are not waited for, so they are marked to be discarded
+fn queue_header_writes(header_writes: &[header_batch::HeaderWrite], sysex_to_sampler_queue: &Mutex<VecDeque<Vec<u8>>>, batched_header_changes: &mut VecDeque<Vec<u8>>) {
+    if header_writes.is_empty() {
+        return
+    }
+
+    if let Ok(mut sysex_to_sampler_queue) = sysex_to_sampler_queue.lock() {
+        for write in header_writes.iter() {
+            let message = header_change_message(write);
+            batched_header_changes.push_back(message.clone());
+            sysex_to_sampler_queue.push_back(message);
+        }
+    }
+}
+
+fn queue_header_change(cx: &mut FunctionContext, program_number: u8, keygroup_number: Option<u8>, offset: Handle<JsNumber>, data: Handle<JsArray>) -> NeonResult<bool> {
+    let offset = offset.value(cx) as u8;
+    let mut changed_data = vec![];
+    for value in data.to_vec(cx)?.iter() {
+        if let Ok(data) = value.downcast::<JsNumber, FunctionContext>(cx) {
+            changed_data.push(data.value(cx) as u8);
+        }
+    }
+
+    if journal_enabled() {
+        journal_queued_header_change(program_number, keygroup_number, offset, &changed_data);
+    }
+
+    match HEADER_CHANGES.lock() {
+        Ok(mut header_changes) => {
+            header_changes.queue(program_number, keygroup_number, offset, &changed_data, std::time::Instant::now());
+            Ok(true)
+        }
+        Err(_) => Ok(false),
+    }
+}
+
+// sampler_queue_program_header_change(program number, offset, data) - returns straight away, the change
+// is sent with any others made within header_batch::HEADER_CHANGE_WINDOW
+fn sampler_queue_program_header_change(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    if let (Ok(program_number), Ok(offset), Ok(data)) = (cx.argument::<JsNumber>(0), cx.argument::<JsNumber>(1), cx.argument::<JsArray>(2)) {
+        let program_number = program_number.value(&mut cx) as u8;
+        let queued = queue_header_change(&mut cx, program_number, None, offset, data)?;
+        return Ok(cx.boolean(queued))
+    }
+
+    Ok(cx.boolean(false))
+}
+
+// sampler_queue_keygroup_header_change(program number, keygroup number, offset, data)
+fn sampler_queue_keygroup_header_change(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    if let (Ok(program_number), Ok(keygroup_number), Ok(offset), Ok(data)) = (cx.argument::<JsNumber>(0), cx.argument::<JsNumber>(1), cx.argument::<JsNumber>(2), cx.argument::<JsArray>(3)) {
+        let program_number = program_number.value(&mut cx) as u8;
+        let keygroup_number = keygroup_number.value(&mut cx) as u8;
+        let queued = queue_header_change(&mut cx, program_number, Some(keygroup_number), offset, data)?;
+        return Ok(cx.boolean(queued))
+    }
+
+    Ok(cx.boolean(false))
+}
+
+// sends whatever is queued without waiting for the window - call it when a control is let go
+fn sampler_flush_header_changes(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_flush_header_changes...");
+    match HEADER_CHANGES.lock() {
+        Ok(mut header_changes) => {
+            header_changes.request_flush();
+            Ok(cx.boolean(true))
+        }
+        Err(_) => Ok(cx.boolean(false)),
+    }
+}
+
+fn request_program_header_bytes(program_number: u16, offset: u16, number_of_bytes: u16) -> Option<Vec<u8>> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestProgramHeaderBytes(program_number, offset, number_of_bytes)));
+
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::ProgramHeader(data))) if data.len() == number_of_bytes as usize => Some(data),
+        _ => None,
+    }
+}
+
+fn request_keygroup_header(program_number: u16, keygroup_number: u8) -> Option<Vec<u8>> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestKeygroupHeader(program_number, keygroup_number)));
+
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::KeygroupHeader(data))) => Some(data),
+        _ => None,
+    }
+}
+
+// in the order ChangeS1000MiscBytes takes them
+fn request_s1000_misc_bytes() -> Option<Vec<u8>> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestS1000MiscellaneousData));
+
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::S1000MiscellaneousData(data, None))) => {
+            ["basic_midi_channel", "selected_program_number", "midi_play_commands_omni_override", "midi_exlusive_channel", "basic_channel_omni", "midi_program_select_enable"]
+                .iter()
+                .map(|key| data.get(*key).map(|value| *value as u8))
+                .collect()
+        }
+        _ => None,
+    }
+}
+
+fn journal_enabled() -> bool {
+    JOURNAL.lock().map(|journal| journal.is_enabled()).unwrap_or(false)
+}
+
+// Every change to the sampler goes through here. The bytes it replaces are read first and kept in the
+// journal so the change can be undone - reads and changes share the queue, so the read sees what the
+// sampler holds just before the change.
+// the old bytes are read before the change is sent, but the change is only journaled once the
+// sampler accepts it - undoing a refused new program would delete whatever already had its number
+fn send_sampler_change(event: IncomingSamplerEvent) -> bool {
+    let journal_entry = if journal_enabled() { Some(journal_change(&event)) } else { None };
+    if !send_command(event) {
+        return false
+    }
+
+    if let (Some((description, change)), Ok(mut journal)) = (journal_entry, JOURNAL.lock()) {
+        journal.record(description.as_str(), change);
+    }
+
+    true
+}
+
+// for changes the sampler does not answer
+fn send_unanswered_sampler_change(event: IncomingSamplerEvent) {
+    let journal_entry = if journal_enabled() { Some(journal_change(&event)) } else { None };
+    if INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(event)).is_ok() {
+        if let (Some((description, change)), Ok(mut journal)) = (journal_entry, JOURNAL.lock()) {
+            journal.record(description.as_str(), change);
+        }
+    }
+}
+
+// the changes made by work are undone and redone together
+fn journal_step<T>(description: &str, work: impl FnOnce() -> T) -> T {
+    if let Ok(mut journal) = JOURNAL.lock() {
+        journal.begin_step(description);
+    }
+    let result = work();
+    if let Ok(mut journal) = JOURNAL.lock() {
+        journal.end_step();
+    }
+
+    result
+}
+
+fn header_bytes(header: Option<Vec<u8>>, offset: u8, number_of_bytes: usize) -> Option<Vec<u8>> {
+    header.and_then(|header| header.get((offset as usize)..(offset as usize + number_of_bytes)).map(|bytes| bytes.to_vec()))
+}
+
+// what the event is about to overwrite - anything that cannot be read back becomes a step undo stops at
+fn journal_change(event: &IncomingSamplerEvent) -> (String, journal::JournalChange) {
+    use journal::{JournalChange, JournalTarget};
+
+    let bytes = |target: JournalTarget, offset: u16, old: Option<Vec<u8>>, new: &Vec<u8>| match old {
+        Some(old) => JournalChange::Bytes { target, offset, old, new: new.clone() },
+        None => JournalChange::Irreversible("the bytes it replaced could not be read".to_string()),
+    };
+
+    match event {
+        IncomingSamplerEvent::ChangeProgramHeader(program_number, offset, data) => (
+            format!("change program {} header", program_number),
+            bytes(JournalTarget::ProgramHeader(*program_number as u16), *offset as u16, request_program_header_bytes(*program_number as u16, *offset as u16, data.len() as u16), data),
+        ),
+        IncomingSamplerEvent::ChangeKeyGroupHeader(program_number, keygroup_number, offset, data) => (
+            format!("change program {} keygroup {} header", program_number, keygroup_number),
+            bytes(JournalTarget::KeygroupHeader(*program_number as u16, *keygroup_number), *offset as u16, header_bytes(request_keygroup_header(*program_number as u16, *keygroup_number), *offset, data.len()), data),
+        ),
+        IncomingSamplerEvent::ChangeSampleHeader(sample_number, offset, data) => (
+            format!("change sample {} header", sample_number),
+            bytes(JournalTarget::SampleHeader(*sample_number as u16), *offset as u16, header_bytes(request_sample_header(*sample_number as u16), *offset, data.len()), data),
+        ),
+        IncomingSamplerEvent::ResponseFXReverb(item_number, selector, offset, data) => {
+            // the file name comes back as a string rather than bytes
+            let old = if *item_number == 0 && *selector == effects::HEADER_SELECTOR && *offset == effects::HEADER_FILENAME_OFFSET && data.len() == 12 {
+                request_fx_file_name().map(|name| convert_name_to_sampler_sysex_name(name)[..12].to_vec())
+            }
+            else {
+                request_effect_block_part(*selector, *item_number, *offset, data.len() as u16)
+            };
+            (format!("change fx item {} selector {}", item_number, selector), bytes(JournalTarget::FxReverb(*item_number, *selector), *offset, old, data))
+        }
+        IncomingSamplerEvent::ResponseMiscellaneousBytes(data_index, data_bank_number, data) => {
+            if let Some(entry) = misc_data::command_at(*data_bank_number, *data_index) {
+                return (format!("{} command", entry.name), JournalChange::Irreversible(format!("{} cannot be taken back", entry.name)))
+            }
+            let old = request_misc_value(*data_index, *data_bank_number).and_then(|value| misc_data::encode(*data_bank_number, &value).ok());
+            (format!("change miscellaneous bank {} index {}", data_bank_number, data_index), bytes(JournalTarget::Miscellaneous(*data_index, *data_bank_number), 0, old, data))
+        }
+        IncomingSamplerEvent::ResponseCueList(entry, selector, offset, data) => (
+            format!("change cue list entry {}", entry),
+            bytes(JournalTarget::CueList(*entry, *selector), *offset, request_cue_list_bytes(*entry, *selector, *offset, data.len()), data),
+        ),
+        IncomingSamplerEvent::ResponseTakeList(entry, selector, offset, data) => (
+            format!("change take list entry {}", entry),
+            bytes(JournalTarget::TakeList(*entry, *selector), *offset, request_take_list_bytes(*entry, *selector, *offset, data.len()), data),
+        ),
+        IncomingSamplerEvent::ChangeS1000MiscBytes(basic_midi_channel, selected_program_number, midi_play_commands_omni_override, midi_exlusive_channel, basic_channel_omni, midi_program_select_enable) => {
+            let new = vec![*basic_midi_channel, *selected_program_number, *midi_play_commands_omni_override, *midi_exlusive_channel, *basic_channel_omni, *midi_program_select_enable];
+            let change = match request_s1000_misc_bytes() {
+                Some(old) => JournalChange::S1000Miscellaneous { old, new },
+                None => JournalChange::Irreversible("the bytes it replaced could not be read".to_string()),
+            };
+            ("change S1000 miscellaneous bytes".to_string(), change)
+        }
+        IncomingSamplerEvent::NewProgram(program_number, data) => (
+            format!("new program {}", program_number),
+            JournalChange::NewProgram { program: *program_number, header: data.clone() },
+        ),
+        IncomingSamplerEvent::DeleteProgram(program_number) => {
+            let header = request_program_header(*program_number);
+            let number_of_keygroups = header.as_ref().and_then(|header| header.get(program_builder::NUMBER_OF_KEYGROUPS_OFFSET)).copied().unwrap_or(0);
+            let keygroups: Option<Vec<Vec<u8>>> = (0..number_of_keygroups).map(|keygroup_number| request_keygroup_header(*program_number, keygroup_number)).collect();
+            let change = match (header, keygroups) {
+                (Some(header), Some(keygroups)) => JournalChange::DeleteProgram { program: *program_number, header, keygroups },
+                _ => JournalChange::Irreversible("the program could not be read before it was deleted".to_string()),
+            };
+            (format!("delete program {}", program_number), change)
+        }
+        IncomingSamplerEvent::NewKeygroup(program_number, keygroup_number, data) => (
+            format!("new program {} keygroup {}", program_number, keygroup_number),
+            JournalChange::NewKeygroup { program: *program_number, keygroup: *keygroup_number, header: data.clone() },
+        ),
+        IncomingSamplerEvent::DeleteKeygroup(program_number, keygroup_number) => {
+            let change = match request_keygroup_header(*program_number, *keygroup_number) {
+                Some(header) => JournalChange::DeleteKeygroup { program: *program_number, keygroup: *keygroup_number, header },
+                None => JournalChange::Irreversible("the keygroup could not be read before it was deleted".to_string()),
+            };
+            (format!("delete program {} keygroup {}", program_number, keygroup_number), change)
+        }
+        IncomingSamplerEvent::NewSample(sample_number) => (format!("new sample {}", sample_number), JournalChange::NewSample { sample: *sample_number }),
+        // sample data is not read back over sysex before it is replaced
+        IncomingSamplerEvent::DeleteSample(sample_number) => (format!("delete sample {}", sample_number), JournalChange::Irreversible("sample data is not kept".to_string())),
+        IncomingSamplerEvent::UploadSample(sample_number, _, _) | IncomingSamplerEvent::NewSampleFromTemplate(sample_number, _, _) => (
+            format!("upload sample {}", sample_number),
+            JournalChange::Irreversible("sample data is not kept".to_string()),
+        ),
+        _ => ("change".to_string(), JournalChange::Irreversible("the change is not journaled".to_string())),
+    }
+}
+
+// the bytes still waiting in the batch are what the sampler will hold, so only the rest are read
+fn journal_queued_header_change(program_number: u8, keygroup_number: Option<u8>, offset: u8, data: &[u8]) {
+    let pending: Vec<Option<u8>> = match HEADER_CHANGES.lock() {
+        Ok(header_changes) => (0..data.len()).map(|index| header_changes.pending_byte(program_number, keygroup_number, offset as u16 + index as u16)).collect(),
+        Err(_) => return,
+    };
+
+    let old = if pending.iter().all(|byte| byte.is_some()) {
+        pending.iter().flatten().copied().collect()
+    }
+    else {
+        let read = match keygroup_number {
+            Some(keygroup_number) => header_bytes(request_keygroup_header(program_number as u16, keygroup_number), offset, data.len()),
+            None => request_program_header_bytes(program_number as u16, offset as u16, data.len() as u16),
+        };
+        match read {
+            Some(read) => read.iter().zip(pending.iter()).map(|(read, pending)| pending.unwrap_or(*read)).collect(),
+            None => return,
+        }
+    };
+
+    let (description, target) = match keygroup_number {
+        Some(keygroup_number) => (format!("change program {} keygroup {} header", program_number, keygroup_number), journal::JournalTarget::KeygroupHeader(program_number as u16, keygroup_number)),
+        None => (format!("change program {} header", program_number), journal::JournalTarget::ProgramHeader(program_number as u16)),
+    };
+    if let Ok(mut journal) = JOURNAL.lock() {
+        journal.record_coalesced(description.as_str(), journal::JournalChange::Bytes { target, offset: offset as u16, old, new: data.to_vec() });
+    }
+}
+
+// sent straight to the sampler so undo and redo are not journaled themselves
+fn send_command(event: IncomingSamplerEvent) -> bool {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(event));
+
+    matches!(
+        OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT),
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::S1000CommandReply(true)))
+    )
+}
+
+fn write_journal_bytes(target: journal::JournalTarget, offset: u16, data: Vec<u8>) -> bool {
+    use journal::JournalTarget;
+
+    // a loaded journal could still hold one
+    if let JournalTarget::Miscellaneous(data_index, data_bank_number) = target {
+        if misc_data::command_at(data_bank_number, data_index).is_some() {
+            return false
+        }
+    }
+
+    send_command(match target {
+        JournalTarget::ProgramHeader(program_number) => IncomingSamplerEvent::ChangeProgramHeader(program_number as u8, offset as u8, data),
+        JournalTarget::KeygroupHeader(program_number, keygroup_number) => IncomingSamplerEvent::ChangeKeyGroupHeader(program_number as u8, keygroup_number, offset as u8, data),
+        JournalTarget::SampleHeader(sample_number) => IncomingSamplerEvent::ChangeSampleHeader(sample_number as u8, offset as u8, data),
+        JournalTarget::FxReverb(item_number, selector) => IncomingSamplerEvent::ResponseFXReverb(item_number, selector, offset, data),
+        JournalTarget::Miscellaneous(data_index, data_bank_number) => IncomingSamplerEvent::ResponseMiscellaneousBytes(data_index, data_bank_number, data),
+        JournalTarget::CueList(entry, selector) => IncomingSamplerEvent::ResponseCueList(entry, selector, offset, data),
+        JournalTarget::TakeList(entry, selector) => IncomingSamplerEvent::ResponseTakeList(entry, selector, offset, data),
+    })
+}
+
+// the sampler does not reply to these, as in sampler_change_s1000_misc_bytes
+fn write_s1000_misc_bytes(data: &[u8]) -> bool {
+    match *data {
+        [basic_midi_channel, selected_program_number, midi_play_commands_omni_override, midi_exlusive_channel, basic_channel_omni, midi_program_select_enable] => {
+            let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::ChangeS1000MiscBytes(basic_midi_channel, selected_program_number, midi_play_commands_omni_override, midi_exlusive_channel, basic_channel_omni, midi_program_select_enable)));
+            true
+        }
+        _ => false,
+    }
+}
+
+// the same way build_program sends a program - one keygroup with the header, the rest added after it
+fn restore_program(program_number: u16, header: &[u8], keygroups: &[Vec<u8>]) -> bool {
+    let mut header = header.to_vec();
+    if let Some(number_of_keygroups) = header.get_mut(program_builder::NUMBER_OF_KEYGROUPS_OFFSET) {
+        *number_of_keygroups = 1;
+    }
+
+    send_command(IncomingSamplerEvent::NewProgram(program_number, header)) && keygroups.iter().enumerate().all(|(keygroup_number, keygroup)| {
+        let target_program_number = if keygroup_number == 0 { JUST_CREATED_PROGRAM_NUMBER } else { program_number };
+        send_command(IncomingSamplerEvent::NewKeygroup(target_program_number, keygroup_number as u8, keygroup.clone()))
+    })
+}
+
+fn apply_journal_change(change: &journal::JournalChange, undo: bool) -> bool {
+    use journal::JournalChange;
+
+    match change {
+        JournalChange::Bytes { target, offset, old, new } => write_journal_bytes(*target, *offset, if undo { old.clone() } else { new.clone() }),
+        JournalChange::S1000Miscellaneous { old, new } => write_s1000_misc_bytes(if undo { old } else { new }),
+        JournalChange::NewProgram { program, .. } if undo => send_command(IncomingSamplerEvent::DeleteProgram(*program)),
+        JournalChange::NewProgram { program, header } => send_command(IncomingSamplerEvent::NewProgram(*program, header.clone())),
+        JournalChange::DeleteProgram { program, header, keygroups } if undo => restore_program(*program, header, keygroups),
+        JournalChange::DeleteProgram { program, .. } => send_command(IncomingSamplerEvent::DeleteProgram(*program)),
+        // a keygroup sent with a new program goes when the program does
+        JournalChange::NewKeygroup { program, .. } if undo && *program == JUST_CREATED_PROGRAM_NUMBER => true,
+        JournalChange::NewKeygroup { program, keygroup, .. } if undo => send_command(IncomingSamplerEvent::DeleteKeygroup(*program, *keygroup)),
+        JournalChange::NewKeygroup { program, keygroup, header } => send_command(IncomingSamplerEvent::NewKeygroup(*program, *keygroup, header.clone())),
+        JournalChange::DeleteKeygroup { program, keygroup, header } if undo => send_command(IncomingSamplerEvent::NewKeygroup(*program, *keygroup, header.clone())),
+        JournalChange::DeleteKeygroup { program, keygroup, .. } => send_command(IncomingSamplerEvent::DeleteKeygroup(*program, *keygroup)),
+        JournalChange::NewSample { sample } if undo => send_command(IncomingSamplerEvent::DeleteSample(*sample)),
+        JournalChange::NewSample { sample } => send_command(IncomingSamplerEvent::NewSample(*sample)),
+        JournalChange::Irreversible(_) => !undo,
+    }
+}
+
+// a queued edit sent after an undo or redo would overwrite it
+fn flush_header_changes() {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::FlushHeaderChanges));
+}
+
+// the changes of a step are undone last first
+fn undo_journal_step() -> Result<String, String> {
+    flush_header_changes();
+    let step = match JOURNAL.lock() {
+        Ok(journal) => journal.next_undo().map_err(|error| error.to_string())?.clone(),
+        Err(_) => return Err("the journal is not available".to_string()),
+    };
+
+    if !step.changes.iter().rev().all(|change| apply_journal_change(change, true)) {
+        return Err(format!("{} was only partly undone", step.description))
+    }
+    if let Ok(mut journal) = JOURNAL.lock() {
+        journal.mark_undone();
+    }
+
+    Ok(step.description)
+}
+
+fn redo_journal_step() -> Result<String, String> {
+    flush_header_changes();
+    let step = match JOURNAL.lock() {
+        Ok(journal) => journal.next_redo().map_err(|error| error.to_string())?.clone(),
+        Err(_) => return Err("the journal is not available".to_string()),
+    };
+
+    if !step.changes.iter().all(|change| apply_journal_change(change, false)) {
+        return Err(format!("{} was only partly redone", step.description))
+    }
+    if let Ok(mut journal) = JOURNAL.lock() {
+        journal.mark_redone();
+    }
+
+    Ok(step.description)
+}
+
+// undoes or redoes one step at a time until the journal is back where the checkpoint was made
+fn restore_journal_checkpoint(name: &str) -> Result<(), String> {
+    flush_header_changes();
+    let checkpoint_position = match JOURNAL.lock() {
+        Ok(journal) => journal.checkpoint_position(name).map_err(|error| error.to_string())?,
+        Err(_) => return Err("the journal is not available".to_string()),
+    };
+
+    loop {
+        let position = JOURNAL.lock().map(|journal| journal.position()).map_err(|_| "the journal is not available".to_string())?;
+        match position.cmp(&checkpoint_position) {
+            Ordering::Greater => undo_journal_step()?,
+            Ordering::Less => redo_journal_step()?,
+            Ordering::Equal => return Ok(()),
+        };
+    }
+}
+
+// returns the description of the step undone
+fn sampler_undo(mut cx: FunctionContext) -> JsResult<JsValue> {
+    info!("Entered sampler_undo...");
+    match undo_journal_step() {
+        Ok(description) => Ok(cx.string(description).upcast()),
+        Err(error) => {
+            info!("sampler_undo: {}", error);
+            Ok(cx.boolean(false).upcast())
+        }
+    }
+}
+
+fn sampler_redo(mut cx: FunctionContext) -> JsResult<JsValue> {
+    info!("Entered sampler_redo...");
+    match redo_journal_step() {
+        Ok(description) => Ok(cx.string(description).upcast()),
+        Err(error) => {
+            info!("sampler_redo: {}", error);
+            Ok(cx.boolean(false).upcast())
+        }
+    }
+}
+
+// { enabled, position, steps: [{ description, reversible }], checkpoints: [{ name, position }] } - the
+// steps before position are applied and the rest can be redone
+fn sampler_journal(mut cx: FunctionContext) -> JsResult<JsObject> {
+    info!("Entered sampler_journal...");
+    let object = cx.empty_object();
+
+    if let Ok(journal) = JOURNAL.lock() {
+        let enabled = cx.boolean(journal.is_enabled());
+        object.set(&mut cx, "enabled", enabled)?;
+        let position = cx.number(journal.position() as f64);
+        object.set(&mut cx, "position", position)?;
+
+        let steps = cx.empty_array();
+        for (index, step) in journal.steps().iter().enumerate() {
+            let step_object = cx.empty_object();
+            let description = cx.string(step.description.as_str());
+            step_object.set(&mut cx, "description", description)?;
+            let reversible = cx.boolean(step.is_reversible());
+            step_object.set(&mut cx, "reversible", reversible)?;
+            steps.set(&mut cx, index as u32, step_object)?;
+        }
+        object.set(&mut cx, "steps", steps)?;
+
+        let checkpoints = cx.empty_array();
+        for (index, (name, position)) in journal.checkpoints().iter().enumerate() {
+            let checkpoint_object = cx.empty_object();
+            let name = cx.string(name.as_str());
+            checkpoint_object.set(&mut cx, "name", name)?;
+            let position = cx.number(*position as f64);
+            checkpoint_object.set(&mut cx, "position", position)?;
+            checkpoints.set(&mut cx, index as u32, checkpoint_object)?;
+        }
+        object.set(&mut cx, "checkpoints", checkpoints)?;
+    }
+
+    Ok(object)
+}
+
+fn sampler_journal_checkpoint(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_journal_checkpoint...");
+    if let Ok(name) = cx.argument::<JsString>(0) {
+        let name = name.value(&mut cx);
+        if let Ok(mut journal) = JOURNAL.lock() {
+            journal.checkpoint(name.as_str());
+            return Ok(cx.boolean(true))
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
+fn sampler_journal_restore_checkpoint(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_journal_restore_checkpoint...");
+    if let Ok(name) = cx.argument::<JsString>(0) {
+        let name = name.value(&mut cx);
+        match restore_journal_checkpoint(name.as_str()) {
+            Ok(_) => return Ok(cx.boolean(true)),
+            Err(error) => info!("sampler_journal_restore_checkpoint: {}", error),
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
+// off stops the reads before each change - what is already journaled is kept
+fn sampler_journal_set_enabled(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_journal_set_enabled...");
+    if let Ok(enabled) = cx.argument::<JsBoolean>(0) {
+        let enabled = enabled.value(&mut cx);
+        if let Ok(mut journal) = JOURNAL.lock() {
+            journal.set_enabled(enabled);
+            return Ok(cx.boolean(true))
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
+fn sampler_journal_clear(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_journal_clear...");
+    match JOURNAL.lock() {
+        Ok(mut journal) => {
+            journal.clear();
+            Ok(cx.boolean(true))
+        }
+        Err(_) => Ok(cx.boolean(false)),
+    }
+}
+
+fn sampler_journal_save(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_journal_save...");
+    if let Ok(path) = cx.argument::<JsString>(0) {
+        let path = path.value(&mut cx);
+        if let Ok(journal) = JOURNAL.lock() {
+            match journal.save(path.as_str()) {
+                Ok(_) => return Ok(cx.boolean(true)),
+                Err(error) => info!("sampler_journal_save: {}", error),
+            }
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
+// replaces this session's journal - only useful while the sampler still holds what the file describes
+fn sampler_journal_load(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_journal_load...");
+    if let Ok(path) = cx.argument::<JsString>(0) {
+        let path = path.value(&mut cx);
+        if let Ok(mut journal) = JOURNAL.lock() {
+            match journal.load(path.as_str()) {
+                Ok(_) => return Ok(cx.boolean(true)),
+                Err(error) => info!("sampler_journal_load: {}", error),
+            }
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
+fn progress_callback_argument<'a>(cx: &mut FunctionContext<'a>, index: i32) -> Option<Handle<'a, JsFunction>> {
+    match cx.argument_opt(index) {
+        Some(callback) => callback.downcast::<JsFunction, FunctionContext>(cx).ok(),
+        None => None,
+    }
+}
+
+// the progress callback can return false to cancel the dump
+fn call_progress_callback<'a>(cx: &mut FunctionContext<'a>, callback: Option<Handle<'a, JsFunction>>, packets_received: i32, packets_expected: i32) -> bool {
+    if let Some(callback) = callback {
+        let this = cx.undefined();
+        let args = vec![cx.number(packets_received).upcast::<JsValue>(), cx.number(packets_expected).upcast::<JsValue>()];
+        match callback.call(cx, this, args) {
+            Ok(result) => {
+                if let Ok(carry_on) = result.downcast::<JsBoolean, FunctionContext>(cx) {
+                    return carry_on.value(cx)
+                }
+            }
+            Err(_) => return false,
+        }
+    }
+
+    true
+}
+
+fn upload_sample(sample_number: u16, header: Vec<u8>, samples: Vec<i16>) -> bool {
+    // even a failed upload may have replaced some of the audio
+    if let Some(sample_header) = SampleHeader::from_bytes(&header) {
+        waveform::forget_overview(&sample_header.name);
+    }
+
+    send_sampler_change(IncomingSamplerEvent::UploadSample(sample_number, header, samples))
+}
+
+// downloads a whole sample as signed words along with its raw and parsed header
+fn download_sample(sample_number: u16, progress: &mut dyn FnMut(i32, i32) -> bool) -> Option<(Vec<u8>, SampleHeader, Vec<i16>)> {
+    let header_data = request_sample_header(sample_number)?;
+    let header = SampleHeader::from_bytes(&header_data)?;
+    let samples = request_sample_data(sample_number, 0, header.sample_length, progress)?;
+
+    Some((header_data, header, samples.iter().map(|sample| *sample as i16).collect()))
+}
+
+fn sampler_crossfade_sample_loop(mut cx: FunctionContext) -> JsResult<JsNumber> {
+    info!("Entered sampler_crossfade_sample_loop...");
+
+    if let (Ok(sample_number), Ok(loop_number), Ok(crossfade_length)) = (cx.argument::<JsNumber>(0), cx.argument::<JsNumber>(1), cx.argument::<JsNumber>(2)) {
+        let sample_number = sample_number.value(&mut cx) as u16;
+        let loop_number = loop_number.value(&mut cx) as usize;
+        let crossfade_length = crossfade_length.value(&mut cx) as usize;
+        let progress_callback = progress_callback_argument(&mut cx, 3);
+
+        if loop_number >= sample_header::NUMBER_OF_LOOPS {
+            info!("sampler_crossfade_sample_loop: invalid loop number.");
+            return Ok(cx.number(-1))
+        }
+
+        let mut progress = |packets_received: i32, packets_expected: i32| {
+            call_progress_callback(&mut cx, progress_callback, packets_received, packets_expected)
+        };
+
+        if let Some((header_data, header, mut samples)) = download_sample(sample_number, &mut progress) {
+            let sample_loop = &header.loops[loop_number];
+
+            if !sample_loop.is_used() {
+                info!("sampler_crossfade_sample_loop: loop {} is not set.", loop_number);
+                return Ok(cx.number(-1))
+            }
+
+            let crossfade_length = dsp::crossfade_loop(&mut samples, sample_loop.start() as usize, sample_loop.at as usize, crossfade_length);
+            info!("sampler_crossfade_sample_loop: crossfaded {} samples.", crossfade_length);
+
+            // the header goes back unchanged so the loop points are kept
+            if crossfade_length > 0 && upload_sample(sample_number, header_data, samples) {
+                return Ok(cx.number(crossfade_length as f64))
+            }
+        }
+    }
+
+    info!("sampler_crossfade_sample_loop: failure.");
+    Ok(cx.number(-1))
+}
+
+fn sampler_process_sample(mut cx: FunctionContext) -> JsResult<JsNumber> {
+    info!("Entered sampler_process_sample...");
+
+    if let (Ok(sample_number), Ok(js_operations)) = (cx.argument::<JsNumber>(0), cx.argument::<JsArray>(1)) {
+        let sample_number = sample_number.value(&mut cx) as u16;
+        let mut operations = vec![];
+
+        if let Ok(js_operations) = js_operations.to_vec(&mut cx) {
+            for js_operation in js_operations.iter() {
+                if let Ok(js_operation) = js_operation.downcast::<JsObject, FunctionContext>(&mut cx) {
+                    let name = js_operation.get_value(&mut cx, "operation")?
+                        .downcast::<JsString, FunctionContext>(&mut cx)
+                        .map(|name| name.value(&mut cx))
+                        .unwrap_or_default();
+                    let value = js_operation.get_value(&mut cx, "value")?
+                        .downcast::<JsNumber, FunctionContext>(&mut cx)
+                        .map(|value| value.value(&mut cx))
+                        .unwrap_or(0.0);
+
+                    match dsp::Operation::new(name.as_str(), value) {
+                        Some(operation) => operations.push(operation),
+                        None => {
+                            info!("sampler_process_sample: unknown or invalid operation {} with value {}.", name, value);
+                            return Ok(cx.number(-1))
+                        }
+                    }
+                }
+            }
+        }
+
+        // the result replaces the sample unless another sample number (and name) is given
+        let target_sample_number = match cx.argument_opt(2) {
+            Some(value) => match value.downcast::<JsNumber, FunctionContext>(&mut cx) {
+                Ok(value) => value.value(&mut cx) as u16,
+                Err(_) => sample_number,
+            },
+            None => sample_number,
+        };
+        let target_name = match cx.argument_opt(3) {
+            Some(value) => value.downcast::<JsString, FunctionContext>(&mut cx).ok().map(|name| name.value(&mut cx)),
+            None => None,
+        };
+        let target_name = match target_name.map(|name| validated_name(name.as_str())).transpose() {
+            Ok(target_name) => target_name,
+            Err(error) => {
+                info!("sampler_process_sample: {}", error);
+                return Ok(cx.number(-1))
+            }
+        };
+        let progress_callback = progress_callback_argument(&mut cx, 4);
+
+        let mut progress = |packets_received: i32, packets_expected: i32| {
+            call_progress_callback(&mut cx, progress_callback, packets_received, packets_expected)
+        };
+
+        if let Some((mut header_data, header, samples)) = download_sample(sample_number, &mut progress) {
+            // the target rate was checked with the operations, the header's could be anything - it only
+            // matters when resampling, the other operations keep whatever rate the sample has
+            let resamples = operations.iter().any(|operation| matches!(operation, dsp::Operation::Resample(_)));
+            if header.sample_rate == 0 || (resamples && !dsp::SUPPORTED_SAMPLE_RATES.contains(&(header.sample_rate as u32))) {
+                info!("sampler_process_sample: sample {} has an unsupported sample rate of {}Hz.", sample_number, header.sample_rate);
+                return Ok(cx.number(-1))
+            }
+
+            let processed = dsp::process(samples, header.sample_rate as u32, &operations);
+
+            if processed.samples.is_empty() {
+                info!("sampler_process_sample: nothing left to upload.");
+                return Ok(cx.number(-1))
+            }
+
+            // loops follow the audio - any that no longer fit are dropped
+            let loops: Vec<SampleLoop> = header.used_loops().filter_map(|sample_loop| {
+                let start = processed.map_position(sample_loop.start())?;
+                let end = processed.map_position(sample_loop.at)?;
+                let (start, end) = (start.min(end), start.max(end));
+
+                Some(SampleLoop { at: end, length: end - start, length_fraction: 0, dwell_time: sample_loop.dwell_time })
+            }).collect();
+
+            sample_header::set_sample_length(&mut header_data, processed.samples.len() as u32);
+            sample_header::set_sample_rate(&mut header_data, processed.sample_rate as u16);
+            sample_header::set_loops(&mut header_data, &loops);
+            if let Some(target_name) = target_name {
+                sample_header::set_name(&mut header_data, &target_name);
+            }
+
+            let sample_length = processed.samples.len();
+            if upload_sample(target_sample_number, header_data, processed.samples) {
+                return Ok(cx.number(sample_length as f64))
+            }
+        }
+    }
+
+    info!("sampler_process_sample: failure.");
+    Ok(cx.number(-1))
+}
+
+fn request_sample_data(sample_number: u16, offset: u32, number_of_samples: u32, progress: &mut dyn FnMut(i32, i32) -> bool) -> Option<Vec<u16>> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestSampleData(sample_number, offset, number_of_samples)));
+
+    loop {
+        match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(SAMPLE_DUMP_PACKET_RECEIVE_TIMEOUT) {
+            Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::SampleDataProgress(packets_received, packets_expected))) => {
+                if !progress(packets_received, packets_expected) {
+                    info!("request_sample_data: cancelled at packet {} of {}.", packets_received, packets_expected);
+                    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::CancelSampleData));
+                    return None
+                }
+            }
+            Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::SampleData(mut samples))) => {
+                // the last packet is padded out to a whole packet of words
+                samples.truncate(number_of_samples as usize);
+                return Some(samples)
+            }
+            Ok(_) => info!("request_sample_data: ignoring unexpected message."),
+            Err(_) => {
+                info!("request_sample_data: timed out waiting for a sample dump packet.");
+                let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::CancelSampleData));
+                return None
+            }
+        }
+    }
+}
+
+fn request_resident_sample_names() -> Option<Vec<String>> {
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestResidentSampleNames));
+
+    match OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT) {
+        Ok(OutgoingEvent::SamplerEvent(OutgoingSamplerEvent::ResidentSampleNames(sample_names, None))) => Some(sample_names),
+        _ => None,
+    }
+}
+
+fn change_keygroup_header(program_number: u8, keygroup_number: u8, keygroup_header_offset: u8, data: Vec<u8>) -> bool {
+    send_sampler_change(
+        IncomingSamplerEvent::ChangeKeyGroupHeader(program_number, keygroup_number, keygroup_header_offset, data))
+}
+
+// a stereo file becomes a "-L"/"-R" pair in the sample number given and the one after it
+fn sampler_import_wav(mut cx: FunctionContext) -> JsResult<JsArray> {
+    info!("Entered sampler_import_wav...");
+    let sample_numbers = cx.empty_array();
+
+    if let (Ok(file), Ok(sample_number), Ok(name)) = (cx.argument::<JsString>(0), cx.argument::<JsNumber>(1), cx.argument::<JsString>(2)) {
+        let file = file.value(&mut cx);
+        let sample_number = sample_number.value(&mut cx) as u16;
+        let name = name.value(&mut cx);
+        let original_pitch = match cx.argument_opt(3) {
+            Some(value) => match value.downcast::<JsNumber, FunctionContext>(&mut cx) {
+                Ok(value) => value.value(&mut cx) as u8,
+                Err(_) => 60,
+            },
+            None => 60,
+        };
+
+        let wav_file = match wav::WavFile::read(file.as_str()) {
+            Ok(wav_file) => wav_file,
+            Err(error) => {
+                info!("sampler_import_wav: could not read {}: {}", file, error);
+                return Ok(sample_numbers)
+            }
+        };
+
+        // the header holds the rate in 16 bits and the sampler only plays a few rates
+        if wav_file.sample_rate == 0 {
+            info!("sampler_import_wav: {} has a sample rate of 0.", file);
+            return Ok(sample_numbers)
+        }
+        let sample_rate = dsp::nearest_supported_sample_rate(wav_file.sample_rate);
+        if sample_rate != wav_file.sample_rate {
+            info!("sampler_import_wav: resampling {} from {}Hz to {}Hz.", file, wav_file.sample_rate, sample_rate);
+        }
+
+        let samples: Vec<(String, &Vec<f64>)> = if wav_file.channels.len() >= 2 {
+            let (left_name, right_name) = stereo::pair_names(name.as_str());
+            vec![(left_name, &wav_file.channels[0]), (right_name, &wav_file.channels[1])]
+        }
+        else {
+            vec![(name, &wav_file.channels[0])]
+        };
+        let samples = match samples.into_iter().map(|(name, channel)| validated_name(name.as_str()).map(|name| (name, channel))).collect::<Result<Vec<(SamplerName, &Vec<f64>)>, _>>() {
+            Ok(samples) => samples,
+            Err(error) => {
+                info!("sampler_import_wav: {}", error);
+                return Ok(sample_numbers)
+            }
+        };
+
+        for (index, (name, channel)) in samples.into_iter().enumerate() {
+            let words: Vec<i16> = channel.iter().map(|value| dsp::to_sample_word(value * 32768.0)).collect();
+            let words = dsp::resample(&words, wav_file.sample_rate, sample_rate);
+            let header = sample_header::new_header(&name, words.len() as u32, sample_rate as u16, original_pitch);
+            let channel_sample_number = sample_number + index as u16;
+
+            info!("sampler_import_wav: uploading {} as sample {}.", name, channel_sample_number);
+            if !upload_sample(channel_sample_number, header, words) {
+                info!("sampler_import_wav: failed to upload {}.", name);
+                break
+            }
+
+            let js_sample_number = cx.number(channel_sample_number);
+            let _ = sample_numbers.set(&mut cx, index as u32, js_sample_number);
+        }
+    }
+
+    Ok(sample_numbers)
+}
+
+// half of a resident stereo pair is exported together with its partner as a stereo file
+fn sampler_export_wav(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_export_wav...");
+
+    if let (Ok(sample_number), Ok(file)) = (cx.argument::<JsNumber>(0), cx.argument::<JsString>(1)) {
+        let sample_number = sample_number.value(&mut cx) as u16;
+        let file = file.value(&mut cx);
+        let progress_callback = progress_callback_argument(&mut cx, 2);
+
+        let mut progress = |packets_received: i32, packets_expected: i32| {
+            call_progress_callback(&mut cx, progress_callback, packets_received, packets_expected)
+        };
+
+        let header = match request_sample_header(sample_number).and_then(|data| SampleHeader::from_bytes(&data)) {
+            Some(header) => header,
+            None => return Ok(cx.boolean(false)),
+        };
+
+        let sample_numbers = match request_resident_sample_names().and_then(|names| stereo::find_pair(&names, header.name.as_str())) {
+            Some((left, right)) => vec![left as u16, right as u16],
+            None => vec![sample_number],
+        };
+
+        let mut channels = vec![];
+        for channel_sample_number in sample_numbers.iter() {
+            match download_sample(*channel_sample_number, &mut progress) {
+                Some((_, _, samples)) => channels.push(samples.iter().map(|sample| *sample as f64 / 32768.0).collect::<Vec<f64>>()),
+                None => return Ok(cx.boolean(false)),
+            }
+        }
+
+        // the halves of a pair are not always the same length
+        let length = channels.iter().map(|channel| channel.len()).max().unwrap_or(0);
+        for channel in channels.iter_mut() {
+            channel.resize(length, 0.0);
+        }
+
+        let wav_file = wav::WavFile { sample_rate: header.sample_rate as u32, channels };
+        match wav_file.write(file.as_str()) {
+            Ok(_) => return Ok(cx.boolean(true)),
+            Err(error) => info!("sampler_export_wav: could not write {}: {}", file, error),
+        }
+    }
+
+    Ok(cx.boolean(false))
+}
+
+// puts the left half of a stereo pair in zone 1 panned left and the right half in zone 2 panned right
+fn sampler_assign_stereo_pair_to_keygroup(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_assign_stereo_pair_to_keygroup...");
+
+    if let (Ok(program_number), Ok(keygroup_number), Ok(name)) = (cx.argument::<JsNumber>(0), cx.argument::<JsNumber>(1), cx.argument::<JsString>(2)) {
+        let program_number = program_number.value(&mut cx) as u8;
+        let keygroup_number = keygroup_number.value(&mut cx) as u8;
+        let name = name.value(&mut cx);
+        let base = stereo::split_name(name.as_str()).map(|(base, _)| base.to_string()).unwrap_or(name);
+        let (left_name, right_name) = stereo::pair_names(base.as_str());
+
+        match request_resident_sample_names() {
+            Some(names) if stereo::find_pair(&names, left_name.as_str()).is_some() => (),
+            _ => {
+                info!("sampler_assign_stereo_pair_to_keygroup: {} and {} are not both resident.", left_name, right_name);
+                return Ok(cx.boolean(false))
+            }
+        }
+
+        let (left_name, right_name) = match (validated_name(left_name.as_str()), validated_name(right_name.as_str())) {
+            (Ok(left_name), Ok(right_name)) => (left_name, right_name),
+            (Err(error), _) | (_, Err(error)) => {
+                info!("sampler_assign_stereo_pair_to_keygroup: {}", error);
+                return Ok(cx.boolean(false))
+            }
+        };
+
+        let zones = [(0, left_name, keygroup::PAN_LEFT), (1, right_name, keygroup::PAN_RIGHT)];
+        for (zone, zone_name, pan) in zones.iter() {
+            let name_data = zone_name.to_sysex();
+            let pan_data = vec![keygroup::encode_plus_or_minus_fifty(*pan)];
+
+            if !change_keygroup_header(program_number, keygroup_number, keygroup::ZONE_SAMPLE_NAME_OFFSETS[*zone] as u8, name_data)
+                || !change_keygroup_header(program_number, keygroup_number, keygroup::ZONE_PAN_OFFSETS[*zone] as u8, pan_data) {
+                info!("sampler_assign_stereo_pair_to_keygroup: failed to set zone {}.", zone + 1);
+                return Ok(cx.boolean(false))
+            }
+        }
+
+        return Ok(cx.boolean(true))
+    }
+
+    Ok(cx.boolean(false))
+}
+
+// keygroup 0 sent to this program number is attached to the program that was just created
+const JUST_CREATED_PROGRAM_NUMBER: u16 = 255;
+
+fn new_program(program_number: u16, data: Vec<u8>) -> bool {
+    send_sampler_change(IncomingSamplerEvent::NewProgram(program_number, data))
+}
+
+fn new_keygroup(program_number: u16, keygroup_number: u8, data: Vec<u8>) -> bool {
+    send_sampler_change(IncomingSamplerEvent::NewKeygroup(program_number, keygroup_number, data))
+}
+
+fn delete_program(program_number: u16) -> bool {
+    send_sampler_change(IncomingSamplerEvent::DeleteProgram(program_number))
+}
+
+// Sends the program header, then the keygroups in key order. Each zone is tuned in its keygroup so the
+// sample plays at its recorded pitch on the root note - sample headers are only read, as other programs
+// may play the same samples. If any keygroup fails the program is deleted again.
+fn build_program(program_number: u16, name: &str, zones: Vec<program_builder::ZoneSpec>) -> Result<usize, String> {
+    let name = validated_name(name).map_err(|error| format!("{} cannot be the program name: {}", name, error))?;
+    let resident_names = request_resident_sample_names().ok_or("could not read the resident sample names")?;
+    let zones = program_builder::expand_stereo_zones(&resident_names, zones);
+
+    // nothing is sent until every sample has been found
+    let mut original_pitches: HashMap<String, u8> = HashMap::new();
+    for zone in zones.iter() {
+        let sample_name = zone.sample_name.trim_end();
+        if original_pitches.contains_key(sample_name) {
+            continue
+        }
+
+        let sample_number = resident_names.iter().position(|resident_name| resident_name.trim_end() == sample_name).ok_or_else(|| format!("{} is not resident", zone.sample_name))?;
+        let original_pitch = request_sample_header(sample_number as u16)
+            .and_then(|data| data.get(sample_header::ORIGINAL_PITCH_OFFSET).copied())
+            .ok_or_else(|| format!("the original pitch of {} could not be read", zone.sample_name))?;
+        original_pitches.insert(sample_name.to_string(), original_pitch);
+    }
+
+    let keygroups = program_builder::plan_keygroups(&zones)?;
+    let keygroup_headers = keygroups.iter().map(|keygroup_spec| program_builder::keygroup_header(keygroup_spec, &original_pitches)).collect::<Result<Vec<Vec<u8>>, String>>()?;
+
+    // the program starts with one keygroup and the rest are added after it
+    if !new_program(program_number, program_builder::program_header(&name, 1)) {
+        return Err(format!("program {} was not created", program_number))
+    }
 
-                        return Ok(cx.string(name))
-                    }
-                }
-            }
+    for (keygroup_number, keygroup_header) in keygroup_headers.into_iter().enumerate() {
+        let target_program_number = if keygroup_number == 0 { JUST_CREATED_PROGRAM_NUMBER } else { program_number };
+
+        if !new_keygroup(target_program_number, keygroup_number as u8, keygroup_header) {
+            delete_program(program_number);
+            return Err(format!("keygroup {} was not created", keygroup_number + 1))
         }
     }
 
-    Ok(cx.string(""))
+    Ok(keygroups.len())
 }
 
-fn sampler_request_miscellaneous_bytes_update_name(mut cx: FunctionContext) -> JsResult<JsBoolean> {
-    info!("Entered sampler_request_miscellaneous_bytes_update_name...");
-    if let Ok(data_index) = cx.argument::<JsNumber>(0) {
-        let data_index = data_index.value(&mut cx) as u16;
+fn zone_spec_property(cx: &mut FunctionContext, zone: Handle<JsObject>) -> NeonResult<Option<program_builder::ZoneSpec>> {
+    let sample_name = match zone.get_value(cx, "sample_name")?.downcast::<JsString, FunctionContext>(cx) {
+        Ok(sample_name) => sample_name.value(cx),
+        Err(_) => return Ok(None),
+    };
+    let root_note = match number_property(cx, zone, "root_note")? {
+        Some(root_note) => root_note as u8,
+        None => return Ok(None),
+    };
+    let key_range = match (number_property(cx, zone, "low_note")?, number_property(cx, zone, "high_note")?) {
+        (Some(low_note), Some(high_note)) => Some((low_note as u8, high_note as u8)),
+        _ => None,
+    };
+    let velocity_low = number_property(cx, zone, "velocity_low")?.unwrap_or(0.0) as u8;
+    let velocity_high = number_property(cx, zone, "velocity_high")?.unwrap_or(127.0) as u8;
 
-        if let Ok(name) = cx.argument::<JsString>(1) {
-            let name = name.value(&mut cx);
-            let data_bank_number = 6; // 12 byte name values data bank
-            let sampler_name = convert_name_to_sampler_sysex_name(name);
+    Ok(Some(program_builder::ZoneSpec { sample_name, root_note, key_range, velocity_range: (velocity_low, velocity_high), pan: 0 }))
+}
 
-            let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::ResponseMiscellaneousBytes(data_index, data_bank_number, 0, Some(sampler_name))));
-            if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-                if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                    if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                        return Ok(cx.boolean(success))
-                    }
+// zones are {sample_name, root_note, [low_note, high_note], [velocity_low, velocity_high]} - returns the number of keygroups built
+fn sampler_build_program(mut cx: FunctionContext) -> JsResult<JsNumber> {
+    info!("Entered sampler_build_program...");
+
+    if let (Ok(program_number), Ok(name), Ok(zones)) = (cx.argument::<JsNumber>(0), cx.argument::<JsString>(1), cx.argument::<JsArray>(2)) {
+        let program_number = program_number.value(&mut cx) as u16;
+        let name = name.value(&mut cx);
+        let mut zone_specs = vec![];
+
+        for (index, zone) in zones.to_vec(&mut cx)?.iter().enumerate() {
+            let zone_spec = match zone.downcast::<JsObject, FunctionContext>(&mut cx) {
+                Ok(zone) => zone_spec_property(&mut cx, zone)?,
+                Err(_) => None,
+            };
+
+            match zone_spec {
+                Some(zone_spec) => zone_specs.push(zone_spec),
+                None => {
+                    info!("sampler_build_program: zone {} needs a sample_name and a root_note.", index);
+                    return Ok(cx.number(-1))
                 }
             }
         }
-    }
-
-    Ok(cx.boolean(false))
-}
 
-fn sampler_request_cuelist_file_name(mut cx: FunctionContext) -> JsResult<JsString> {
-    info!("Entered sampler_request_cuelist_file_name...");
-    let data_index = 0; // always 0 for the header
-    let selector = 0; // header
-    let offset = 3; // 12 byte name offset
-    let number_of_bytes_of_data = 12;
-    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestCueList(data_index, selector, offset, number_of_bytes_of_data)));
-    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-            if let OutgoingSamplerEvent::CueListName(name) = sampler_event {
-                return Ok(cx.string(name))
-            }
+        match journal_step("build program", || build_program(program_number, name.as_str(), zone_specs)) {
+            Ok(number_of_keygroups) => return Ok(cx.number(number_of_keygroups as f64)),
+            Err(error) => info!("sampler_build_program: {}", error),
         }
     }
 
-    Ok(cx.string(""))
+    Ok(cx.number(-1))
 }
 
-fn sampler_request_take_list_file_name(mut cx: FunctionContext) -> JsResult<JsString> {
-    info!("Entered sampler_request_take_list_file_name...");
-    let data_index = 0; // always 0 for the header
-    let selector = 0; // header
-    let offset = 3; // 12 byte name offset
-    let number_of_bytes_of_data = 12;
-        let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestTakeList(data_index, selector, offset, number_of_bytes_of_data)));
-    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-            if let OutgoingSamplerEvent::TakeListName(name) = sampler_event {
-                return Ok(cx.string(name))
-            }
+// builds a program from the root notes and velocity tags in sample names - all resident samples
+// when no names are given - and returns the number of keygroups built
+fn sampler_auto_map_program(mut cx: FunctionContext) -> JsResult<JsNumber> {
+    info!("Entered sampler_auto_map_program...");
+
+    if let (Ok(program_number), Ok(name)) = (cx.argument::<JsNumber>(0), cx.argument::<JsString>(1)) {
+        let program_number = program_number.value(&mut cx) as u16;
+        let name = name.value(&mut cx);
+        let sample_names = match cx.argument_opt(2) {
+            Some(value) => match value.downcast::<JsArray, FunctionContext>(&mut cx) {
+                Ok(value) => {
+                    let mut sample_names = vec![];
+                    for element in value.to_vec(&mut cx)?.iter() {
+                        if let Ok(sample_name) = element.downcast::<JsString, FunctionContext>(&mut cx) {
+                            sample_names.push(sample_name.value(&mut cx));
+                        }
+                    }
+                    Some(sample_names)
+                }
+                Err(_) => None,
+            },
+            None => None,
+        };
+
+        let sample_names = match sample_names.or_else(request_resident_sample_names) {
+            Some(sample_names) => sample_names,
+            None => return Ok(cx.number(-1)),
+        };
+
+        let (zones, unmapped) = auto_map::map_zones(&sample_names);
+        for sample_name in unmapped.iter() {
+            info!("sampler_auto_map_program: no note found in {}.", sample_name);
+        }
+
+        match journal_step("build program", || build_program(program_number, name.as_str(), zones)) {
+            Ok(number_of_keygroups) => return Ok(cx.number(number_of_keygroups as f64)),
+            Err(error) => info!("sampler_auto_map_program: {}", error),
         }
     }
 
-    Ok(cx.string(""))
+    Ok(cx.number(-1))
 }
 
-fn sampler_request_fx_file_name(mut cx: FunctionContext) -> JsResult<JsString> {
-    info!("Entered sampler_request_fx_file_name...");
-    let data_index = 0; // always 0 for the header
-    let selector = 0; // header
-    let offset = 3; // 12 byte name offset
-    let number_of_bytes_of_data = 12;
-    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestFXReverb(data_index, selector, number_of_bytes_of_data, offset)));
-    if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-        if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-            if let OutgoingSamplerEvent::FXReverbFilename(name) = sampler_event {
-                return Ok(cx.string(name))
+// the sampler name, root note and velocity range each file would get - file names without a note have no root_note
+fn sampler_auto_map_names(mut cx: FunctionContext) -> JsResult<JsArray> {
+    info!("Entered sampler_auto_map_names...");
+    let mappings = cx.empty_array();
+
+    if let Ok(file_names) = cx.argument::<JsArray>(0) {
+        let mut names = vec![];
+        for element in file_names.to_vec(&mut cx)?.iter() {
+            if let Ok(file_name) = element.downcast::<JsString, FunctionContext>(&mut cx) {
+                names.push(file_name.value(&mut cx));
             }
         }
-    }
 
-    Ok(cx.string(""))
-}
+        let sample_names = auto_map::sampler_names(&names);
+        let (zones, _) = auto_map::map_zones(&sample_names);
 
-fn sampler_request_miscellaneous_bytes_update(mut cx: FunctionContext) -> JsResult<JsBoolean> {
-    info!("Entered sampler_request_miscellaneous_bytes_update...");
-    if let Ok(data_index) = cx.argument::<JsNumber>(0) {
-        let data_index = data_index.value(&mut cx) as u16;
-        if let Ok(data_bank_number) = cx.argument::<JsNumber>(1) {
-            let data_bank_number = data_bank_number.value(&mut cx) as u8;
-            if let Ok(changed_value) = cx.argument::<JsNumber>(2) {
-                let changed_value = changed_value.value(&mut cx) as u32;
-    
-                let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::ResponseMiscellaneousBytes(data_index, data_bank_number, changed_value, None)));
-                if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(RECEIVE_TIMEOUT.clone()) {
-                    if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                        if let OutgoingSamplerEvent::S1000CommandReply(success) = sampler_event {
-                            return Ok(cx.boolean(success));
-                        }
-                    }
-                }
+        for (index, (file_name, sample_name)) in names.iter().zip(sample_names.iter()).enumerate() {
+            let mapping = cx.empty_object();
+            let js_file_name = cx.string(file_name);
+            let js_sample_name = cx.string(sample_name);
+            let _ = mapping.set(&mut cx, "file_name", js_file_name);
+            let _ = mapping.set(&mut cx, "sample_name", js_sample_name);
+
+            if let Some(zone) = zones.iter().find(|zone| zone.sample_name == *sample_name) {
+                let root_note = cx.number(zone.root_note);
+                let velocity_low = cx.number(zone.velocity_range.0);
+                let velocity_high = cx.number(zone.velocity_range.1);
+                let _ = mapping.set(&mut cx, "root_note", root_note);
+                let _ = mapping.set(&mut cx, "velocity_low", velocity_low);
+                let _ = mapping.set(&mut cx, "velocity_high", velocity_high);
             }
+
+            let _ = mappings.set(&mut cx, index as u32, mapping);
         }
     }
 
-    Ok(cx.boolean(false))
+    Ok(mappings)
 }
 
-
 fn sampler_request_sample_data(mut cx: FunctionContext) -> JsResult<JsArray> {
     info!("Entered sampler_request_sample_data...");
     let sample_data = cx.empty_array();
@@ -3069,61 +6168,104 @@ fn sampler_request_sample_data(mut cx: FunctionContext) -> JsResult<JsArray> {
         
         if let Ok(number_of_samples) = cx.argument::<JsNumber>(1) {
             let number_of_samples = number_of_samples.value(&mut cx);
+            let offset = match cx.argument_opt(2) {
+                Some(offset) => match offset.downcast::<JsNumber, FunctionContext>(&mut cx) {
+                    Ok(offset) => offset.value(&mut cx) as u32,
+                    Err(_) => 0,
+                },
+                None => 0,
+            };
+            let progress_callback = progress_callback_argument(&mut cx, 3);
 
-            info!("sampler_request_sample_data: number of samples={}", number_of_samples);
-            
-            let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::RequestSampleData(sample_number, number_of_samples as u32)));
-            if let Ok(msg) = OUT_GOING_COMM_CHANNELS.rx.recv_timeout(Duration::from_secs(100)) {
-                if let OutgoingEvent::SamplerEvent(sampler_event) = msg {
-                    if let OutgoingSamplerEvent::SampleData(samples) = sampler_event {
-                        for (index, sample) in samples.iter().enumerate() {
-                            let key = cx.number(index as f64);
-                            let value = cx.number(*sample as f64);
-                            let _ = sample_data.set(&mut cx, key, value);
-                        }
-                        return Ok(sample_data)
-                    }
+            info!("sampler_request_sample_data: offset={}, number of samples={}", offset, number_of_samples);
+
+            let mut progress = |packets_received: i32, packets_expected: i32| {
+                call_progress_callback(&mut cx, progress_callback, packets_received, packets_expected)
+            };
+
+            if let Some(samples) = request_sample_data(sample_number, offset, number_of_samples as u32, &mut progress) {
+                for (index, sample) in samples.iter().enumerate() {
+                    let key = cx.number(index as f64);
+                    let value = cx.number(*sample as f64);
+                    let _ = sample_data.set(&mut cx, key, value);
                 }
             }
         }
     }
-                    
-    return Ok(sample_data)
+
+    Ok(sample_data)
+}
+
+fn sampler_cancel_sample_data(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    info!("Entered sampler_cancel_sample_data...");
+    let _ = INCOMING_COMM_CHANNELS.tx.send(IncomingEvent::SamplerEvent(IncomingSamplerEvent::CancelSampleData));
+
+    Ok(cx.boolean(true))
 }
 
+// names are always 12 characters - bytes outside the character map show as '?' so they are not lost silently
 fn convert_sampler_sysex_name_to_name(sampler_sysex_name: &Vec<u8>) -> String {
-    let mut name = String::from("");
-    
-    for (index, letter) in sampler_sysex_name.iter().enumerate() {
-        if index < 12 && (*letter as usize) < SAMPLER_CHAR_MAP.len() {
-            name.push(SAMPLER_CHAR_MAP[*letter as usize]);
-        }
+    if let Err(error) = SamplerName::from_sysex(sampler_sysex_name) {
+        info!("convert_sampler_sysex_name_to_name: {}", error);
     }
 
-    name
+    sampler_sysex_name.iter().take(sampler_name::SAMPLER_NAME_LENGTH).map(|letter| {
+        SAMPLER_CHAR_MAP.get(*letter as usize).copied().unwrap_or('?')
+    }).collect()
 }
 
+// transliterates, replaces and truncates as needed, logging what changed - writes that have to be
+// exact check the name with SamplerName::new first
 fn convert_name_to_sampler_sysex_name(name: String) -> Vec<u8> {
-    let mut sampler_sysex_name = vec![];
-    
-    for letter in name.chars() {
-        if let Some(index) = SAMPLER_CHAR_MAP.iter().position(|akai_letter| *akai_letter == letter) {
-            sampler_sysex_name.push(index as u8);
-        }
-        else {
-            sampler_sysex_name.push(0);
-        }
+    let (sampler_name, warnings) = SamplerName::lossy(name.as_str());
+
+    for warning in warnings.iter() {
+        info!("convert_name_to_sampler_sysex_name: {}: {}", name, warning);
     }
-    while sampler_sysex_name.len() < 12 {
-        if let Some(index) = SAMPLER_CHAR_MAP.iter().position(|akai_letter| *akai_letter == ' ') {
-            sampler_sysex_name.push(index as u8);
+
+    sampler_name.to_sysex()
+}
+
+// names typed by the user are transliterated but never truncated
+fn validated_name(name: &str) -> Result<SamplerName, sampler_name::SamplerNameError> {
+    SamplerName::new(sampler_name::transliterate(name).as_str())
+}
+
+// {name, valid, invalid_characters: [{position, character}], warnings: [...]} where name is what would be written
+fn sampler_validate_name(mut cx: FunctionContext) -> JsResult<JsObject> {
+    info!("Entered sampler_validate_name...");
+    let result = cx.empty_object();
+
+    if let Ok(name) = cx.argument::<JsString>(0) {
+        let name = name.value(&mut cx);
+        let (sampler_name, warnings) = SamplerName::lossy(name.as_str());
+        let valid = validated_name(name.as_str()).is_ok();
+
+        let js_name = cx.string(sampler_name.as_str());
+        let js_valid = cx.boolean(valid);
+        let _ = result.set(&mut cx, "name", js_name);
+        let _ = result.set(&mut cx, "valid", js_valid);
+
+        let invalid_characters = cx.empty_array();
+        for (index, (position, character)) in sampler_name::invalid_characters(name.as_str()).iter().enumerate() {
+            let invalid_character = cx.empty_object();
+            let js_position = cx.number(*position as f64);
+            let js_character = cx.string(character.to_string());
+            let _ = invalid_character.set(&mut cx, "position", js_position);
+            let _ = invalid_character.set(&mut cx, "character", js_character);
+            let _ = invalid_characters.set(&mut cx, index as u32, invalid_character);
         }
-        else {
-            sampler_sysex_name.push(0);
+        let _ = result.set(&mut cx, "invalid_characters", invalid_characters);
+
+        let js_warnings = cx.empty_array();
+        for (index, warning) in warnings.iter().enumerate() {
+            let js_warning = cx.string(warning.to_string());
+            let _ = js_warnings.set(&mut cx, index as u32, js_warning);
         }
+        let _ = result.set(&mut cx, "warnings", js_warnings);
     }
 
-    sampler_sysex_name
+    Ok(result)
 }
 
 #[neon::main]
@@ -3143,6 +6285,66 @@ fn main(mut cx: ModuleContext) -> NeonResult<()> {
     cx.export_function("sampler_request_keygroup_header", sampler_request_keygroup_header)?;
     cx.export_function("sampler_request_sample_header", sampler_request_sample_header)?;
     cx.export_function("sampler_request_sample_data", sampler_request_sample_data)?;
+    cx.export_function("sampler_cancel_sample_data", sampler_cancel_sample_data)?;
+    cx.export_function("sampler_request_sample_waveform", sampler_request_sample_waveform)?;
+    cx.export_function("sampler_clear_waveform_cache", sampler_clear_waveform_cache)?;
+    cx.export_function("sampler_find_sample_loops", sampler_find_sample_loops)?;
+    cx.export_function("sampler_set_sample_loop", sampler_set_sample_loop)?;
+    cx.export_function("sampler_crossfade_sample_loop", sampler_crossfade_sample_loop)?;
+    cx.export_function("sampler_process_sample", sampler_process_sample)?;
+    cx.export_function("sampler_import_wav", sampler_import_wav)?;
+    cx.export_function("sampler_export_wav", sampler_export_wav)?;
+    cx.export_function("sampler_assign_stereo_pair_to_keygroup", sampler_assign_stereo_pair_to_keygroup)?;
+    cx.export_function("sampler_build_program", sampler_build_program)?;
+    cx.export_function("sampler_auto_map_program", sampler_auto_map_program)?;
+    cx.export_function("sampler_auto_map_names", sampler_auto_map_names)?;
+    cx.export_function("sampler_validate_name", sampler_validate_name)?;
+    cx.export_function("sampler_library_open", sampler_library_open)?;
+    cx.export_function("sampler_library_index_volume", sampler_library_index_volume)?;
+    cx.export_function("sampler_library_index_resident", sampler_library_index_resident)?;
+    cx.export_function("sampler_library_add_entries", sampler_library_add_entries)?;
+    cx.export_function("sampler_library_find", sampler_library_find)?;
+    cx.export_function("sampler_crawl_hard_disk", sampler_crawl_hard_disk)?;
+    cx.export_function("sampler_misc_data_registry", sampler_misc_data_registry)?;
+    cx.export_function("sampler_get_misc", sampler_get_misc)?;
+    cx.export_function("sampler_set_misc", sampler_set_misc)?;
+    cx.export_function("sampler_get_misc_name", sampler_get_misc_name)?;
+    cx.export_function("sampler_set_misc_name", sampler_set_misc_name)?;
+    cx.export_function("sampler_get_effect", sampler_get_effect)?;
+    cx.export_function("sampler_set_effect", sampler_set_effect)?;
+    cx.export_function("sampler_get_reverb", sampler_get_reverb)?;
+    cx.export_function("sampler_set_reverb", sampler_set_reverb)?;
+    cx.export_function("sampler_export_fx_file", sampler_export_fx_file)?;
+    cx.export_function("sampler_import_fx_file", sampler_import_fx_file)?;
+    cx.export_function("sampler_effects_and_reverbs", sampler_effects_and_reverbs)?;
+    cx.export_function("sampler_effect_part", sampler_effect_part)?;
+    cx.export_function("sampler_reverb_part", sampler_reverb_part)?;
+    cx.export_function("sampler_cue_list", sampler_cue_list)?;
+    cx.export_function("sampler_cue_list_add", sampler_cue_list_add)?;
+    cx.export_function("sampler_cue_list_edit", sampler_cue_list_edit)?;
+    cx.export_function("sampler_cue_list_delete", sampler_cue_list_delete)?;
+    cx.export_function("sampler_cue_list_move", sampler_cue_list_move)?;
+    cx.export_function("sampler_cue_list_sort", sampler_cue_list_sort)?;
+    cx.export_function("sampler_take_list", sampler_take_list)?;
+    cx.export_function("sampler_take", sampler_take)?;
+    cx.export_function("sampler_take_update", sampler_take_update)?;
+    cx.export_function("sampler_take_list_export", sampler_take_list_export)?;
+    cx.export_function("sampler_cue_list_export_midi_file", sampler_cue_list_export_midi_file)?;
+    cx.export_function("sampler_cue_list_import_midi_file", sampler_cue_list_import_midi_file)?;
+    cx.export_function("sampler_queue_program_header_change", sampler_queue_program_header_change)?;
+    cx.export_function("sampler_queue_keygroup_header_change", sampler_queue_keygroup_header_change)?;
+    cx.export_function("sampler_flush_header_changes", sampler_flush_header_changes)?;
+    cx.export_function("sampler_undo", sampler_undo)?;
+    cx.export_function("sampler_redo", sampler_redo)?;
+    cx.export_function("sampler_journal", sampler_journal)?;
+    cx.export_function("sampler_journal_checkpoint", sampler_journal_checkpoint)?;
+    cx.export_function("sampler_journal_restore_checkpoint", sampler_journal_restore_checkpoint)?;
+    cx.export_function("sampler_journal_set_enabled", sampler_journal_set_enabled)?;
+    cx.export_function("sampler_journal_clear", sampler_journal_clear)?;
+    cx.export_function("sampler_journal_save", sampler_journal_save)?;
+    cx.export_function("sampler_journal_load", sampler_journal_load)?;
+    cx.export_function("sampler_sample_references", sampler_sample_references)?;
+    cx.export_function("sampler_sample_dependencies", sampler_sample_dependencies)?;
     cx.export_function("sampler_delete_program", sampler_delete_program)?;
     cx.export_function("sampler_delete_keygroup", sampler_delete_keygroup)?;
     cx.export_function("sampler_delete_sample", sampler_delete_sample)?;
@@ -3226,15 +6428,24 @@ fn kick_off_midir() {
         let mut output_port: Option<MidiOutputPort> = None;
         let mut client_request_received = Arc::new(Mutex::new(false));
         let mut sample_dump_packets_to_send = Arc::new(Mutex::new(VecDeque::<Vec<u8>>::new()));
-        let mut expected_sample_dump_packets = Arc::new(Mutex::new(0));
+        let expected_sample_dump_packets = Arc::new(Mutex::new((0u32, 0))); // (request generation, packets expected) - every request and cancel starts a new generation
+        let expected_hard_disk_directory_entries: Arc<Mutex<Option<usize>>> = Arc::new(Mutex::new(None)); // set by each directory request
 
         let mut midi_in = MidiInput::new("sampler sysex editor input").unwrap();
         let mut midi_out = MidiOutput::new("sampler sysex editor output").unwrap();
 
         let mut sysex_to_sampler_queue: Arc<Mutex<VecDeque<Vec<u8>>>> = Arc::new(Mutex::new(VecDeque::new()));
         let mut string_buf = "".to_string();
+        let mut batched_header_changes = VecDeque::<Vec<u8>>::new(); // queued messages whose replies nobody waits for
+        let discard_next_reply = Arc::new(Mutex::new(false));
 
         while keep_alive {
+            let header_writes = match HEADER_CHANGES.lock() {
+                Ok(mut header_changes) if header_changes.is_due(std::time::Instant::now()) => header_changes.take(),
+                _ => vec![],
+            };
+            queue_header_writes(&header_writes, &sysex_to_sampler_queue, &mut batched_header_changes);
+
             if let Ok(mut client_request_received) = client_request_received.lock() {
                 if let Ok(mut sysex_to_sampler_queue) = sysex_to_sampler_queue.lock() {
                     if !*client_request_received && sysex_to_sampler_queue.len() > 0 {
@@ -3243,8 +6454,15 @@ fn kick_off_midir() {
                             // check the sysex message opcode
                             // start with a hardcoded list then refactor
                             if let Some(opcode) = message.get(3) {
-                                if *opcode != 0x11 {
+                                if *opcode != 0x11 && *opcode != SAMPLE_DUMP_STANDARD_CANCEL {
                                     *client_request_received = true;
+
+                                    if batched_header_changes.front() == Some(&message) {
+                                        batched_header_changes.pop_front();
+                                        if let Ok(mut discard_next_reply) = discard_next_reply.lock() {
+                                            *discard_next_reply = true;
+                                        }
+                                    }
                                 }
                             }
     
@@ -3301,6 +6519,8 @@ fn kick_off_midir() {
                             let out_comm_channels_tx = out_comm_channels.tx.clone();
                             let client_request_received = client_request_received.clone();
                             let expected_sample_dump_packets = expected_sample_dump_packets.clone();
+                            let expected_hard_disk_directory_entries = expected_hard_disk_directory_entries.clone();
+                            let discard_next_reply = discard_next_reply.clone();
                             let sample_dump_packet_ack_handler = 
                                             SampleSysexSampleDumpAckMessageHandler::new(sample_dump_packets_to_send.clone(), sysex_to_sampler_queue.clone());
                             let mut sample_sysex_message_processor = SampleSysexMessageProcessor::new();
@@ -3310,20 +6530,42 @@ fn kick_off_midir() {
                                 "", 
                                 move |_, message, _| {
                                     info!("Output connection to sampler callback: Entered...");
+                                    let message_vec = match sample_sysex_message_processor.assemble(message) {
+                                        Some(message_vec) => message_vec,
+                                        None => {
+                                            info!("Output connection to sampler callback: Waiting for the rest of the sysex message.");
+                                            return
+                                        }
+                                    };
                                     let mut string_buf = "".to_string();
 
-                                    for value in message.iter() {
+                                    for value in message_vec.iter() {
                                         string_buf.push_str(format!("{}, ", value).as_str());
                                     }
 
                                     if let Ok(mut client_request_received) = client_request_received.lock() {
                                         if let Ok(expected_sample_dump_packets) = expected_sample_dump_packets.lock() {
-                                            sample_sysex_message_processor.sample_dump_packet_message_handler_mut().set_expected_sample_dump_data_packet_count(*expected_sample_dump_packets);
+                                            let (request_generation, expected_packets) = *expected_sample_dump_packets;
+                                            sample_sysex_message_processor.sample_dump_packet_message_handler_mut().set_expected_sample_dump_data_packet_count(request_generation, expected_packets);
+                                        }
+                                        if let Ok(mut expected_hard_disk_directory_entries) = expected_hard_disk_directory_entries.lock() {
+                                            if let Some(expected_number_of_entries) = expected_hard_disk_directory_entries.take() {
+                                                sample_sysex_message_processor.hard_disk_directory_entries_message_handler_mut().set_expected_number_of_entries(expected_number_of_entries);
+                                            }
+                                        }
+                                        // only a reply can clear the flag, not something the sampler sent unasked
+                                        let discard_reply = *client_request_received && match discard_next_reply.lock() {
+                                            Ok(mut discard_next_reply) => std::mem::replace(&mut *discard_next_reply, false),
+                                            Err(_) => false,
+                                        };
+                                        if discard_reply {
+                                            info!("Output connection to sampler callback: Reply to a batched header change: {}", string_buf.as_str());
+                                            string_buf.clear();
+                                            *client_request_received = false;
                                         }
-                                        if *client_request_received {
+                                        else if *client_request_received {
                                             info!("Output connection to sampler callback: Processing client requested sampler sysex message...");
                                             info!("Output connection to sampler callback: Received from sampler: {}", string_buf.as_str());
-                                            let message_vec = message.to_vec();
                                             if !sample_sysex_message_processor.handle_message(&message_vec, &out_comm_channels_tx) {
                                                 info!("Output connection to sampler callback: Could not find a message handler.");
                                                 if sample_dump_packet_ack_handler.can_handle(&message_vec) {
@@ -3337,12 +6579,23 @@ fn kick_off_midir() {
                                             info!("Output connection to sampler callback: Processing sampler sysex message...");
                                             info!("Output connection to sampler callback: Received from sampler: {}", string_buf.as_str());
 
-                                            let message_vec = message.to_vec();
                                             if sample_dump_packet_ack_handler.can_handle(&message_vec) {
                                                 sample_dump_packet_ack_handler.handle(&message_vec, &out_comm_channels_tx);
                                             }
                                             else if sample_sysex_message_processor.sample_dump_packet_message_handler().can_handle(&message_vec) {
                                                 sample_sysex_message_processor.sample_dump_packet_message_handler_mut().handle_mut(&message_vec, &out_comm_channels_tx);
+
+                                                // the dump has completed - stop the next message being treated as part of it
+                                                if !sample_sysex_message_processor.sample_dump_packet_message_handler().is_receiving() {
+                                                    if let Ok(mut expected_sample_dump_packets) = expected_sample_dump_packets.lock() {
+                                                        expected_sample_dump_packets.1 = 0;
+                                                    }
+                                                }
+                                            }
+                                            // the rest of a directory listing that was split over several replies
+                                            else if sample_sysex_message_processor.hard_disk_directory_entries_message_handler().is_receiving()
+                                                && sample_sysex_message_processor.hard_disk_directory_entries_message_handler().can_handle(&message_vec) {
+                                                sample_sysex_message_processor.hard_disk_directory_entries_message_handler_mut().handle_mut(&message_vec, &out_comm_channels_tx);
                                             }
 
                                             string_buf.clear();
@@ -3404,6 +6657,13 @@ fn kick_off_midir() {
                     IncomingEvent::SamplerEvent(sampler_event) => {
                         info!("Client request for sampler received.");
 
+                        let header_writes = match (sampler_event.needs_all_header_changes(), sampler_event.header_program(), HEADER_CHANGES.lock()) {
+                            (true, _, Ok(mut header_changes)) => header_changes.take(),
+                            (false, Some(program_number), Ok(mut header_changes)) => header_changes.take_program(program_number),
+                            _ => vec![],
+                        };
+                        queue_header_writes(&header_writes, &sysex_to_sampler_queue, &mut batched_header_changes);
+
                         match sampler_event {
                             IncomingSamplerEvent::NewProgram(program_number, payload) => {
                                 info!("Received new program from client.");
@@ -3435,15 +6695,15 @@ fn kick_off_midir() {
                                     sysex_to_sampler_queue.push_back(message);
                                 }
                             }
-                            IncomingSamplerEvent::NewSampleFromTemplate(sample_number, template, payload) => {
-                                info!("Received new sample from template from client.");
-
-                                info!("NewSampleFromTemplate: Sending new sample from template to sampler.");
+                            // a rendered template goes up the same way as any other sample
+                            IncomingSamplerEvent::UploadSample(sample_number, header, samples) | IncomingSamplerEvent::NewSampleFromTemplate(sample_number, header, samples) => {
+                                info!("Received upload sample from client.");
+                                info!("UploadSample: Sending sample header to sampler.");
                                 let mut message = vec![];
                                 let sample_number_lsb = (sample_number & U16_LSB_TO_AKAI_U8_MASK) as u8;
                                 let sample_number_msb = (sample_number >> U16_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT) as u8;
 
-                                info!("NewSampleFromTemplate: Sample number lsb={}, msb={}", sample_number_lsb, sample_number_msb);
+                                info!("UploadSample: Sample number lsb={}, msb={}", sample_number_lsb, sample_number_msb);
 
                                 message.push(START_OF_SYSTEM_EXCLUSIVE);
                                 message.push(SAMPLER_MANUFACTURER_CODE);
@@ -3453,8 +6713,7 @@ fn kick_off_midir() {
                                 message.push(sample_number_lsb);
                                 message.push(sample_number_msb);
 
-                                // handle the payload
-                                for element in payload.iter() {
+                                for element in header.iter() {
                                     message.push(element & 15);
                                     message.push(element >> 4);
                                 }
@@ -3465,74 +6724,11 @@ fn kick_off_midir() {
                                     if let Ok(mut sample_dump_packets_to_send) = sample_dump_packets_to_send.lock() {
                                         sysex_to_sampler_queue.push_back(message);
 
-                                        let frequency   = 440.0;
-                                        let mut oscillator = Oscillator::new(frequency, template);
-                                        let number_of_samples = (44100.0 / 440.0) as i32;
-                                        let mut samples = vec![];
-    
-                                        // data
-                                        for _ in 0..number_of_samples {
-                                            let sample = oscillator.next_sample();
-                                            samples.push((sample & 63) as u8);
-                                            samples.push(((sample >> 6) & 63) as u8);
-                                            samples.push(((sample >> 12) & 63) as u8);
-                                        }
-                                        
-                                        let full_packets = samples.len() / 120;
-                                        let partial_packet = samples.len() % 120;
-                                        let number_of_packets: usize = if partial_packet > 0 {
-                                            full_packets + 1
-                                        }
-                                        else {
-                                            full_packets
-                                        };
-                                        for packet_number in 0..number_of_packets {
-    
-                                            let mut midi_sample_dump_data_packet = vec![];
-                                            let mut checksum = 0;
-                                            let mut converted_samples_used = 0;
-    
-                                            midi_sample_dump_data_packet.push(START_OF_SYSTEM_EXCLUSIVE);
-                                            midi_sample_dump_data_packet.push(SYSEX_NON_REAL_TIME_CATEGORY);
-                                            checksum ^= SYSEX_NON_REAL_TIME_CATEGORY;
-                                            midi_sample_dump_data_packet.push(0x00);
-                                            checksum ^= 0x00;
-                                            midi_sample_dump_data_packet.push(SAMPLE_DUMP_STANDARD_DATA_PACKET);
-                                            checksum ^= SAMPLE_DUMP_STANDARD_DATA_PACKET;
-                                            midi_sample_dump_data_packet.push(packet_number as u8);
-                                            checksum ^= packet_number as u8;
-    
-                                            // loop until 120 samples or end
-                                            while converted_samples_used < 120 && samples.len() > 0 {
-                                                let sample = samples.remove(0);
-                                                checksum ^= sample;
-                                                midi_sample_dump_data_packet.push(sample);
-                                                converted_samples_used += 1;
-                                            }
-    
-                                            // if less than 120 in real data pad up to 120 with 0s
-                                            while converted_samples_used < 120 {
-                                                checksum ^= 0;
-                                                midi_sample_dump_data_packet.push(0);
-                                                converted_samples_used += 1;
-                                            }
-    
-                                            // finish the checksum
-                                            checksum &= 0x7F;
-                                            midi_sample_dump_data_packet.push(checksum);
-    
-                                            midi_sample_dump_data_packet.push(EOX);
-    
-                                            info!("NewSampleFromTemplate: Midi sample dump: ");
-                                            let mut sample_dump_packet_display = "NewSampleFromTemplate: Midi sample dump: ".to_string();
-                                            for value in midi_sample_dump_data_packet.iter() {
-                                                sample_dump_packet_display.push_str(format!("{}, ", value).as_str());
-                                            }
-                                            info!("{}", sample_dump_packet_display);
-
-                                            info!("NewSampleFromTemplate: Adding sample dump packet #{} to sample_dump_packets_to_send.", packet_number);
-                                            sample_dump_packets_to_send.push_back(midi_sample_dump_data_packet);
-                                        }
+                                        // the packets are sent one at a time as the sampler acknowledges them
+                                        let packets = sample_dump::data_packets(&sample_dump::sample_words_to_bytes(&samples));
+                                        info!("UploadSample: Adding {} sample dump packets to sample_dump_packets_to_send.", packets.len());
+                                        sample_dump_packets_to_send.clear();
+                                        sample_dump_packets_to_send.extend(packets);
                                     }
                                 }
                             }
@@ -3928,7 +7124,7 @@ fn kick_off_midir() {
                                     sysex_to_sampler_queue.push_back(message);
                                 }
                             }
-                            IncomingSamplerEvent::RequestSampleData(sample_number, number_of_samples) => {
+                            IncomingSamplerEvent::RequestSampleData(sample_number, offset, number_of_samples) => {
                                 info!("Received request sample header from client.");
                                 info!("Sending request sample header to sampler.");
                                 let mut message = vec![];
@@ -3939,7 +7135,13 @@ fn kick_off_midir() {
                                 let number_of_samples_byte3 = ((number_of_samples >> (U32_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT * 2)) & U32_LSB_TO_AKAI_U8_MASK) as u8;
                                 let number_of_samples_msb = ((number_of_samples >> (U32_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT * 3)) & U32_LSB_TO_AKAI_U8_MASK) as u8;
 
+                                let offset_lsb = (offset & U32_LSB_TO_AKAI_U8_MASK) as u8;
+                                let offset_byte2 = ((offset >> U32_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT) & U32_LSB_TO_AKAI_U8_MASK) as u8;
+                                let offset_byte3 = ((offset >> (U32_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT * 2)) & U32_LSB_TO_AKAI_U8_MASK) as u8;
+                                let offset_msb = ((offset >> (U32_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT * 3)) & U32_LSB_TO_AKAI_U8_MASK) as u8;
+
                                 info!("Sample number lsb={}, msb={}", sample_number_lsb, sample_number_msb);
+                                info!("Offset lsb={}, byte2={}, byte3={}, msb={}", offset_lsb, offset_byte2, offset_byte3, offset_msb);
                                 info!("Number of samples lsb={}, byte2={}, byte3={}, msb={}", number_of_samples_lsb, number_of_samples_byte2, number_of_samples_byte3, number_of_samples_msb);
 
                                 message.push(START_OF_SYSTEM_EXCLUSIVE);
@@ -3949,10 +7151,10 @@ fn kick_off_midir() {
                                 message.push(SAMPLER_IDENTITY);
                                 message.push(sample_number_lsb);
                                 message.push(sample_number_msb);
-                                message.push(0x00);
-                                message.push(0x00);
-                                message.push(0x00);
-                                message.push(0x00);
+                                message.push(offset_lsb);
+                                message.push(offset_byte2);
+                                message.push(offset_byte3);
+                                message.push(offset_msb);
                                 message.push(number_of_samples_lsb);
                                 message.push(number_of_samples_byte2);
                                 message.push(number_of_samples_byte3);
@@ -3966,15 +7168,25 @@ fn kick_off_midir() {
                                 }
 
                                 if let Ok(mut expected_sample_dump_packets) = expected_sample_dump_packets.lock() {
-                                    let sample_dump_data_count = number_of_samples * 3;
-                                    let remainder = sample_dump_data_count % 120; 
-                                    let number_of_packets = if remainder > 0 {
-                                        sample_dump_data_count / 120 + 1
-                                    }
-                                    else {
-                                        sample_dump_data_count / 120
-                                    };
-                                    *expected_sample_dump_packets = number_of_packets as i32;
+                                    // each sample word takes 3 bytes
+                                    *expected_sample_dump_packets = (expected_sample_dump_packets.0.wrapping_add(1), sample_dump::number_of_packets(number_of_samples as usize * 3) as i32);
+                                }
+
+                                if let Ok(mut sysex_to_sampler_queue) = sysex_to_sampler_queue.lock() {
+                                    sysex_to_sampler_queue.push_back(message);
+                                }
+                            }
+                            IncomingSamplerEvent::CancelSampleData => {
+                                info!("Received cancel sample data from client.");
+                                info!("Sending sample dump cancel to sampler.");
+                                let message = vec![START_OF_SYSTEM_EXCLUSIVE, SYSEX_NON_REAL_TIME_CATEGORY, 0x00, SAMPLE_DUMP_STANDARD_CANCEL, 0x00, EOX];
+
+                                if let Ok(mut expected_sample_dump_packets) = expected_sample_dump_packets.lock() {
+                                    *expected_sample_dump_packets = (expected_sample_dump_packets.0.wrapping_add(1), 0);
+                                }
+
+                                if let Ok(mut client_request_received) = client_request_received.lock() {
+                                    *client_request_received = false;
                                 }
 
                                 if let Ok(mut sysex_to_sampler_queue) = sysex_to_sampler_queue.lock() {
@@ -4094,6 +7306,42 @@ fn kick_off_midir() {
                                     sysex_to_sampler_queue.push_back(message);
                                 }
                             }
+                            IncomingSamplerEvent::ResponseCueList(event_number, selector, offset_into_structure, data) => {
+                                info!("Received response (change sampler data) cue list from client.");
+                                info!("Sending response (change sampler data) cue list to sampler.");
+                                let mut message = vec![];
+                                let event_number_lsb = (event_number & U16_LSB_TO_AKAI_U8_MASK) as u8;
+                                let event_number_msb = (event_number >> U16_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT) as u8;
+                                let number_of_bytes_of_data_lsb = (data.len() as u16 & U16_LSB_TO_AKAI_U8_MASK) as u8;
+                                let number_of_bytes_of_data_msb = ((data.len() as u16) >> U16_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT) as u8;
+                                let offset_into_structure_lsb = (offset_into_structure & U16_LSB_TO_AKAI_U8_MASK) as u8;
+                                let offset_into_structure_msb = (offset_into_structure >> U16_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT) as u8;
+
+                                message.push(START_OF_SYSTEM_EXCLUSIVE);
+                                message.push(SAMPLER_MANUFACTURER_CODE);
+                                message.push(0x00);
+                                message.push(S3000SysexFunctionCodes::ResponseCueList as u8);
+                                message.push(SAMPLER_IDENTITY);
+                                message.push(event_number_lsb);
+                                message.push(event_number_msb);
+                                message.push(selector);
+                                message.push(offset_into_structure_lsb);
+                                message.push(offset_into_structure_msb);
+                                message.push(number_of_bytes_of_data_lsb);
+                                message.push(number_of_bytes_of_data_msb);
+
+                                // nibbled data being sent
+                                for value in data.iter() {
+                                    message.push(value & 15); // lsb first
+                                    message.push(value >> 4); // msb last
+                                }
+
+                                message.push(EOX);
+
+                                if let Ok(mut sysex_to_sampler_queue) = sysex_to_sampler_queue.lock() {
+                                    sysex_to_sampler_queue.push_back(message);
+                                }
+                            }
                             IncomingSamplerEvent::RequestTakeList(take_number, selector, offset_into_structure, number_of_bytes_of_data) => {
                                 info!("Received request take list from client.");
                                 info!("Sending request take list to sampler.");
@@ -4128,6 +7376,42 @@ fn kick_off_midir() {
                                     sysex_to_sampler_queue.push_back(message);
                                 }
                             }
+                            IncomingSamplerEvent::ResponseTakeList(take_number, selector, offset_into_structure, data) => {
+                                info!("Received response (change sampler data) take list from client.");
+                                info!("Sending response (change sampler data) take list to sampler.");
+                                let mut message = vec![];
+                                let take_number_lsb = (take_number & U16_LSB_TO_AKAI_U8_MASK) as u8;
+                                let take_number_msb = (take_number >> U16_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT) as u8;
+                                let number_of_bytes_of_data_lsb = (data.len() as u16 & U16_LSB_TO_AKAI_U8_MASK) as u8;
+                                let number_of_bytes_of_data_msb = ((data.len() as u16) >> U16_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT) as u8;
+                                let offset_into_structure_lsb = (offset_into_structure & U16_LSB_TO_AKAI_U8_MASK) as u8;
+                                let offset_into_structure_msb = (offset_into_structure >> U16_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT) as u8;
+
+                                message.push(START_OF_SYSTEM_EXCLUSIVE);
+                                message.push(SAMPLER_MANUFACTURER_CODE);
+                                message.push(0x00);
+                                message.push(S3000SysexFunctionCodes::ResponseTakeList as u8);
+                                message.push(SAMPLER_IDENTITY);
+                                message.push(take_number_lsb);
+                                message.push(take_number_msb);
+                                message.push(selector);
+                                message.push(offset_into_structure_lsb);
+                                message.push(offset_into_structure_msb);
+                                message.push(number_of_bytes_of_data_lsb);
+                                message.push(number_of_bytes_of_data_msb);
+
+                                // nibbled data being sent
+                                for value in data.iter() {
+                                    message.push(value & 15); // lsb first
+                                    message.push(value >> 4); // msb last
+                                }
+
+                                message.push(EOX);
+
+                                if let Ok(mut sysex_to_sampler_queue) = sysex_to_sampler_queue.lock() {
+                                    sysex_to_sampler_queue.push_back(message);
+                                }
+                            }
                             IncomingSamplerEvent::RequestMiscellaneousBytes(data_index, data_bank_number) => {
                                 info!("Received request miscellaneous bytes from client.");
                                 info!("Sending request miscellaneous bytes to sampler.");
@@ -4158,14 +7442,14 @@ fn kick_off_midir() {
                                     sysex_to_sampler_queue.push_back(message);
                                 }
                             }
-                            IncomingSamplerEvent::ResponseMiscellaneousBytes(data_index, data_bank_number, changed_value, name_data) => {
+                            IncomingSamplerEvent::ResponseMiscellaneousBytes(data_index, data_bank_number, data) => {
                                 info!("Received reponse miscellaneous bytes from client.");
                                 info!("Sending reponse miscellaneous bytes to sampler.");
                                 let mut message = vec![];
                                 let data_index_lsb = (data_index & U16_LSB_TO_AKAI_U8_MASK) as u8;
                                 let data_index_msb = (data_index >> U16_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT) as u8;
-                                let number_of_bytes_of_data_lsb = (MISCELLANEOUS_BYTES_SIZES[data_bank_number as usize - 1] & U16_LSB_TO_AKAI_U8_MASK) as u8;
-                                let number_of_bytes_of_data_msb = (MISCELLANEOUS_BYTES_SIZES[data_bank_number as usize - 1] >> U16_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT) as u8;
+                                let number_of_bytes_of_data_lsb = ((data.len() as u16) & U16_LSB_TO_AKAI_U8_MASK) as u8;
+                                let number_of_bytes_of_data_msb = ((data.len() as u16) >> U16_MSB_TO_AKAI_U8_BIT_RIGHT_SHIFT_AMOUNT) as u8;
 
                                 info!("Data index lsb={}, msb={}", data_index_lsb, data_index_msb);
                                 info!("Number of bytes of data lsb={}, msb={}", number_of_bytes_of_data_lsb, number_of_bytes_of_data_msb);
@@ -4183,38 +7467,7 @@ fn kick_off_midir() {
                                 message.push(number_of_bytes_of_data_lsb);
                                 message.push(number_of_bytes_of_data_msb);
 
-                                match data_bank_number {
-                                    1 => {
-                                        message.push((changed_value & 15) as u8);
-                                        message.push(((changed_value >> 4) & 15) as u8);
-                                    }
-                                    2 => {
-                                        message.push((changed_value & 15) as u8);
-                                        message.push(((changed_value >> 4) & 15) as u8);
-                                        message.push(((changed_value >> 8) & 15) as u8);
-                                        message.push(((changed_value >> 12) & 15) as u8);
-                                    }
-                                    3 => {
-                                        message.push((changed_value & 15) as u8);
-                                        message.push(((changed_value >> 4) & 15) as u8);
-                                        message.push(((changed_value >> 8) & 15) as u8);
-                                        message.push(((changed_value >> 12) & 15) as u8);
-                                        message.push(((changed_value >> 16) & 15) as u8);
-                                        message.push(((changed_value >> 20) & 15) as u8);
-                                        message.push(((changed_value >> 24) & 15) as u8);
-                                        message.push(((changed_value >> 28) & 15) as u8);
-                                    }
-                                    6 => {
-                                        if let Some(name) = name_data {
-                                            for letter in name.iter() {
-                                                message.push((*letter & 15) as u8);
-                                                message.push(((*letter >> 4) & 15) as u8);
-                                            }
-                                        }
-                                    }
-                                    _ => ()
-                                }
-
+                                message.extend(misc_data::to_nibbles(&data));
 
                                 message.push(EOX);
 
@@ -4222,6 +7475,7 @@ fn kick_off_midir() {
                                     sysex_to_sampler_queue.push_back(message);
                                 }
                             }
+                            IncomingSamplerEvent::FlushHeaderChanges => (), // already flushed above
                             IncomingSamplerEvent::RequestS1000MiscellaneousData => {
                                 info!("Received request s1000 miscellaneous data from client.");
                                 info!("Sending request s1000 miscellaneous data to sampler.");
@@ -4598,6 +7852,10 @@ fn kick_off_midir() {
                                 message.push(number_of_bytes_of_data_msb);
                                 message.push(EOX);
 
+                                if let Ok(mut expected_hard_disk_directory_entries) = expected_hard_disk_directory_entries.lock() {
+                                    *expected_hard_disk_directory_entries = Some(0);
+                                }
+
                                 if let Ok(mut sysex_to_sampler_queue) = sysex_to_sampler_queue.lock() {
                                     sysex_to_sampler_queue.push_back(message);
                                 }
@@ -4629,6 +7887,10 @@ fn kick_off_midir() {
                                 message.push(number_of_bytes_of_data_msb);
                                 message.push(EOX);
 
+                                if let Ok(mut expected_hard_disk_directory_entries) = expected_hard_disk_directory_entries.lock() {
+                                    *expected_hard_disk_directory_entries = Some(number_of_entries_to_get as usize);
+                                }
+
                                 if let Ok(mut sysex_to_sampler_queue) = sysex_to_sampler_queue.lock() {
                                     sysex_to_sampler_queue.push_back(message);
                                 }
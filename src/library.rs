@@ -0,0 +1,401 @@
+// A local index of the programs, samples and other files seen on the sampler's disks or in disk
+// images, so questions like "which volume has STRINGS 2 at 44.1kHz" do not need a rescan.
+
+use std::{sync::Mutex, time::{SystemTime, UNIX_EPOCH}};
+
+use rusqlite::{params, types::Value, Connection, Row};
+
+use crate::sample_header::{self, SampleHeader};
+
+pub const SAMPLER_SOURCE: &str = "sampler"; // anything else is the path of a disk image
+pub const MEMORY_SOURCE: &str = "memory"; // what is resident in the sampler rather than on a disk
+
+const PROGRAM_NUMBER_OF_KEYGROUPS_OFFSET: usize = 42;
+const FINGERPRINT_SEGMENTS: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileKind {
+    Program,
+    Sample,
+    CueList,
+    TakeList,
+    Effects,
+    DrumInputs,
+    Other,
+}
+
+impl FileKind {
+    // the selectors used when asking for hard disk directory entries
+    pub fn from_selector(selector: u8) -> Self {
+        match selector {
+            1 => FileKind::Program,
+            2 => FileKind::Sample,
+            3 => FileKind::CueList,
+            4 => FileKind::TakeList,
+            5 => FileKind::Effects,
+            6 => FileKind::DrumInputs,
+            _ => FileKind::Other,
+        }
+    }
+
+    // the file type byte of a directory entry - a lower case letter with the top bit set
+    pub fn from_file_type(file_type: u8) -> Self {
+        match file_type & 0x7F {
+            b'p' => FileKind::Program,
+            b's' => FileKind::Sample,
+            b'c' => FileKind::CueList,
+            b't' => FileKind::TakeList,
+            b'x' => FileKind::Effects,
+            b'd' => FileKind::DrumInputs,
+            _ => FileKind::Other,
+        }
+    }
+
+    pub fn new(name: &str) -> Self {
+        match name {
+            "program" => FileKind::Program,
+            "sample" => FileKind::Sample,
+            "cue_list" => FileKind::CueList,
+            "take_list" => FileKind::TakeList,
+            "effects" => FileKind::Effects,
+            "drum_inputs" => FileKind::DrumInputs,
+            _ => FileKind::Other,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileKind::Program => "program",
+            FileKind::Sample => "sample",
+            FileKind::CueList => "cue_list",
+            FileKind::TakeList => "take_list",
+            FileKind::Effects => "effects",
+            FileKind::DrumInputs => "drum_inputs",
+            FileKind::Other => "other",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LibraryLocation {
+    pub source: String,
+    pub partition: u8,
+    pub volume: u8,
+    pub volume_name: String,
+}
+
+impl LibraryLocation {
+    // resident programs and samples may have come from any volume, or from none
+    pub fn memory() -> Self {
+        Self { source: MEMORY_SOURCE.to_string(), partition: 0, volume: 0, volume_name: String::new() }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LibraryEntry {
+    pub location: LibraryLocation,
+    pub name: String,
+    pub kind: FileKind,
+    pub file_type: Option<u8>,
+    pub model: Option<u8>,
+    pub sample_rate: Option<u32>,
+    pub sample_length: Option<u32>,
+    pub original_pitch: Option<u8>,
+    pub number_of_loops: Option<u8>,
+    pub number_of_keygroups: Option<u8>,
+    pub header: Option<Vec<u8>>,
+    pub fingerprint: Option<String>,
+    pub last_seen: u64, // seconds since the epoch
+}
+
+impl LibraryEntry {
+    pub fn new(location: LibraryLocation, name: &str, kind: FileKind) -> Self {
+        Self {
+            location,
+            name: name.trim_end().to_string(),
+            kind,
+            file_type: None,
+            model: None,
+            sample_rate: None,
+            sample_length: None,
+            original_pitch: None,
+            number_of_loops: None,
+            number_of_keygroups: None,
+            header: None,
+            fingerprint: None,
+            last_seen: now(),
+        }
+    }
+
+    pub fn sample(location: LibraryLocation, header_data: &[u8], fingerprint: Option<String>) -> Option<Self> {
+        let header = SampleHeader::from_bytes(header_data)?;
+        let mut entry = Self::new(location, header.name.as_str(), FileKind::Sample);
+
+        entry.sample_rate = Some(header.sample_rate as u32);
+        entry.sample_length = Some(header.sample_length);
+        entry.original_pitch = header_data.get(sample_header::ORIGINAL_PITCH_OFFSET).copied();
+        entry.number_of_loops = Some(header.number_of_loops);
+        entry.header = Some(header_data.to_vec());
+        entry.fingerprint = fingerprint;
+
+        Some(entry)
+    }
+
+    pub fn program(location: LibraryLocation, name: &str, header_data: &[u8]) -> Self {
+        let mut entry = Self::new(location, name, FileKind::Program);
+
+        entry.number_of_keygroups = header_data.get(PROGRAM_NUMBER_OF_KEYGROUPS_OFFSET).copied();
+        entry.header = Some(header_data.to_vec());
+
+        entry
+    }
+}
+
+// every field is optional - names match case insensitively and '*' matches anything
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LibraryQuery {
+    pub name: Option<String>,
+    pub kind: Option<FileKind>,
+    pub source: Option<String>,
+    pub partition: Option<u8>,
+    pub volume: Option<u8>,
+    pub sample_rate: Option<u32>,
+    pub fingerprint: Option<String>,
+}
+
+pub struct Library {
+    connection: Connection,
+}
+
+lazy_static! {
+    static ref OPEN_LIBRARY: Mutex<Option<Library>> = Mutex::new(None);
+}
+
+// replaces whichever library was open before
+pub fn open_library(path: &str) -> rusqlite::Result<()> {
+    let library = Library::open(path)?;
+    if let Ok(mut open_library) = OPEN_LIBRARY.lock() {
+        *open_library = Some(library);
+    }
+
+    Ok(())
+}
+
+// None when no library has been opened
+pub fn with_library<T>(action: impl FnOnce(&mut Library) -> rusqlite::Result<T>) -> Option<rusqlite::Result<T>> {
+    match OPEN_LIBRARY.lock() {
+        Ok(mut open_library) => open_library.as_mut().map(action),
+        Err(_) => None,
+    }
+}
+
+impl Library {
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        let connection = Connection::open(path)?;
+
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS library_entries (
+                id INTEGER PRIMARY KEY,
+                source TEXT NOT NULL,
+                partition INTEGER NOT NULL,
+                volume INTEGER NOT NULL,
+                volume_name TEXT NOT NULL,
+                name TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                file_type INTEGER,
+                model INTEGER,
+                sample_rate INTEGER,
+                sample_length INTEGER,
+                original_pitch INTEGER,
+                number_of_loops INTEGER,
+                number_of_keygroups INTEGER,
+                header BLOB,
+                fingerprint TEXT,
+                last_seen INTEGER NOT NULL,
+                UNIQUE (source, partition, volume, name, kind)
+            );
+            CREATE INDEX IF NOT EXISTS library_entries_name ON library_entries (name);
+            CREATE INDEX IF NOT EXISTS library_entries_fingerprint ON library_entries (fingerprint);"
+        )?;
+
+        Ok(Self { connection })
+    }
+
+    // all or nothing, and much quicker than one at a time
+    pub fn record_all(&mut self, entries: &[LibraryEntry]) -> rusqlite::Result<()> {
+        let transaction = self.connection.transaction()?;
+        for entry in entries.iter() {
+            record_with(&transaction, entry)?;
+        }
+
+        transaction.commit()
+    }
+
+    // A complete listing of one volume - anything recorded against it that is no longer there goes,
+    // while header fields and fingerprints of files that are still there are kept.
+    pub fn record_volume(&mut self, location: &LibraryLocation, entries: &[LibraryEntry]) -> rusqlite::Result<()> {
+        let transaction = self.connection.transaction()?;
+        {
+            let mut statement = transaction.prepare("SELECT id, name, kind FROM library_entries WHERE source = ?1 AND partition = ?2 AND volume = ?3")?;
+            let recorded: Vec<(i64, String, String)> = statement
+                .query_map(params![location.source, location.partition, location.volume], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                .collect::<rusqlite::Result<Vec<(i64, String, String)>>>()?;
+
+            for (id, name, kind) in recorded.iter() {
+                if !entries.iter().any(|entry| entry.name == *name && entry.kind.name() == kind.as_str()) {
+                    transaction.execute("DELETE FROM library_entries WHERE id = ?1", params![id])?;
+                }
+            }
+        }
+        for entry in entries.iter() {
+            record_with(&transaction, entry)?;
+        }
+
+        transaction.commit()
+    }
+
+    pub fn find(&self, query: &LibraryQuery) -> rusqlite::Result<Vec<LibraryEntry>> {
+        let mut conditions: Vec<&str> = vec![];
+        let mut values: Vec<Value> = vec![];
+
+        if let Some(name) = query.name.as_ref() {
+            conditions.push("name LIKE ? ESCAPE '\\'");
+            values.push(Value::Text(like_pattern(name)));
+        }
+        if let Some(kind) = query.kind {
+            conditions.push("kind = ?");
+            values.push(Value::Text(kind.name().to_string()));
+        }
+        if let Some(source) = query.source.as_ref() {
+            conditions.push("source = ?");
+            values.push(Value::Text(source.clone()));
+        }
+        if let Some(partition) = query.partition {
+            conditions.push("partition = ?");
+            values.push(Value::Integer(partition as i64));
+        }
+        if let Some(volume) = query.volume {
+            conditions.push("volume = ?");
+            values.push(Value::Integer(volume as i64));
+        }
+        if let Some(sample_rate) = query.sample_rate {
+            conditions.push("sample_rate = ?");
+            values.push(Value::Integer(sample_rate as i64));
+        }
+        if let Some(fingerprint) = query.fingerprint.as_ref() {
+            conditions.push("fingerprint = ?");
+            values.push(Value::Text(fingerprint.clone()));
+        }
+
+        let mut sql = "SELECT source, partition, volume, volume_name, name, kind, file_type, model, sample_rate, sample_length,
+            original_pitch, number_of_loops, number_of_keygroups, header, fingerprint, last_seen FROM library_entries".to_string();
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(conditions.join(" AND ").as_str());
+        }
+        sql.push_str(" ORDER BY source, partition, volume, kind, name");
+
+        let mut statement = self.connection.prepare(sql.as_str())?;
+        let entries = statement.query_map(rusqlite::params_from_iter(values.iter()), entry_from_row)?;
+
+        entries.collect()
+    }
+}
+
+// details already known are kept when the entry is seen again without them, e.g. in a directory listing
+fn record_with(connection: &Connection, entry: &LibraryEntry) -> rusqlite::Result<()> {
+    connection.execute(
+        "INSERT INTO library_entries (source, partition, volume, volume_name, name, kind, file_type, model, sample_rate, sample_length,
+            original_pitch, number_of_loops, number_of_keygroups, header, fingerprint, last_seen)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+        ON CONFLICT (source, partition, volume, name, kind) DO UPDATE SET
+            volume_name = excluded.volume_name,
+            file_type = COALESCE(excluded.file_type, file_type),
+            model = COALESCE(excluded.model, model),
+            sample_rate = COALESCE(excluded.sample_rate, sample_rate),
+            sample_length = COALESCE(excluded.sample_length, sample_length),
+            original_pitch = COALESCE(excluded.original_pitch, original_pitch),
+            number_of_loops = COALESCE(excluded.number_of_loops, number_of_loops),
+            number_of_keygroups = COALESCE(excluded.number_of_keygroups, number_of_keygroups),
+            header = COALESCE(excluded.header, header),
+            fingerprint = COALESCE(excluded.fingerprint, fingerprint),
+            last_seen = excluded.last_seen",
+        params![
+            entry.location.source,
+            entry.location.partition,
+            entry.location.volume,
+            entry.location.volume_name,
+            entry.name,
+            entry.kind.name(),
+            entry.file_type,
+            entry.model,
+            entry.sample_rate,
+            entry.sample_length,
+            entry.original_pitch,
+            entry.number_of_loops,
+            entry.number_of_keygroups,
+            entry.header,
+            entry.fingerprint,
+            entry.last_seen as i64,
+        ],
+    )?;
+
+    Ok(())
+}
+
+fn entry_from_row(row: &Row) -> rusqlite::Result<LibraryEntry> {
+    Ok(LibraryEntry {
+        location: LibraryLocation {
+            source: row.get(0)?,
+            partition: row.get(1)?,
+            volume: row.get(2)?,
+            volume_name: row.get(3)?,
+        },
+        name: row.get(4)?,
+        kind: FileKind::new(row.get::<_, String>(5)?.as_str()),
+        file_type: row.get(6)?,
+        model: row.get(7)?,
+        sample_rate: row.get(8)?,
+        sample_length: row.get(9)?,
+        original_pitch: row.get(10)?,
+        number_of_loops: row.get(11)?,
+        number_of_keygroups: row.get(12)?,
+        header: row.get(13)?,
+        fingerprint: row.get(14)?,
+        last_seen: row.get::<_, i64>(15)? as u64,
+    })
+}
+
+// sampler names are upper case, so matching ignores case; '*' is the only wildcard
+fn like_pattern(name: &str) -> String {
+    name.trim_end().chars().map(|character| match character {
+        '%' | '_' | '\\' => format!("\\{}", character),
+        '*' => "%".to_string(),
+        _ => character.to_string(),
+    }).collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0)
+}
+
+// The loudness contour of the audio relative to its own peak, in 32 steps, plus the length.
+// Copies of a sample share it whatever their name or level, so it finds duplicates across volumes.
+pub fn audio_fingerprint(samples: &[i16]) -> String {
+    if samples.is_empty() {
+        return "0:".to_string()
+    }
+
+    let segment_length = samples.len().div_ceil(FINGERPRINT_SEGMENTS);
+    let levels: Vec<f64> = samples.chunks(segment_length).map(|segment| {
+        (segment.iter().map(|sample| (*sample as f64).powi(2)).sum::<f64>() / segment.len() as f64).sqrt()
+    }).collect();
+    let loudest = levels.iter().cloned().fold(0.0, f64::max);
+
+    let contour: String = levels.iter().map(|level| {
+        let step = if loudest > 0.0 { (level / loudest * 15.0).round() as u32 } else { 0 };
+        std::char::from_digit(step, 16).unwrap_or('0')
+    }).collect();
+
+    format!("{}:{}", samples.len(), contour)
+}
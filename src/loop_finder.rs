@@ -0,0 +1,163 @@
+// Proposes loop points inside a rough region of a sample.
+// Candidates start and end on rising zero crossings, their length is matched to the pitch period
+// found by autocorrelation and they are ranked by how badly the waveform jumps at the splice.
+
+pub const DEFAULT_NUMBER_OF_CANDIDATES: usize = 8;
+
+const MINIMUM_FREQUENCY: f64 = 20.0;
+const MAXIMUM_FREQUENCY: f64 = 4000.0;
+const MAXIMUM_CROSSINGS_PER_END: usize = 24;
+const PERIOD_TOLERANCE: f64 = 2.0; // samples
+const MAXIMUM_PITCH_ANALYSIS_LENGTH: usize = 8192;
+const MINIMUM_COMPARISON_WINDOW: usize = 16;
+const MAXIMUM_COMPARISON_WINDOW: usize = 256;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoopCandidate {
+    pub start: u32,
+    pub end: u32,
+    pub discontinuity: f64, // 0.0 is a seamless splice
+}
+
+impl LoopCandidate {
+    pub fn length(&self) -> u32 {
+        self.end - self.start
+    }
+}
+
+// samples hold the region being searched - the result is relative to the first of them
+pub fn find_loops(samples: &[i16], sample_rate: u32, number_of_candidates: usize) -> (Option<f64>, Vec<LoopCandidate>) {
+    let period = pitch_period(samples, sample_rate);
+    let crossings = rising_zero_crossings(samples);
+
+    if crossings.len() < 2 {
+        return (period, vec![])
+    }
+
+    let window = period
+        .map(|period| period.round() as usize)
+        .unwrap_or(MINIMUM_COMPARISON_WINDOW)
+        .clamp(MINIMUM_COMPARISON_WINDOW, MAXIMUM_COMPARISON_WINDOW);
+
+    let mut candidates = vec![];
+    let end_crossings = crossings.iter().rev().take(MAXIMUM_CROSSINGS_PER_END);
+
+    for end in end_crossings {
+        let start_crossings = crossings.iter().take_while(|start| *start < end).take(MAXIMUM_CROSSINGS_PER_END * 4);
+
+        for start in start_crossings {
+            let length = (end - start) as f64;
+
+            if let Some(period) = period {
+                let cycles = (length / period).round();
+                if cycles < 1.0 || (length - cycles * period).abs() > PERIOD_TOLERANCE {
+                    continue
+                }
+            }
+
+            candidates.push(LoopCandidate {
+                start: *start as u32,
+                end: *end as u32,
+                discontinuity: discontinuity(samples, *start, *end, window),
+            });
+        }
+    }
+
+    // prefer the smoothest splice, then the longest loop
+    candidates.sort_by(|a, b| {
+        a.discontinuity.partial_cmp(&b.discontinuity)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| b.length().cmp(&a.length()))
+    });
+    candidates.truncate(number_of_candidates);
+
+    (period, candidates)
+}
+
+// the fundamental period in samples from the first strong peak of the normalised autocorrelation
+pub fn pitch_period(samples: &[i16], sample_rate: u32) -> Option<f64> {
+    // the middle of the region is plenty to find the pitch and keeps the search quick
+    let excess = samples.len().saturating_sub(MAXIMUM_PITCH_ANALYSIS_LENGTH);
+    let samples = &samples[(excess / 2)..(samples.len() - (excess - excess / 2))];
+    let minimum_lag = ((sample_rate as f64 / MAXIMUM_FREQUENCY) as usize).max(2);
+    let maximum_lag = ((sample_rate as f64 / MINIMUM_FREQUENCY) as usize).min(samples.len() / 2);
+
+    if maximum_lag <= minimum_lag + 1 {
+        return None
+    }
+
+    let samples: Vec<f64> = samples.iter().map(|sample| *sample as f64).collect();
+    let correlations: Vec<f64> = (0..=(maximum_lag + 1)).map(|lag| normalised_autocorrelation(&samples, lag)).collect();
+    let strongest = correlations[minimum_lag..=maximum_lag].iter().cloned().fold(f64::MIN, f64::max);
+
+    if strongest <= 0.3 {
+        return None
+    }
+
+    // the first peak near the strongest one avoids picking a multiple of the period
+    for lag in minimum_lag..=maximum_lag {
+        let correlation = correlations[lag];
+
+        if correlation >= strongest * 0.9 && correlation >= correlations[lag - 1] && correlation >= correlations[lag + 1] {
+            // parabolic interpolation for a fractional period
+            let (previous, next) = (correlations[lag - 1], correlations[lag + 1]);
+            let denominator = previous - 2.0 * correlation + next;
+            let shift = if denominator.abs() > f64::EPSILON { 0.5 * (previous - next) / denominator } else { 0.0 };
+
+            return Some(lag as f64 + shift.clamp(-0.5, 0.5))
+        }
+    }
+
+    None
+}
+
+pub fn rising_zero_crossings(samples: &[i16]) -> Vec<usize> {
+    samples.windows(2)
+        .enumerate()
+        .filter(|(_, pair)| pair[0] < 0 && pair[1] >= 0)
+        .map(|(index, pair)| {
+            // take whichever side of the crossing is closer to zero
+            if (pair[0] as i32).abs() < (pair[1] as i32).abs() { index } else { index + 1 }
+        })
+        .collect()
+}
+
+// compares what plays after the loop end with what would have played without the jump
+// on both sides of the splice - relative to the level of the signal
+pub fn discontinuity(samples: &[i16], start: usize, end: usize, window: usize) -> f64 {
+    let window = window.min(samples.len() - end).min(end - start).max(1);
+    let mut difference = 0.0;
+    let mut level = 0.0;
+
+    for offset in 0..window {
+        let before_end = samples[end - 1 - offset.min(end - 1)] as f64;
+        let before_start = samples[start.saturating_sub(1 + offset)] as f64;
+        let after_start = samples[start + offset] as f64;
+        let after_end = samples[(end + offset).min(samples.len() - 1)] as f64;
+
+        difference += (after_start - after_end).powi(2) + (before_start - before_end).powi(2);
+        level += after_start.powi(2) + before_end.powi(2);
+    }
+
+    // the first sample of the loop takes the place of the sample at the end
+    let step = samples[start] as f64 - samples[end] as f64;
+
+    let level = (level / (2 * window) as f64).max(1.0);
+    (difference / (2 * window) as f64 + step.powi(2)).sqrt() / level.sqrt()
+}
+
+fn normalised_autocorrelation(samples: &[f64], lag: usize) -> f64 {
+    if lag >= samples.len() {
+        return 0.0
+    }
+
+    let (mut sum, mut energy_a, mut energy_b) = (0.0, 0.0, 0.0);
+    for index in 0..(samples.len() - lag) {
+        sum += samples[index] * samples[index + lag];
+        energy_a += samples[index] * samples[index];
+        energy_b += samples[index + lag] * samples[index + lag];
+    }
+
+    let energy = (energy_a * energy_b).sqrt();
+    if energy > 0.0 { sum / energy } else { 0.0 }
+}
@@ -10,12 +10,15 @@ import {
   Patch,
   Post,
   Put,
+  Query,
 } from '@nestjs/common';
 import {
   FileDetails,
   MidiService,
   ProgramDetails,
   S1000MiscellaneousDataType,
+  SampleDeleteMode,
+  SampleDeleteResult,
 } from './midi.service';
 import {
   ChorusEffect,
@@ -130,8 +133,18 @@ export class MidiController {
   }
 
   @Delete('sampler/sample/:sample_number')
-  samplerDeleteSample(@Param('sample_number') sampleNumber: number): boolean {
-    return this.midiService.samplerDeleteSample(sampleNumber);
+  samplerDeleteSample(
+    @Param('sample_number') sampleNumber: number,
+    @Query('mode') mode?: SampleDeleteMode,
+  ): SampleDeleteResult {
+    if (mode !== undefined && !['refuse', 'warn', 'cascade'].includes(mode)) {
+      throw new HttpException(
+        'The delete mode must be refuse, warn or cascade.',
+        HttpStatus.BAD_REQUEST,
+      );
+    }
+
+    return this.midiService.samplerDeleteSample(sampleNumber, mode);
   }
 
   @Delete('sampler/program/:program_number/keygroup/:keygroup_number')
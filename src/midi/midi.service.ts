@@ -29,6 +29,23 @@ export type ProgramDetails = {
   name: string;
 };
 
+// refuse leaves a sample that is still used by keygroup zones, warn deletes it anyway and
+// cascade clears the zones first
+export type SampleDeleteMode = 'refuse' | 'warn' | 'cascade';
+
+export type SampleZoneReference = {
+  program: number;
+  program_name: string;
+  keygroup: number;
+  zone: number;
+};
+
+export type SampleDeleteResult = {
+  deleted: boolean;
+  refused: boolean;
+  references: SampleZoneReference[];
+};
+
 @Injectable()
 export abstract class MidiService {
   abstract getMidiInputPorts(): any;
@@ -58,7 +75,10 @@ export abstract class MidiService {
     sampleNumber: number,
     template: string,
   ): boolean;
-  abstract samplerDeleteSample(sampleNumber: number): boolean;
+  abstract samplerDeleteSample(
+    sampleNumber: number,
+    mode?: SampleDeleteMode,
+  ): SampleDeleteResult;
   abstract samplerDeleteKeygroup(
     programNumber: number,
     keygroupNumber: number,
@@ -24,6 +24,8 @@ import {
   MidiService,
   ProgramDetails,
   S1000MiscellaneousDataType,
+  SampleDeleteMode,
+  SampleDeleteResult,
 } from './midi.service';
 
 // eslint-disable-next-line @typescript-eslint/no-var-requires
@@ -162,8 +164,11 @@ export class SamplerMidiService extends MidiService {
     );
   }
 
-  samplerDeleteSample(sampleNumber: number): boolean {
-    return midilib.sampler_delete_sample(sampleNumber);
+  samplerDeleteSample(
+    sampleNumber: number,
+    mode: SampleDeleteMode = 'refuse',
+  ): SampleDeleteResult {
+    return midilib.sampler_delete_sample(sampleNumber, mode);
   }
 
   samplerDeleteKeygroup(
@@ -19,6 +19,7 @@ import {
   MidiService,
   ProgramDetails,
   S1000MiscellaneousDataType,
+  SampleDeleteResult,
 } from './midi.service';
 import { SamplerInMemoryProgramMapper } from 'src/mappers/sampler-program-mapper';
 import { SamplerInMemoryKeyGroupMapper } from 'src/mappers/sampler-key-group-mapper';
@@ -446,14 +447,14 @@ export class SamplerSimulatorMidiService extends MidiService {
   ): boolean {
     throw new Error('Method not implemented.');
   }
-  samplerDeleteSample(sampleNumber: number): boolean {
+  samplerDeleteSample(sampleNumber: number): SampleDeleteResult {
     if (sampleNumber < this.memorySamples.length) {
       if (this.memorySamples.splice(sampleNumber, 1).length === 1) {
-        return true;
+        return { deleted: true, refused: false, references: [] };
       }
     }
 
-    return false;
+    return { deleted: false, refused: false, references: [] };
   }
   samplerDeleteKeygroup(
     programNumber: number,
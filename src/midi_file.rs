@@ -0,0 +1,228 @@
+// Minimal Standard MIDI File reading and writing - just the events a cue list can hold, timed in seconds.
+// Files are written as format 0 at 480 ticks per quarter note and 120 bpm. Any format, tempo map or
+// SMPTE division is read.
+
+use std::{fs, io};
+
+const TICKS_PER_QUARTER_NOTE: u16 = 480;
+const MICROSECONDS_PER_QUARTER_NOTE: u32 = 500_000; // 120 bpm
+
+const META_EVENT: u8 = 0xFF;
+const META_MARKER: u8 = 0x06;
+const META_TEMPO: u8 = 0x51;
+const META_END_OF_TRACK: u8 = 0x2F;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MidiMessage {
+    NoteOn { channel: u8, key: u8, velocity: u8 },
+    NoteOff { channel: u8, key: u8, velocity: u8 },
+    ProgramChange { channel: u8, program: u8 },
+    Marker(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimedMidiMessage {
+    pub seconds: f64,
+    pub message: MidiMessage,
+}
+
+pub fn read(path: &str) -> io::Result<Vec<TimedMidiMessage>> {
+    from_bytes(&fs::read(path)?)
+}
+
+pub fn write(path: &str, messages: &[TimedMidiMessage]) -> io::Result<()> {
+    fs::write(path, to_bytes(messages))
+}
+
+// messages from every track, in time order
+pub fn from_bytes(data: &[u8]) -> io::Result<Vec<TimedMidiMessage>> {
+    if data.len() < 14 || &data[0..4] != b"MThd" {
+        return Err(invalid_data("not a standard MIDI file"))
+    }
+    let header_size = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+    let division = u16::from_be_bytes([data[12], data[13]]);
+    if division & 0x8000 != 0 && smpte_ticks_per_second(division).is_none() {
+        return Err(invalid_data("the SMPTE division needs a frame rate of 24, 25, 29 or 30 and at least one tick per frame"))
+    }
+
+    let mut tempo_changes = vec![]; // (tick, microseconds per quarter note)
+    let mut messages = vec![]; // (tick, message)
+    let mut position = 8 + header_size;
+
+    while position + 8 <= data.len() {
+        let chunk_size = u32::from_be_bytes([data[position + 4], data[position + 5], data[position + 6], data[position + 7]]) as usize;
+        let chunk_start = position + 8;
+        let chunk_end = (chunk_start + chunk_size).min(data.len());
+
+        if &data[position..(position + 4)] == b"MTrk" {
+            read_track(&data[chunk_start..chunk_end], &mut tempo_changes, &mut messages)?;
+        }
+
+        position = chunk_start + chunk_size;
+    }
+
+    tempo_changes.sort_by_key(|(tick, _)| *tick);
+    let mut timed: Vec<TimedMidiMessage> = messages.into_iter().map(|(tick, message)| TimedMidiMessage { seconds: ticks_to_seconds(tick, division, &tempo_changes), message }).collect();
+    timed.sort_by(|a, b| a.seconds.partial_cmp(&b.seconds).unwrap_or(std::cmp::Ordering::Equal));
+
+    Ok(timed)
+}
+
+fn read_track(track: &[u8], tempo_changes: &mut Vec<(u64, u32)>, messages: &mut Vec<(u64, MidiMessage)>) -> io::Result<()> {
+    let mut position = 0;
+    let mut tick: u64 = 0;
+    let mut running_status: Option<u8> = None;
+
+    while position < track.len() {
+        tick += read_variable_length(track, &mut position)? as u64;
+        let mut status = *track.get(position).ok_or_else(|| invalid_data("track ends mid event"))?;
+        if status < 0x80 {
+            // running status - the byte is the first data byte
+            status = running_status.ok_or_else(|| invalid_data("running status with no previous status"))?;
+        }
+        else {
+            position += 1;
+        }
+
+        match status {
+            META_EVENT => {
+                let meta_type = *track.get(position).ok_or_else(|| invalid_data("track ends mid meta event"))?;
+                position += 1;
+                let length = read_variable_length(track, &mut position)? as usize;
+                let meta_data = track.get(position..(position + length)).ok_or_else(|| invalid_data("track ends mid meta event"))?;
+                position += length;
+
+                match meta_type {
+                    META_TEMPO if length == 3 => tempo_changes.push((tick, u32::from_be_bytes([0, meta_data[0], meta_data[1], meta_data[2]]))),
+                    META_MARKER => messages.push((tick, MidiMessage::Marker(String::from_utf8_lossy(meta_data).to_string()))),
+                    META_END_OF_TRACK => break,
+                    _ => (),
+                }
+            }
+            0xF0 | 0xF7 => {
+                let length = read_variable_length(track, &mut position)? as usize;
+                position += length;
+            }
+            _ => {
+                running_status = Some(status);
+                let channel = status & 0x0F;
+                let number_of_data_bytes = if status & 0xF0 == 0xC0 || status & 0xF0 == 0xD0 { 1 } else { 2 };
+                let data = track.get(position..(position + number_of_data_bytes)).ok_or_else(|| invalid_data("track ends mid event"))?;
+                position += number_of_data_bytes;
+
+                match status & 0xF0 {
+                    0x90 if data[1] > 0 => messages.push((tick, MidiMessage::NoteOn { channel, key: data[0], velocity: data[1] })),
+                    0x90 | 0x80 => messages.push((tick, MidiMessage::NoteOff { channel, key: data[0], velocity: data[1] })),
+                    0xC0 => messages.push((tick, MidiMessage::ProgramChange { channel, program: data[0] })),
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// SMPTE - frames per second as a negative number then ticks per frame. None for anything else.
+fn smpte_ticks_per_second(division: u16) -> Option<f64> {
+    let frames_per_second = match (division >> 8) as u8 as i8 {
+        -24 => 24.0,
+        -25 => 25.0,
+        -29 => 29.97,
+        -30 => 30.0,
+        _ => return None,
+    };
+
+    match division & 0xFF {
+        0 => None,
+        ticks_per_frame => Some(frames_per_second * ticks_per_frame as f64),
+    }
+}
+
+fn ticks_to_seconds(tick: u64, division: u16, tempo_changes: &[(u64, u32)]) -> f64 {
+    if let Some(ticks_per_second) = smpte_ticks_per_second(division) {
+        return tick as f64 / ticks_per_second
+    }
+
+    let ticks_per_quarter_note = division.max(1) as f64;
+    let mut seconds = 0.0;
+    let mut last_tick = 0;
+    let mut tempo = MICROSECONDS_PER_QUARTER_NOTE;
+    for (change_tick, change_tempo) in tempo_changes.iter().take_while(|(change_tick, _)| *change_tick < tick) {
+        seconds += (change_tick - last_tick) as f64 * tempo as f64 / 1_000_000.0 / ticks_per_quarter_note;
+        last_tick = *change_tick;
+        tempo = *change_tempo;
+    }
+
+    seconds + (tick - last_tick) as f64 * tempo as f64 / 1_000_000.0 / ticks_per_quarter_note
+}
+
+pub fn to_bytes(messages: &[TimedMidiMessage]) -> Vec<u8> {
+    let ticks_per_second = TICKS_PER_QUARTER_NOTE as f64 * 1_000_000.0 / MICROSECONDS_PER_QUARTER_NOTE as f64;
+    let mut sorted: Vec<&TimedMidiMessage> = messages.iter().collect();
+    sorted.sort_by(|a, b| a.seconds.partial_cmp(&b.seconds).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut track = vec![0, META_EVENT, META_TEMPO, 3];
+    track.extend_from_slice(&MICROSECONDS_PER_QUARTER_NOTE.to_be_bytes()[1..]);
+
+    let mut last_tick: u64 = 0;
+    for timed in sorted {
+        let tick = (timed.seconds.max(0.0) * ticks_per_second).round() as u64;
+        write_variable_length(&mut track, (tick - last_tick.min(tick)) as u32);
+        last_tick = tick.max(last_tick);
+
+        match &timed.message {
+            MidiMessage::NoteOn { channel, key, velocity } => track.extend_from_slice(&[0x90 | (channel & 0x0F), key & 0x7F, velocity & 0x7F]),
+            MidiMessage::NoteOff { channel, key, velocity } => track.extend_from_slice(&[0x80 | (channel & 0x0F), key & 0x7F, velocity & 0x7F]),
+            MidiMessage::ProgramChange { channel, program } => track.extend_from_slice(&[0xC0 | (channel & 0x0F), program & 0x7F]),
+            MidiMessage::Marker(text) => {
+                track.extend_from_slice(&[META_EVENT, META_MARKER]);
+                write_variable_length(&mut track, text.len() as u32);
+                track.extend_from_slice(text.as_bytes());
+            }
+        }
+    }
+    track.extend_from_slice(&[0, META_EVENT, META_END_OF_TRACK, 0]);
+
+    let mut data = Vec::with_capacity(22 + track.len());
+    data.extend_from_slice(b"MThd");
+    data.extend_from_slice(&6_u32.to_be_bytes());
+    data.extend_from_slice(&0_u16.to_be_bytes()); // format 0
+    data.extend_from_slice(&1_u16.to_be_bytes()); // one track
+    data.extend_from_slice(&TICKS_PER_QUARTER_NOTE.to_be_bytes());
+    data.extend_from_slice(b"MTrk");
+    data.extend_from_slice(&(track.len() as u32).to_be_bytes());
+    data.extend_from_slice(&track);
+
+    data
+}
+
+fn read_variable_length(data: &[u8], position: &mut usize) -> io::Result<u32> {
+    let mut value: u32 = 0;
+
+    for _ in 0..4 {
+        let byte = *data.get(*position).ok_or_else(|| invalid_data("track ends mid variable length number"))?;
+        *position += 1;
+        value = (value << 7) | (byte & 0x7F) as u32;
+        if byte & 0x80 == 0 {
+            return Ok(value)
+        }
+    }
+
+    Err(invalid_data("variable length number is longer than 4 bytes"))
+}
+
+fn write_variable_length(data: &mut Vec<u8>, value: u32) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut value = value >> 7;
+    while value > 0 {
+        bytes.push(((value & 0x7F) as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.reverse();
+    data.extend_from_slice(&bytes);
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,306 @@
+// The named entries of the S3000's miscellaneous data. Each one lives at an index in a data bank, and
+// the bank sets how many bytes its value takes (MISCELLANEOUS_BYTES_SIZES). Bank 6 holds names, bank 7
+// holds 16 raw bytes and every other bank holds a number sent least significant byte first.
+
+use std::fmt;
+
+use crate::sampler_name::{SamplerName, SAMPLER_NAME_LENGTH};
+use crate::MISCELLANEOUS_BYTES_SIZES;
+
+pub const NAME_BANK: u8 = 6;
+pub const BYTES_BANK: u8 = 7;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MiscValue {
+    Number(u64),
+    Name(String),
+    Bytes(Vec<u8>),
+}
+
+impl fmt::Display for MiscValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MiscValue::Number(number) => write!(f, "{}", number),
+            MiscValue::Name(name) => write!(f, "{}", name),
+            MiscValue::Bytes(bytes) => write!(f, "{:?}", bytes),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MiscDataAccess {
+    ReadOnly,
+    WriteOnly, // commands - writing the value makes the sampler do something
+    ReadWrite,
+}
+
+impl MiscDataAccess {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MiscDataAccess::ReadOnly => "read",
+            MiscDataAccess::WriteOnly => "write",
+            MiscDataAccess::ReadWrite => "read_write",
+        }
+    }
+
+    pub fn is_readable(&self) -> bool {
+        *self != MiscDataAccess::WriteOnly
+    }
+
+    pub fn is_writable(&self) -> bool {
+        *self != MiscDataAccess::ReadOnly
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MiscDataEntry {
+    pub name: &'static str,
+    pub bank: u8,
+    pub index: u16,
+    pub minimum: u64,
+    pub maximum: u64,
+    pub access: MiscDataAccess,
+    pub description: &'static str,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MiscDataError {
+    UnknownName(String),
+    NotReadable(&'static str),
+    NotWritable(&'static str),
+    OutOfRange(&'static str, u64, u64, u64), // name, value, minimum, maximum
+    NotAName(&'static str),
+    NotANumber(&'static str),
+    UnknownBank(u8),
+    WrongKindOfValue(u8), // bank
+    DoesNotFit(u8, usize), // bank, number of bytes given
+    InvalidName(String),
+}
+
+impl fmt::Display for MiscDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MiscDataError::UnknownName(name) => write!(f, "there is no miscellaneous data called {}", name),
+            MiscDataError::NotReadable(name) => write!(f, "{} can only be written", name),
+            MiscDataError::NotWritable(name) => write!(f, "{} can only be read", name),
+            MiscDataError::OutOfRange(name, value, minimum, maximum) => write!(f, "{} must be from {} to {} but was {}", name, minimum, maximum, value),
+            MiscDataError::NotAName(name) => write!(f, "{} holds a number not a name", name),
+            MiscDataError::NotANumber(name) => write!(f, "{} holds a name not a number", name),
+            MiscDataError::UnknownBank(bank) => write!(f, "there is no miscellaneous data bank {}", bank),
+            MiscDataError::WrongKindOfValue(bank) => write!(f, "bank {} does not hold that kind of value", bank),
+            MiscDataError::DoesNotFit(bank, length) => write!(f, "{} bytes do not fit in bank {}", length, bank),
+            MiscDataError::InvalidName(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl MiscDataEntry {
+    // number of bytes in the value - each one is sent as two nibbles
+    pub fn width(&self) -> u16 {
+        MISCELLANEOUS_BYTES_SIZES[self.bank as usize - 1]
+    }
+
+    pub fn is_name(&self) -> bool {
+        self.bank == NAME_BANK
+    }
+
+    pub fn check_readable(&self) -> Result<(), MiscDataError> {
+        if self.access.is_readable() { Ok(()) } else { Err(MiscDataError::NotReadable(self.name)) }
+    }
+
+    pub fn check_value(&self, value: &MiscValue) -> Result<(), MiscDataError> {
+        if !self.access.is_writable() {
+            return Err(MiscDataError::NotWritable(self.name))
+        }
+
+        match value {
+            MiscValue::Number(_) if self.is_name() => return Err(MiscDataError::NotANumber(self.name)),
+            MiscValue::Number(number) if *number < self.minimum || *number > self.maximum => {
+                return Err(MiscDataError::OutOfRange(self.name, *number, self.minimum, self.maximum))
+            }
+            MiscValue::Name(_) if !self.is_name() => return Err(MiscDataError::NotAName(self.name)),
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
+pub fn bank_width(bank: u8) -> Option<usize> {
+    if bank == 0 {
+        return None
+    }
+
+    MISCELLANEOUS_BYTES_SIZES.get(bank as usize - 1).map(|width| *width as usize)
+}
+
+// the value in the unnibbled bytes of a reply
+pub fn decode(bank: u8, data: &[u8]) -> Result<MiscValue, MiscDataError> {
+    let width = bank_width(bank).ok_or(MiscDataError::UnknownBank(bank))?;
+    if data.len() < width {
+        return Err(MiscDataError::DoesNotFit(bank, data.len()))
+    }
+    let data = &data[..width];
+
+    Ok(match bank {
+        NAME_BANK => MiscValue::Name(crate::convert_sampler_sysex_name_to_name(&data.to_vec()).trim_end().to_string()),
+        BYTES_BANK => MiscValue::Bytes(data.to_vec()),
+        _ => MiscValue::Number(data.iter().rev().fold(0u64, |number, byte| (number << 8) | *byte as u64)),
+    })
+}
+
+// the bytes to send for a value - always the width of the bank, before being split into nibbles
+pub fn encode(bank: u8, value: &MiscValue) -> Result<Vec<u8>, MiscDataError> {
+    let width = bank_width(bank).ok_or(MiscDataError::UnknownBank(bank))?;
+
+    match (bank, value) {
+        (NAME_BANK, MiscValue::Name(name)) => {
+            let name = SamplerName::new(name).map_err(|error| MiscDataError::InvalidName(error.to_string()))?;
+            let mut data = name.to_sysex();
+            data.truncate(SAMPLER_NAME_LENGTH.min(width));
+
+            Ok(data)
+        }
+        (NAME_BANK, _) | (_, MiscValue::Name(_)) => Err(MiscDataError::WrongKindOfValue(bank)),
+        (_, MiscValue::Bytes(bytes)) => {
+            if bytes.len() > width {
+                return Err(MiscDataError::DoesNotFit(bank, bytes.len()))
+            }
+            let mut data = bytes.clone();
+            data.resize(width, 0);
+
+            Ok(data)
+        }
+        (_, MiscValue::Number(number)) => {
+            if width < 8 && *number >> (width * 8) != 0 {
+                return Err(MiscDataError::DoesNotFit(bank, (64 - number.leading_zeros() as usize).div_ceil(8)))
+            }
+
+            Ok((0..width).map(|byte_index| (number.checked_shr(byte_index as u32 * 8).unwrap_or(0) & 255) as u8).collect())
+        }
+    }
+}
+
+// each byte goes as two nibbles, low nibble first
+pub fn to_nibbles(data: &[u8]) -> Vec<u8> {
+    data.iter().flat_map(|byte| vec![byte & 15, byte >> 4]).collect()
+}
+
+pub fn from_nibbles(nibbles: &[u8]) -> Vec<u8> {
+    nibbles.chunks_exact(2).map(|pair| (pair[0] & 15) | ((pair[1] & 15) << 4)).collect()
+}
+
+const fn entry(name: &'static str, bank: u8, index: u16, minimum: u64, maximum: u64, access: MiscDataAccess, description: &'static str) -> MiscDataEntry {
+    MiscDataEntry { name, bank, index, minimum, maximum, access, description }
+}
+
+pub static MISC_DATA: [MiscDataEntry; 18] = [
+    entry("SELDRV", 1, 0, 0, 1, MiscDataAccess::WriteOnly, "selected drive - 0 floppy, 1 hard disk"),
+    entry("PONDSK", 1, 1, 0, 255, MiscDataAccess::ReadOnly, "number of partitions on the selected hard disk"),
+    entry("SELPAR", 1, 2, 0, 255, MiscDataAccess::ReadWrite, "selected partition"),
+    entry("VONDSK", 1, 3, 0, 255, MiscDataAccess::ReadOnly, "number of volumes in the selected partition"),
+    entry("SELVOL", 1, 4, 0, 255, MiscDataAccess::ReadWrite, "selected volume"),
+    entry("LODVOL", 1, 6, 0, 255, MiscDataAccess::WriteOnly, "load the selected volume into memory - the value is the load type"),
+    entry("CLRLOD", 1, 7, 0, 255, MiscDataAccess::WriteOnly, "clear memory then load the selected volume - the value is the load type"),
+    entry("SAVVOL", 1, 8, 0, 255, MiscDataAccess::WriteOnly, "save memory to the selected volume - the value is the save type"),
+    entry("CLRSAV", 1, 9, 0, 255, MiscDataAccess::WriteOnly, "clear the selected volume then save memory to it - the value is the save type"),
+    entry("PINVOL", 2, 0, 0, 65535, MiscDataAccess::ReadOnly, "number of programs in the selected volume"),
+    entry("SINVOL", 2, 1, 0, 65535, MiscDataAccess::ReadOnly, "number of samples in the selected volume"),
+    entry("QINVOL", 2, 2, 0, 65535, MiscDataAccess::ReadOnly, "number of cue lists in the selected volume"),
+    entry("TINVOL", 2, 3, 0, 65535, MiscDataAccess::ReadOnly, "number of take lists in the selected volume"),
+    entry("XINVOL", 2, 4, 0, 65535, MiscDataAccess::ReadOnly, "number of effects files in the selected volume"),
+    entry("DINVOL", 2, 5, 0, 65535, MiscDataAccess::ReadOnly, "number of drum input files in the selected volume"),
+    entry("FINVOL", 2, 6, 0, 65535, MiscDataAccess::ReadOnly, "number of files in the selected volume"),
+    entry("FINMEM", 2, 10, 0, 65535, MiscDataAccess::ReadOnly, "number of files in memory"),
+    entry("DINAME", 6, 1, 0, 0, MiscDataAccess::ReadWrite, "name of the drum inputs file in memory"),
+];
+
+// names are matched without regard to case
+pub fn find(name: &str) -> Option<&'static MiscDataEntry> {
+    MISC_DATA.iter().find(|entry| entry.name.eq_ignore_ascii_case(name.trim()))
+}
+
+pub fn lookup(name: &str) -> Result<&'static MiscDataEntry, MiscDataError> {
+    find(name).ok_or_else(|| MiscDataError::UnknownName(name.to_string()))
+}
+
+// the entry written by a raw bank and index, if it is a named one
+pub fn find_at(bank: u8, index: u16) -> Option<&'static MiscDataEntry> {
+    MISC_DATA.iter().find(|entry| entry.bank == bank && entry.index == index)
+}
+
+// writing one of these makes the sampler do something, so it is never written again by undo or redo
+pub fn command_at(bank: u8, index: u16) -> Option<&'static MiscDataEntry> {
+    find_at(bank, index).filter(|entry| entry.access == MiscDataAccess::WriteOnly)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_round_trip_at_the_width_of_their_bank() {
+        let values: [(u8, u64, Vec<u8>); 6] = [
+            (1, 0xc8, vec![0xc8]),
+            (2, 0x1234, vec![0x34, 0x12]),
+            (3, 0x1234_5678, vec![0x78, 0x56, 0x34, 0x12]),
+            (4, 0x01_0203_0405, vec![0x05, 0x04, 0x03, 0x02, 0x01]),
+            (5, 0x0102_0304_0506, vec![0x06, 0x05, 0x04, 0x03, 0x02, 0x01]),
+            (8, 0x0102_0304_0506_0708, vec![0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01]),
+        ];
+
+        for (bank, number, data) in values.iter() {
+            assert_eq!(data.len(), MISCELLANEOUS_BYTES_SIZES[*bank as usize - 1] as usize);
+            assert_eq!(encode(*bank, &MiscValue::Number(*number)), Ok(data.clone()));
+            assert_eq!(decode(*bank, data), Ok(MiscValue::Number(*number)));
+        }
+    }
+
+    #[test]
+    fn bank_2_is_recombined_from_nibbles_low_first() {
+        let nibbles = to_nibbles(&encode(2, &MiscValue::Number(0x1234)).unwrap());
+        assert_eq!(nibbles, vec![0x4, 0x3, 0x2, 0x1]);
+
+        // only the low four bits of each nibble count
+        assert_eq!(decode(2, &from_nibbles(&[0x14, 0x23, 0x72, 0x61])), Ok(MiscValue::Number(0x1234)));
+    }
+
+    #[test]
+    fn bank_6_holds_a_name_padded_with_spaces() {
+        let data = vec![14, 28, 31, 23, 29, 10, 10, 10, 10, 10, 10, 10]; // DRUMS
+        assert_eq!(encode(NAME_BANK, &MiscValue::Name("DRUMS".to_string())), Ok(data.clone()));
+        assert_eq!(decode(NAME_BANK, &data), Ok(MiscValue::Name("DRUMS".to_string())));
+
+        assert!(matches!(encode(NAME_BANK, &MiscValue::Name("DRUMS!".to_string())), Err(MiscDataError::InvalidName(_))));
+        assert_eq!(encode(NAME_BANK, &MiscValue::Number(1)), Err(MiscDataError::WrongKindOfValue(NAME_BANK)));
+    }
+
+    #[test]
+    fn bank_7_holds_raw_bytes_padded_with_zeros() {
+        let data: Vec<u8> = (0..16).map(|byte| byte * 16 + 15).collect();
+        assert_eq!(encode(BYTES_BANK, &MiscValue::Bytes(data.clone())), Ok(data.clone()));
+        assert_eq!(decode(BYTES_BANK, &data), Ok(MiscValue::Bytes(data)));
+
+        let mut padded = vec![1, 2, 3];
+        padded.resize(16, 0);
+        assert_eq!(encode(BYTES_BANK, &MiscValue::Bytes(vec![1, 2, 3])), Ok(padded));
+    }
+
+    #[test]
+    fn values_wider_than_their_bank_do_not_fit() {
+        assert_eq!(encode(1, &MiscValue::Number(0x100)), Err(MiscDataError::DoesNotFit(1, 2)));
+        assert_eq!(encode(2, &MiscValue::Number(0x1_0000)), Err(MiscDataError::DoesNotFit(2, 3)));
+        assert_eq!(encode(4, &MiscValue::Number(0x100_0000_0000)), Err(MiscDataError::DoesNotFit(4, 6)));
+        assert_eq!(encode(BYTES_BANK, &MiscValue::Bytes(vec![0; 17])), Err(MiscDataError::DoesNotFit(BYTES_BANK, 17)));
+        assert_eq!(decode(3, &[1, 2, 3]), Err(MiscDataError::DoesNotFit(3, 3)));
+    }
+
+    #[test]
+    fn banks_0_and_9_do_not_exist() {
+        for bank in [0, 9].iter() {
+            assert_eq!(bank_width(*bank), None);
+            assert_eq!(encode(*bank, &MiscValue::Number(0)), Err(MiscDataError::UnknownBank(*bank)));
+            assert_eq!(decode(*bank, &[0; 16]), Err(MiscDataError::UnknownBank(*bank)));
+        }
+    }
+}
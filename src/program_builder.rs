@@ -0,0 +1,196 @@
+// Turns a list of zones into the program header and keygroup headers of a playable multisample.
+// Zones that share a key range are velocity layers in the same keygroup, and any zone without
+// a key range gets the keys between its root note and its neighbours' root notes.
+
+use std::collections::HashMap;
+
+use crate::keygroup::{self, NUMBER_OF_ZONES, PAN_LEFT, PAN_RIGHT, ZONE_PAN_OFFSETS, ZONE_SAMPLE_NAME_OFFSETS, ZONE_SEMITONE_TUNE_OFFSETS};
+use crate::sampler_name::SamplerName;
+use crate::stereo;
+
+pub const PROGRAM_HEADER_SIZE: usize = 192;
+pub const KEYGROUP_HEADER_SIZE: usize = 192;
+pub const NUMBER_OF_KEYGROUPS_OFFSET: usize = 42;
+
+pub const LOWEST_NOTE: u8 = 21;
+pub const HIGHEST_NOTE: u8 = 127;
+
+const PROGRAM_IDENTIFIER: u8 = 1;
+const KEYGROUP_IDENTIFIER: u8 = 2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZoneSpec {
+    pub sample_name: String,
+    pub root_note: u8,
+    pub key_range: Option<(u8, u8)>,
+    pub velocity_range: (u8, u8),
+    pub pan: i8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeygroupSpec {
+    pub low_note: u8,
+    pub high_note: u8,
+    pub zones: Vec<ZoneSpec>,
+}
+
+pub fn plan_keygroups(zones: &[ZoneSpec]) -> Result<Vec<KeygroupSpec>, String> {
+    if zones.is_empty() {
+        return Err("no zones".to_string())
+    }
+
+    for zone in zones.iter() {
+        if let Some((low_note, high_note)) = zone.key_range {
+            if low_note > high_note {
+                return Err(format!("{} has a key range of {} to {}", zone.sample_name, low_note, high_note))
+            }
+        }
+        if zone.velocity_range.0 > zone.velocity_range.1 {
+            return Err(format!("{} has a velocity range of {} to {}", zone.sample_name, zone.velocity_range.0, zone.velocity_range.1))
+        }
+    }
+
+    // the root notes of zones without a key range split the keyboard between them
+    let mut roots: Vec<u8> = zones.iter().filter(|zone| zone.key_range.is_none()).map(|zone| zone.root_note).collect();
+    roots.sort_unstable();
+    roots.dedup();
+
+    let split_range = |root_note: u8| -> (u8, u8) {
+        let position = roots.iter().position(|root| *root == root_note).unwrap_or(0);
+        let low_note = if position == 0 { LOWEST_NOTE } else { split_point(roots[position - 1], root_note) + 1 };
+        let high_note = if position + 1 == roots.len() { HIGHEST_NOTE } else { split_point(root_note, roots[position + 1]) };
+
+        (low_note.min(root_note), high_note.max(root_note))
+    };
+
+    let mut keygroups: Vec<KeygroupSpec> = vec![];
+    for zone in zones.iter() {
+        let (low_note, high_note) = zone.key_range.unwrap_or_else(|| split_range(zone.root_note));
+
+        match keygroups.iter_mut().find(|keygroup| keygroup.low_note == low_note && keygroup.high_note == high_note) {
+            Some(keygroup) => keygroup.zones.push(zone.clone()),
+            None => keygroups.push(KeygroupSpec { low_note, high_note, zones: vec![zone.clone()] }),
+        }
+    }
+
+    for keygroup in keygroups.iter() {
+        if keygroup.zones.len() > NUMBER_OF_ZONES {
+            return Err(format!("keys {} to {} have {} zones but a keygroup only holds {}", keygroup.low_note, keygroup.high_note, keygroup.zones.len(), NUMBER_OF_ZONES))
+        }
+    }
+
+    keygroups.sort_by_key(|keygroup| (keygroup.low_note, keygroup.high_note));
+
+    Ok(keygroups)
+}
+
+// the last key that belongs to the lower of two adjacent roots
+fn split_point(lower_root: u8, upper_root: u8) -> u8 {
+    lower_root + (upper_root - lower_root) / 2
+}
+
+// factory defaults for everything the builder does not set
+pub fn program_header(name: &SamplerName, number_of_keygroups: u8) -> Vec<u8> {
+    let mut data = vec![0; PROGRAM_HEADER_SIZE];
+
+    data[0] = PROGRAM_IDENTIFIER;
+    data[3..15].copy_from_slice(&name.to_sysex());
+    data[17] = 31; // polyphony - 32 voices
+    data[18] = 1; // priority - normal
+    data[19] = LOWEST_NOTE; // play range low
+    data[20] = HIGHEST_NOTE; // play range high
+    data[22] = 255; // individual output - off
+    data[23] = 99; // stereo level
+    data[25] = 80; // loudness
+    data[26] = 20; // velocity > loudness
+    data[29] = 50; // lfo2 speed
+    data[33] = 50; // lfo1 speed
+    data[39] = 2; // bend wheel up
+    data[NUMBER_OF_KEYGROUPS_OFFSET] = number_of_keygroups;
+
+    data
+}
+
+// The semitones a zone is tuned by so its sample plays at its recorded pitch on the root note. The
+// tuning goes in the keygroup rather than the sample's original pitch, which other programs rely on.
+pub fn zone_semitone_tune(root_note: u8, original_pitch: u8) -> Result<i8, String> {
+    let tune = original_pitch as i16 - root_note as i16;
+    if !(-50..=50).contains(&tune) {
+        return Err(format!("a root note of {} is more than 50 semitones from the original pitch of {}", root_note, original_pitch))
+    }
+
+    Ok(tune as i8)
+}
+
+// original_pitches are keyed by trimmed sample name
+pub fn keygroup_header(keygroup_spec: &KeygroupSpec, original_pitches: &HashMap<String, u8>) -> Result<Vec<u8>, String> {
+    let mut data = vec![0; KEYGROUP_HEADER_SIZE];
+
+    data[0] = KEYGROUP_IDENTIFIER;
+    data[3] = keygroup_spec.low_note;
+    data[4] = keygroup_spec.high_note;
+    data[7] = 99; // filter frequency - open
+    data[8] = 12; // filter key follow
+    data[13] = 50; // envelope 1 decay
+    data[14] = 99; // envelope 1 sustain
+    data[15] = 45; // envelope 1 release
+    data[21] = 50; // envelope 2 rate 3
+    data[22] = 99; // envelope 2 level 3
+    data[23] = 45; // envelope 2 rate 4
+
+    let blank_name = crate::convert_name_to_sampler_sysex_name(String::new());
+    for zone in 0..NUMBER_OF_ZONES {
+        let name_offset = ZONE_SAMPLE_NAME_OFFSETS[zone];
+        let name = match keygroup_spec.zones.get(zone) {
+            Some(zone_spec) => crate::convert_name_to_sampler_sysex_name(zone_spec.sample_name.clone()),
+            None => blank_name.clone(),
+        };
+        let (velocity_low, velocity_high) = keygroup_spec.zones.get(zone).map(|zone_spec| zone_spec.velocity_range).unwrap_or((0, 127));
+
+        data[name_offset..(name_offset + 12)].copy_from_slice(&name[..12]);
+        data[name_offset + 12] = velocity_low;
+        data[name_offset + 13] = velocity_high;
+        data[ZONE_PAN_OFFSETS[zone]] = keygroup::encode_plus_or_minus_fifty(keygroup_spec.zones.get(zone).map(|zone_spec| zone_spec.pan).unwrap_or(0));
+
+        if let Some(zone_spec) = keygroup_spec.zones.get(zone) {
+            let original_pitch = original_pitches.get(zone_spec.sample_name.trim_end()).ok_or_else(|| format!("the original pitch of {} is not known", zone_spec.sample_name))?;
+            data[ZONE_SEMITONE_TUNE_OFFSETS[zone]] = keygroup::encode_plus_or_minus_fifty(zone_semitone_tune(zone_spec.root_note, *original_pitch)?);
+        }
+    }
+
+    Ok(data)
+}
+
+// A zone naming one half of a resident stereo pair, or the base name of one, plays both halves
+// panned hard left and right. Halves that are already listed are left as they are.
+pub fn expand_stereo_zones(resident_names: &[String], zones: Vec<ZoneSpec>) -> Vec<ZoneSpec> {
+    let is_listed = |name: &str| zones.iter().any(|zone| zone.sample_name.trim_end() == name);
+    let mut expanded = vec![];
+
+    for zone in zones.iter() {
+        let name = zone.sample_name.trim_end();
+        let is_resident = resident_names.iter().any(|resident_name| resident_name.trim_end() == name);
+        let base = match stereo::split_name(name) {
+            Some((base, _)) => base.to_string(),
+            None if !is_resident => name.to_string(),
+            None => {
+                expanded.push(zone.clone());
+                continue
+            }
+        };
+        let (left_name, right_name) = stereo::pair_names(base.as_str());
+
+        if stereo::find_pair(resident_names, left_name.as_str()).is_none() {
+            expanded.push(zone.clone());
+            continue
+        }
+
+        for (side_name, pan) in [(left_name, PAN_LEFT), (right_name, PAN_RIGHT)].iter() {
+            if side_name.as_str() == name || !is_listed(side_name.as_str()) {
+                expanded.push(ZoneSpec { sample_name: side_name.clone(), pan: *pan, ..zone.clone() });
+            }
+        }
+    }
+
+    expanded
+}
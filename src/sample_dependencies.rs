@@ -0,0 +1,63 @@
+// Which keygroup zones in which programs play each sample, so a sample is not deleted out from under
+// the keygroups that use it. Zones refer to samples by name, so that is what the map is keyed by.
+
+use std::collections::BTreeMap;
+
+use crate::keygroup::ZONE_SAMPLE_NAME_OFFSETS;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZoneReference {
+    pub program: u16,
+    pub program_name: String,
+    pub keygroup: u8,
+    pub zone: usize, // 0 to keygroup::NUMBER_OF_ZONES - 1
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeleteMode {
+    Refuse, // nothing is deleted while any zone uses the sample
+    Warn, // deleted anyway, the zones are reported
+    Cascade, // the zones are cleared first
+}
+
+impl DeleteMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "refuse" => Some(DeleteMode::Refuse),
+            "warn" => Some(DeleteMode::Warn),
+            "cascade" => Some(DeleteMode::Cascade),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SampleDependencies {
+    references: BTreeMap<String, Vec<ZoneReference>>, // trimmed sample name -> zones
+}
+
+impl SampleDependencies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // zones with no sample have a blank name and are left out
+    pub fn add_keygroup(&mut self, program: u16, program_name: &str, keygroup: u8, keygroup_header: &[u8]) {
+        for (zone, offset) in ZONE_SAMPLE_NAME_OFFSETS.iter().enumerate() {
+            if let Some(name) = keygroup_header.get(*offset..(*offset + 12)) {
+                let sample_name = crate::convert_sampler_sysex_name_to_name(&name.to_vec()).trim_end().to_string();
+                if !sample_name.is_empty() {
+                    self.references.entry(sample_name).or_default().push(ZoneReference { program, program_name: program_name.trim_end().to_string(), keygroup, zone });
+                }
+            }
+        }
+    }
+
+    pub fn references(&self, sample_name: &str) -> &[ZoneReference] {
+        self.references.get(sample_name.trim_end()).map(|references| references.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Vec<ZoneReference>)> {
+        self.references.iter()
+    }
+}
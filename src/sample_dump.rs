@@ -0,0 +1,49 @@
+// Builds MIDI sample dump standard data packets for sending sample words to the sampler.
+
+use crate::{EOX, SAMPLE_DUMP_STANDARD_DATA_PACKET, START_OF_SYSTEM_EXCLUSIVE, SYSEX_NON_REAL_TIME_CATEGORY};
+
+pub const SAMPLE_DUMP_PACKET_DATA_SIZE: usize = 120;
+
+// the same layout the sample data handler reads back - 3 x 7 bits, least significant first
+pub fn sample_words_to_bytes(samples: &[i16]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(samples.len() * 3);
+
+    for sample in samples.iter() {
+        let sample = *sample as u16;
+        data.push((sample & 127) as u8);
+        data.push(((sample >> 7) & 127) as u8);
+        data.push(((sample >> 14) & 127) as u8);
+    }
+
+    data
+}
+
+pub fn number_of_packets(number_of_bytes: usize) -> usize {
+    number_of_bytes.div_ceil(SAMPLE_DUMP_PACKET_DATA_SIZE)
+}
+
+// splits already encoded sample bytes into packets - the last one is padded with 0s
+pub fn data_packets(data: &[u8]) -> Vec<Vec<u8>> {
+    let mut packets = vec![];
+
+    for (packet_number, chunk) in data.chunks(SAMPLE_DUMP_PACKET_DATA_SIZE).enumerate() {
+        let mut packet = Vec::with_capacity(SAMPLE_DUMP_PACKET_DATA_SIZE + 7);
+        let packet_number = (packet_number & 127) as u8;
+
+        packet.push(START_OF_SYSTEM_EXCLUSIVE);
+        packet.push(SYSEX_NON_REAL_TIME_CATEGORY);
+        packet.push(0x00);
+        packet.push(SAMPLE_DUMP_STANDARD_DATA_PACKET);
+        packet.push(packet_number);
+        packet.extend_from_slice(chunk);
+        packet.resize(5 + SAMPLE_DUMP_PACKET_DATA_SIZE, 0);
+
+        let checksum = packet[1..].iter().fold(0, |checksum, value| checksum ^ value) & 0x7F;
+        packet.push(checksum);
+        packet.push(EOX);
+
+        packets.push(packet);
+    }
+
+    packets
+}
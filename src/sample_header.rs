@@ -0,0 +1,166 @@
+// Typed view of the 192 byte (unnibbled) S1000/S3000 sample header.
+
+use crate::sampler_name::SamplerName;
+
+pub const SAMPLE_HEADER_SIZE: usize = 192;
+pub const NUMBER_OF_LOOPS: usize = 4;
+
+pub const BANDWIDTH_OFFSET: usize = 1;
+pub const ORIGINAL_PITCH_OFFSET: usize = 2;
+pub const NAME_OFFSET: usize = 3;
+pub const VALID_OFFSET: usize = 15;
+pub const NUMBER_OF_LOOPS_OFFSET: usize = 16;
+pub const PLAYBACK_TYPE_OFFSET: usize = 19;
+pub const SAMPLE_LENGTH_OFFSET: usize = 26;
+pub const START_OFFSET_OFFSET: usize = 30;
+pub const PLAY_LENGTH_OFFSET: usize = 34;
+pub const FIRST_LOOP_OFFSET: usize = 38;
+pub const LOOP_SIZE: usize = 12;
+pub const SAMPLE_RATE_OFFSET: usize = 138;
+
+pub const LOOP_DWELL_TIME_HOLD: u16 = 9999; // the loop repeats for as long as the key is held
+
+pub const BANDWIDTH_20_KHZ: u8 = 1;
+pub const VALID: u8 = 128;
+pub const PLAYBACK_TYPE_NO_LOOPING: u8 = 2;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SampleLoop {
+    pub at: u32, // the loop point - playback jumps back from here by the loop length
+    pub length: u32,
+    pub length_fraction: u16,
+    pub dwell_time: u16,
+}
+
+impl SampleLoop {
+    pub fn start(&self) -> u32 {
+        self.at.saturating_sub(self.length)
+    }
+
+    pub fn is_used(&self) -> bool {
+        self.length > 0
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![];
+
+        data.extend_from_slice(&self.at.to_le_bytes());
+        data.extend_from_slice(&self.length_fraction.to_le_bytes());
+        data.extend_from_slice(&self.length.to_le_bytes());
+        data.extend_from_slice(&self.dwell_time.to_le_bytes());
+
+        data
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SampleHeader {
+    pub name: String,
+    pub number_of_loops: u8,
+    pub sample_length: u32,
+    pub loops: Vec<SampleLoop>,
+    pub sample_rate: u16,
+    pub checksum: u32,
+}
+
+impl SampleHeader {
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() < SAMPLE_HEADER_SIZE {
+            return None
+        }
+
+        let loops = (0..NUMBER_OF_LOOPS).map(|index| {
+            let offset = loop_offset(index);
+
+            SampleLoop {
+                at: read_u32(data, offset),
+                length_fraction: read_u16(data, offset + 4),
+                length: read_u32(data, offset + 6),
+                dwell_time: read_u16(data, offset + 10),
+            }
+        }).collect();
+
+        Some(Self {
+            name: crate::convert_sampler_sysex_name_to_name(&data[NAME_OFFSET..(NAME_OFFSET + 12)].to_vec()),
+            number_of_loops: data[NUMBER_OF_LOOPS_OFFSET],
+            sample_length: read_u32(data, SAMPLE_LENGTH_OFFSET),
+            loops,
+            sample_rate: read_u16(data, SAMPLE_RATE_OFFSET),
+            checksum: checksum(&data[..SAMPLE_HEADER_SIZE]),
+        })
+    }
+
+    pub fn used_loops(&self) -> impl Iterator<Item = &SampleLoop> {
+        self.loops.iter().take(self.number_of_loops as usize).filter(|sample_loop| sample_loop.is_used())
+    }
+}
+
+// a header for audio that did not come from the sampler, e.g. an imported WAV file
+pub fn new_header(name: &SamplerName, sample_length: u32, sample_rate: u16, original_pitch: u8) -> Vec<u8> {
+    let mut data = vec![0; SAMPLE_HEADER_SIZE];
+
+    data[BANDWIDTH_OFFSET] = BANDWIDTH_20_KHZ;
+    data[VALID_OFFSET] = VALID;
+    data[PLAYBACK_TYPE_OFFSET] = PLAYBACK_TYPE_NO_LOOPING;
+    set_name(&mut data, name);
+    set_original_pitch(&mut data, original_pitch);
+    set_sample_length(&mut data, sample_length);
+    set_sample_rate(&mut data, sample_rate);
+
+    data
+}
+
+// the setters work on the raw header bytes so anything not modelled here is sent back untouched
+
+pub fn set_name(data: &mut [u8], name: &SamplerName) {
+    data[NAME_OFFSET..(NAME_OFFSET + 12)].copy_from_slice(&name.to_sysex());
+}
+
+pub fn set_original_pitch(data: &mut [u8], note: u8) {
+    data[ORIGINAL_PITCH_OFFSET] = note;
+}
+
+// playback covers the whole of the new audio
+pub fn set_sample_length(data: &mut [u8], sample_length: u32) {
+    write_u32(data, SAMPLE_LENGTH_OFFSET, sample_length);
+    write_u32(data, START_OFFSET_OFFSET, 0);
+    write_u32(data, PLAY_LENGTH_OFFSET, sample_length);
+}
+
+pub fn set_sample_rate(data: &mut [u8], sample_rate: u16) {
+    data[SAMPLE_RATE_OFFSET..(SAMPLE_RATE_OFFSET + 2)].copy_from_slice(&sample_rate.to_le_bytes());
+}
+
+// unused loops are cleared and the rest packed to the front
+pub fn set_loops(data: &mut [u8], loops: &[SampleLoop]) {
+    let used_loops: Vec<&SampleLoop> = loops.iter().filter(|sample_loop| sample_loop.is_used()).take(NUMBER_OF_LOOPS).collect();
+
+    for index in 0..NUMBER_OF_LOOPS {
+        let offset = loop_offset(index);
+        let bytes = used_loops.get(index).map(|sample_loop| sample_loop.to_bytes()).unwrap_or_else(|| SampleLoop::default().to_bytes());
+        data[offset..(offset + LOOP_SIZE)].copy_from_slice(&bytes);
+    }
+
+    data[NUMBER_OF_LOOPS_OFFSET] = used_loops.len() as u8;
+}
+
+pub fn loop_offset(loop_index: usize) -> usize {
+    FIRST_LOOP_OFFSET + loop_index * LOOP_SIZE
+}
+
+// FNV-1a - cheap and good enough to notice a header has been edited
+pub fn checksum(data: &[u8]) -> u32 {
+    data.iter().fold(0x811C_9DC5_u32, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x0100_0193))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    data[offset] as u16 | ((data[offset + 1] as u16) << 8)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    data[offset] as u32 | ((data[offset + 1] as u32) << 8) | ((data[offset + 2] as u32) << 16) | ((data[offset + 3] as u32) << 24)
+}
+
+fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..(offset + 4)].copy_from_slice(&value.to_le_bytes());
+}
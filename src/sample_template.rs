@@ -0,0 +1,346 @@
+// Renders the audio for samples created from a template instead of a recording.
+// Pitched waveforms are built from harmonics so they are band limited and repeat exactly every
+// `period` samples, which lets the loop sit on a cycle boundary without a click.
+
+use std::f64::consts::PI;
+
+use fundsp::hacker32::{brown, pink, white, An, AudioNode};
+
+use crate::dsp::to_sample_word;
+use crate::sample_header::{self, SampleLoop};
+
+pub const DEFAULT_FREQUENCY: f64 = 440.0;
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+pub const WAVETABLE_CYCLE_LENGTH: usize = 2048; // Serum and most other wavetable synths
+
+const FULL_SCALE: f64 = 32767.0;
+const NOISE_LENGTH: f64 = 1.0; // seconds of looped noise when there is no envelope
+const MAX_LENGTH: f64 = 60.0; // seconds - well past what the sampler's memory holds at 44.1kHz
+const MIN_FREQUENCY: f64 = 8.0; // just below MIDI note 0
+const MAX_VOICES: usize = 16;
+const MAX_HARMONICS: usize = 1024;
+const MAX_CYCLE_LENGTH: usize = 65536;
+const MAX_RENDER_TERMS: f64 = 2.0e8; // samples x harmonics x voices, a few seconds of rendering
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Waveform {
+    Sine,
+    Square,
+    Triangle,
+    Saw,
+    Pulse,
+    WhiteNoise,
+    PinkNoise,
+    BrownNoise,
+    Additive(Vec<f64>), // the amplitude of each harmonic starting with the fundamental
+    SingleCycle(Vec<f64>), // one cycle of any length - an AKWF file, a wavetable frame or raw values
+}
+
+impl Waveform {
+    // a single cycle starts empty until its values are read
+    pub fn new(template: &str, harmonics: Vec<f64>) -> Result<Self, String> {
+        match template {
+            "sine" => Ok(Waveform::Sine),
+            "square" => Ok(Waveform::Square),
+            "triangle" => Ok(Waveform::Triangle),
+            "saw" => Ok(Waveform::Saw),
+            "pulse" => Ok(Waveform::Pulse),
+            "white_noise" | "noise" => Ok(Waveform::WhiteNoise),
+            "pink_noise" => Ok(Waveform::PinkNoise),
+            "brown_noise" => Ok(Waveform::BrownNoise),
+            "additive" => Ok(Waveform::Additive(harmonics)),
+            "single_cycle" => Ok(Waveform::SingleCycle(vec![])),
+            _ => Err(format!("{} is not a waveform", template)),
+        }
+    }
+
+    pub fn is_noise(&self) -> bool {
+        matches!(self, Waveform::WhiteNoise | Waveform::PinkNoise | Waveform::BrownNoise)
+    }
+}
+
+// times in seconds, sustain as a level between 0 and 1
+#[derive(Clone, Debug, PartialEq)]
+pub struct Envelope {
+    pub attack: f64,
+    pub decay: f64,
+    pub sustain: f64,
+    pub release: f64,
+    pub duration: f64, // how long the note is held before the release starts
+}
+
+impl Envelope {
+    pub fn length(&self, sample_rate: u32) -> usize {
+        ((self.duration + self.release) * sample_rate as f64).round() as usize
+    }
+
+    pub fn level(&self, time: f64) -> f64 {
+        let held_level = |time: f64| {
+            if time < self.attack {
+                time / self.attack
+            }
+            else if time < self.attack + self.decay {
+                1.0 - (1.0 - self.sustain) * (time - self.attack) / self.decay
+            }
+            else {
+                self.sustain
+            }
+        };
+
+        if time < self.duration {
+            held_level(time)
+        }
+        else if time < self.duration + self.release {
+            held_level(self.duration) * (1.0 - (time - self.duration) / self.release)
+        }
+        else {
+            0.0
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SampleTemplate {
+    pub waveform: Waveform,
+    pub frequency: f64,
+    pub cycles: usize,
+    pub sample_rate: u32,
+    pub amplitude: f64,
+    pub pulse_width: f64,
+    pub voices: usize,
+    pub detune: f64, // cents between the outermost voices of a stack
+    pub envelope: Option<Envelope>, // a one shot instead of a loop
+}
+
+impl SampleTemplate {
+    pub fn new(waveform: Waveform) -> Self {
+        Self {
+            waveform,
+            frequency: DEFAULT_FREQUENCY,
+            cycles: 1,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            amplitude: 1.0,
+            pulse_width: 0.5,
+            voices: 1,
+            detune: 0.0,
+            envelope: None,
+        }
+    }
+
+    pub fn set_root_note(&mut self, note: u8) {
+        self.frequency = note_to_frequency(note as f64);
+    }
+
+    // anything that would give a zero length or take too long to render is refused before rendering
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.frequency.is_finite() || self.frequency < MIN_FREQUENCY {
+            return Err(format!("a frequency of {} is below {}Hz", self.frequency, MIN_FREQUENCY))
+        }
+        if self.cycles == 0 {
+            return Err("at least one cycle is needed".to_string())
+        }
+        if self.voices == 0 || self.voices > MAX_VOICES {
+            return Err(format!("{} voices is not between 1 and {}", self.voices, MAX_VOICES))
+        }
+        match &self.waveform {
+            Waveform::Additive(amplitudes) if amplitudes.len() > MAX_HARMONICS => {
+                return Err(format!("{} harmonics is more than {}", amplitudes.len(), MAX_HARMONICS))
+            }
+            Waveform::SingleCycle(cycle) if cycle.is_empty() => return Err("no cycle was given".to_string()),
+            Waveform::SingleCycle(cycle) if cycle.len() < 2 || cycle.len() > MAX_CYCLE_LENGTH => {
+                return Err(format!("a cycle of {} values is not between 2 and {}", cycle.len(), MAX_CYCLE_LENGTH))
+            }
+            _ => (),
+        }
+        if !crate::dsp::SUPPORTED_SAMPLE_RATES.contains(&self.sample_rate) {
+            return Err(format!("{}Hz is not a sample rate the sampler supports", self.sample_rate))
+        }
+        if let Some(envelope) = &self.envelope {
+            if !envelope.duration.is_finite() || envelope.duration <= 0.0 {
+                return Err(format!("a duration of {} is not a positive number", envelope.duration))
+            }
+            if [envelope.attack, envelope.decay, envelope.release].iter().any(|time| !time.is_finite() || *time < 0.0) {
+                return Err("attack, decay and release must not be negative".to_string())
+            }
+        }
+
+        let length = self.length();
+        if length as f64 > MAX_LENGTH * self.sample_rate as f64 {
+            return Err(format!("{} samples is longer than {} seconds", length, MAX_LENGTH))
+        }
+        if !self.waveform.is_noise() {
+            let terms = length as f64 * self.harmonics(self.frequency).len() as f64 * self.voices as f64;
+            if terms > MAX_RENDER_TERMS {
+                return Err("too long to render - use fewer voices, a shorter length or a higher frequency".to_string())
+            }
+
+            // a loop rounds every voice to a whole number of cycles, so too few cycles puts them all in tune
+            if !self.detune.is_finite() {
+                return Err(format!("a detune of {} is not a number", self.detune))
+            }
+            if self.envelope.is_none() && self.voices > 1 && self.detune != 0.0 {
+                let outermost = self.cycles as f64 * 2.0_f64.powf(self.detune.abs() / 2400.0);
+                if outermost.round() == self.cycles as f64 {
+                    return Err(format!("{} cycles is too few for {} cents of detune to be heard", self.cycles, self.detune))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // whole samples per cycle - the played pitch is sample_rate / period
+    pub fn period(&self) -> usize {
+        ((self.sample_rate as f64 / self.frequency).round() as usize).max(2)
+    }
+
+    // the nearest note to the pitch actually rendered
+    pub fn root_note(&self) -> u8 {
+        let frequency = if self.waveform.is_noise() { self.frequency } else { self.sample_rate as f64 / self.period() as f64 };
+        frequency_to_note(frequency).round().clamp(0.0, 127.0) as u8
+    }
+
+    pub fn length(&self) -> usize {
+        match &self.envelope {
+            Some(envelope) => envelope.length(self.sample_rate).max(1),
+            None if self.waveform.is_noise() => (NOISE_LENGTH * self.sample_rate as f64) as usize,
+            None => self.period() * self.cycles.max(1),
+        }
+    }
+
+    pub fn render(&self) -> Vec<i16> {
+        let length = self.length();
+        let mut mix = match self.waveform {
+            Waveform::WhiteNoise => render_noise(white(), length, self.sample_rate),
+            Waveform::PinkNoise => render_noise(pink(), length, self.sample_rate),
+            Waveform::BrownNoise => render_noise(brown(), length, self.sample_rate),
+            _ => self.render_stack(length),
+        };
+
+        let peak = mix.iter().map(|value| value.abs()).fold(0.0, f64::max);
+        let gain = if peak > 0.0 { self.amplitude.clamp(0.0, 1.0) * FULL_SCALE / peak } else { 0.0 };
+
+        if let Some(envelope) = &self.envelope {
+            for (index, value) in mix.iter_mut().enumerate() {
+                *value *= envelope.level(index as f64 / self.sample_rate as f64);
+            }
+        }
+
+        mix.iter().map(|value| to_sample_word(value * gain)).collect()
+    }
+
+    // a one shot has no loop, otherwise the whole rendered length loops
+    pub fn sample_loop(&self) -> Option<SampleLoop> {
+        if self.envelope.is_some() {
+            return None
+        }
+
+        let length = self.length() as u32;
+        Some(SampleLoop { at: length, length, length_fraction: 0, dwell_time: sample_header::LOOP_DWELL_TIME_HOLD })
+    }
+
+    fn render_stack(&self, length: usize) -> Vec<f64> {
+        let voices = self.voices.max(1);
+        let period = self.period() as f64;
+        let mut mix = vec![0.0; length];
+
+        for voice in 0..voices {
+            // spread the voices evenly across the detune and snap each one to a whole number
+            // of cycles over the rendered length so the stack still loops cleanly
+            let spread = if voices > 1 { voice as f64 / (voices - 1) as f64 - 0.5 } else { 0.0 };
+            let ratio = 2.0_f64.powf(spread * self.detune / 1200.0);
+            let cycles = if self.envelope.is_some() {
+                length as f64 / period * ratio
+            }
+            else {
+                (length as f64 / period * ratio).round().max(1.0)
+            };
+            let cycles_per_sample = cycles / length as f64;
+            let harmonics = self.harmonics(cycles_per_sample * self.sample_rate as f64);
+
+            for (index, value) in mix.iter_mut().enumerate() {
+                let phase = (index as f64 * cycles_per_sample).fract();
+                *value += harmonics.iter().map(|(harmonic, amplitude, offset)| {
+                    amplitude * (2.0 * PI * (*harmonic as f64 * phase + offset)).sin()
+                }).sum::<f64>();
+            }
+        }
+
+        mix
+    }
+
+    // (harmonic number, amplitude, phase offset in cycles) for everything below Nyquist
+    fn harmonics(&self, frequency: f64) -> Vec<(usize, f64, f64)> {
+        let highest = ((self.sample_rate as f64 / 2.0) / frequency).floor().max(1.0) as usize;
+
+        match &self.waveform {
+            Waveform::Square => (1..=highest).step_by(2).map(|harmonic| (harmonic, 1.0 / harmonic as f64, 0.0)).collect(),
+            Waveform::Triangle => (1..=highest).step_by(2).enumerate().map(|(index, harmonic)| {
+                let sign = if index % 2 == 0 { 1.0 } else { -1.0 };
+                (harmonic, sign / (harmonic * harmonic) as f64, 0.0)
+            }).collect(),
+            Waveform::Saw => (1..=highest).map(|harmonic| (harmonic, 1.0 / harmonic as f64, 0.0)).collect(),
+            Waveform::Pulse => {
+                let width = self.pulse_width.clamp(0.01, 0.99);
+                // cosine terms of a pulse train centred on width / 2
+                (1..=highest).map(|harmonic| (harmonic, (PI * harmonic as f64 * width).sin() / harmonic as f64, 0.25 - harmonic as f64 * width / 2.0)).collect()
+            }
+            Waveform::Additive(amplitudes) => amplitudes.iter().enumerate()
+                .filter(|(index, amplitude)| *index < highest && **amplitude != 0.0)
+                .map(|(index, amplitude)| (index + 1, *amplitude, 0.0))
+                .collect(),
+            Waveform::SingleCycle(cycle) => cycle_harmonics(cycle, highest),
+            _ => vec![(1, 1.0, 0.0)],
+        }
+    }
+}
+
+// Picks one cycle out of a wavetable. Without a cycle length, anything that divides into
+// 2048 sample frames is treated as a wavetable and anything else as a single cycle.
+pub fn wavetable_frame(samples: &[f64], cycle_length: Option<usize>, frame: usize) -> Option<Vec<f64>> {
+    let cycle_length = match cycle_length {
+        Some(cycle_length) => cycle_length,
+        None if samples.len().is_multiple_of(WAVETABLE_CYCLE_LENGTH) => WAVETABLE_CYCLE_LENGTH,
+        None => samples.len(),
+    };
+
+    if cycle_length < 2 || samples.len() < cycle_length {
+        return None
+    }
+
+    let frame = frame.min(samples.len() / cycle_length - 1);
+    Some(samples[(frame * cycle_length)..((frame + 1) * cycle_length)].to_vec())
+}
+
+// the Fourier series of a single cycle, which resamples it to any period without aliasing - DC is dropped
+fn cycle_harmonics(cycle: &[f64], highest: usize) -> Vec<(usize, f64, f64)> {
+    let length = cycle.len() as f64;
+
+    (1..=highest.min(cycle.len() / 2)).filter_map(|harmonic| {
+        let (mut cosine, mut sine) = (0.0, 0.0);
+        for (index, value) in cycle.iter().enumerate() {
+            let angle = 2.0 * PI * harmonic as f64 * index as f64 / length;
+            cosine += value * angle.cos();
+            sine += value * angle.sin();
+        }
+
+        let (cosine, sine) = (2.0 * cosine / length, 2.0 * sine / length);
+        let amplitude = cosine.hypot(sine);
+
+        // amplitude * sin(angle + offset) == sine * sin(angle) + cosine * cos(angle)
+        if amplitude > 1e-9 { Some((harmonic, amplitude, cosine.atan2(sine) / (2.0 * PI))) } else { None }
+    }).collect()
+}
+
+pub fn note_to_frequency(note: f64) -> f64 {
+    440.0 * 2.0_f64.powf((note - 69.0) / 12.0)
+}
+
+pub fn frequency_to_note(frequency: f64) -> f64 {
+    69.0 + 12.0 * (frequency / 440.0).log2()
+}
+
+fn render_noise<X: AudioNode<Sample = f32>>(mut noise: An<X>, length: usize, sample_rate: u32) -> Vec<f64> {
+    noise.reset(Some(sample_rate as f64));
+    (0..length).map(|_| noise.get_mono() as f64).collect()
+}
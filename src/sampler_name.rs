@@ -0,0 +1,172 @@
+// Names on the sampler are 12 characters from SAMPLER_CHAR_MAP, sent as indexes into it.
+// A SamplerName only ever holds characters that are in the map, so it always converts to the same
+// bytes it was read from, apart from trailing spaces which are padding.
+
+use std::fmt;
+
+use crate::SAMPLER_CHAR_MAP;
+
+pub const SAMPLER_NAME_LENGTH: usize = 12;
+
+const SPACE: u8 = 10;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SamplerNameError {
+    InvalidCharacters(Vec<(usize, char)>), // position and character
+    InvalidBytes(Vec<(usize, u8)>), // position and byte
+    TooLong(usize),
+}
+
+impl fmt::Display for SamplerNameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SamplerNameError::InvalidCharacters(characters) => {
+                let characters: Vec<String> = characters.iter().map(|(position, character)| format!("'{}' at {}", character, position)).collect();
+                write!(f, "characters the sampler cannot show: {}", characters.join(", "))
+            }
+            SamplerNameError::InvalidBytes(bytes) => {
+                let bytes: Vec<String> = bytes.iter().map(|(position, byte)| format!("{} at {}", byte, position)).collect();
+                write!(f, "bytes outside the sampler character map: {}", bytes.join(", "))
+            }
+            SamplerNameError::TooLong(length) => write!(f, "{} characters is more than the {} the sampler allows", length, SAMPLER_NAME_LENGTH),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SamplerNameWarning {
+    Transliterated(char, char),
+    Replaced(char), // nothing close in the map so it became a space
+    Truncated(String), // what was cut off
+}
+
+impl fmt::Display for SamplerNameWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SamplerNameWarning::Transliterated(from, to) => write!(f, "'{}' was changed to '{}'", from, to),
+            SamplerNameWarning::Replaced(character) => write!(f, "'{}' was replaced with a space", character),
+            SamplerNameWarning::Truncated(removed) => write!(f, "\"{}\" was cut off the end", removed),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SamplerName(String);
+
+impl SamplerName {
+    // only names the sampler can hold exactly are accepted
+    pub fn new(name: &str) -> Result<Self, SamplerNameError> {
+        let invalid = invalid_characters(name);
+        if !invalid.is_empty() {
+            return Err(SamplerNameError::InvalidCharacters(invalid))
+        }
+
+        let length = name.chars().count();
+        if length > SAMPLER_NAME_LENGTH {
+            return Err(SamplerNameError::TooLong(length))
+        }
+
+        Ok(Self(name.trim_end().to_string()))
+    }
+
+    // transliterates what it can, replaces what it cannot and truncates to 12 characters
+    pub fn lossy(name: &str) -> (Self, Vec<SamplerNameWarning>) {
+        let mut warnings = vec![];
+        let mut characters = vec![];
+
+        for character in name.chars() {
+            match transliterate_character(character) {
+                // a change of case is not worth a warning
+                Some(transliterated) if transliterated == character.to_ascii_uppercase() => characters.push(transliterated),
+                Some(transliterated) => {
+                    warnings.push(SamplerNameWarning::Transliterated(character, transliterated));
+                    characters.push(transliterated);
+                }
+                None => {
+                    warnings.push(SamplerNameWarning::Replaced(character));
+                    characters.push(' ');
+                }
+            }
+        }
+
+        if characters.len() > SAMPLER_NAME_LENGTH {
+            let removed: String = characters.drain(SAMPLER_NAME_LENGTH..).collect();
+            warnings.push(SamplerNameWarning::Truncated(removed));
+        }
+
+        (Self(characters.iter().collect::<String>().trim_end().to_string()), warnings)
+    }
+
+    pub fn from_sysex(data: &[u8]) -> Result<Self, SamplerNameError> {
+        let invalid: Vec<(usize, u8)> = data.iter().take(SAMPLER_NAME_LENGTH).enumerate()
+            .filter(|(_, byte)| (**byte as usize) >= SAMPLER_CHAR_MAP.len())
+            .map(|(position, byte)| (position, *byte))
+            .collect();
+        if !invalid.is_empty() {
+            return Err(SamplerNameError::InvalidBytes(invalid))
+        }
+
+        let name: String = data.iter().take(SAMPLER_NAME_LENGTH).map(|byte| SAMPLER_CHAR_MAP[*byte as usize]).collect();
+        Ok(Self(name.trim_end().to_string()))
+    }
+
+    // always 12 bytes, padded with spaces
+    pub fn to_sysex(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = self.0.chars()
+            .map(|character| SAMPLER_CHAR_MAP.iter().position(|sampler_character| *sampler_character == character).unwrap_or(SPACE as usize) as u8)
+            .collect();
+        data.resize(SAMPLER_NAME_LENGTH, SPACE);
+
+        data
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl fmt::Display for SamplerName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+pub fn invalid_characters(name: &str) -> Vec<(usize, char)> {
+    name.chars().enumerate().filter(|(_, character)| !SAMPLER_CHAR_MAP.contains(character)).collect()
+}
+
+// lower case to upper case, '_' to ' ' and accented letters to the plain letter
+pub fn transliterate(name: &str) -> String {
+    name.chars().map(|character| transliterate_character(character).unwrap_or(character)).collect()
+}
+
+fn transliterate_character(character: char) -> Option<char> {
+    if SAMPLER_CHAR_MAP.contains(&character) {
+        return Some(character)
+    }
+    if character == '_' {
+        return Some(' ')
+    }
+
+    let upper_case = character.to_uppercase().next().unwrap_or(character);
+    let plain = match upper_case {
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' | 'Ā' | 'Ă' | 'Ą' => 'A',
+        'Ç' | 'Ć' | 'Č' => 'C',
+        'Ď' | 'Đ' => 'D',
+        'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ė' | 'Ę' | 'Ě' => 'E',
+        'Ğ' => 'G',
+        'Ì' | 'Í' | 'Î' | 'Ï' | 'Ī' | 'Į' | 'İ' => 'I',
+        'Ł' | 'Ľ' | 'Ĺ' => 'L',
+        'Ñ' | 'Ń' | 'Ň' => 'N',
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' | 'Ō' | 'Ő' => 'O',
+        'Ř' | 'Ŕ' => 'R',
+        'Ś' | 'Š' | 'Ş' => 'S',
+        'Ť' | 'Ţ' => 'T',
+        'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ū' | 'Ů' | 'Ű' | 'Ų' => 'U',
+        'Ý' | 'Ÿ' => 'Y',
+        'Ź' | 'Ż' | 'Ž' => 'Z',
+        other => other,
+    };
+
+    if SAMPLER_CHAR_MAP.contains(&plain) { Some(plain) } else { None }
+}
@@ -0,0 +1,39 @@
+// Akai stereo samples are a pair of mono samples named "<base>-L" and "<base>-R".
+
+pub const LEFT_SUFFIX: &str = "-L";
+pub const RIGHT_SUFFIX: &str = "-R";
+
+const MAXIMUM_BASE_NAME_LENGTH: usize = 10; // leaves room for the suffix in a 12 character name
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+// the base is shortened if needed so both names stay within 12 characters
+pub fn pair_names(base: &str) -> (String, String) {
+    let base: String = base.trim_end().chars().take(MAXIMUM_BASE_NAME_LENGTH).collect();
+    let base = base.trim_end();
+
+    (format!("{}{}", base, LEFT_SUFFIX), format!("{}{}", base, RIGHT_SUFFIX))
+}
+
+pub fn split_name(name: &str) -> Option<(&str, Side)> {
+    let name = name.trim_end();
+
+    if let Some(base) = name.strip_suffix(LEFT_SUFFIX) {
+        Some((base, Side::Left))
+    }
+    else {
+        name.strip_suffix(RIGHT_SUFFIX).map(|base| (base, Side::Right))
+    }
+}
+
+// the positions of the left and right halves of a pair that includes the named sample
+pub fn find_pair(names: &[String], name: &str) -> Option<(usize, usize)> {
+    let (base, _) = split_name(name)?;
+    let position = |suffix: &str| names.iter().position(|candidate| candidate.trim_end() == format!("{}{}", base, suffix));
+
+    Some((position(LEFT_SUFFIX)?, position(RIGHT_SUFFIX)?))
+}
@@ -0,0 +1,187 @@
+// Typed view of the S3000's take list - a header (selector 0 of RequestTakeList/ResponseTakeList) and
+// the takes (selector 1), each a named section of a sample. Both are unnibbled bytes; anything not
+// modelled here is kept so it goes back to the sampler untouched.
+
+use std::fmt;
+
+use crate::sampler_name::SamplerName;
+
+pub const HEADER_SELECTOR: u8 = 0;
+pub const TAKE_SELECTOR: u8 = 1;
+
+pub const HEADER_SIZE: usize = 18;
+pub const HEADER_NAME_OFFSET: usize = 3;
+pub const HEADER_NUMBER_OF_TAKES_OFFSET: usize = 16; // u16 little endian
+
+pub const TAKE_SIZE: usize = 32;
+pub const MAXIMUM_NUMBER_OF_TAKES: usize = 200;
+
+const TAKE_NAME_OFFSET: usize = 0;
+const TAKE_SAMPLE_OFFSET: usize = 12; // u16 little endian
+const TAKE_START_OFFSET: usize = 14; // u32 little endian, in sample words
+const TAKE_END_OFFSET: usize = 18;
+const TAKE_LEVEL_OFFSET: usize = 22;
+const TAKE_PAN_OFFSET: usize = 23; // -50 to 50
+
+fn take_name(data: &[u8]) -> String {
+    crate::convert_sampler_sysex_name_to_name(&data[TAKE_NAME_OFFSET..(TAKE_NAME_OFFSET + 12)].to_vec()).trim_end().to_string()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Take {
+    pub sample: u16,
+    pub start: u32,
+    pub end: u32,
+    pub level: u8,
+    pub pan: i8,
+    data: Vec<u8>, // the bytes read, so unmodelled ones are written back as they were
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TakeListError {
+    WrongSize(usize),
+    EndBeforeStart(u32, u32),
+    OutOfRange(&'static str, i64, i64, i64), // field, value, minimum, maximum
+    UnknownFormat(String),
+}
+
+impl fmt::Display for TakeListError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TakeListError::WrongSize(size) => write!(f, "{} bytes is the wrong size for a take list header or take", size),
+            TakeListError::EndBeforeStart(start, end) => write!(f, "a take cannot end at {} before it starts at {}", end, start),
+            TakeListError::OutOfRange(field, value, minimum, maximum) => write!(f, "{} must be from {} to {} but was {}", field, minimum, maximum, value),
+            TakeListError::UnknownFormat(format) => write!(f, "{} is not csv or json", format),
+        }
+    }
+}
+
+impl Take {
+    pub fn from_bytes(data: &[u8]) -> Result<Self, TakeListError> {
+        if data.len() != TAKE_SIZE {
+            return Err(TakeListError::WrongSize(data.len()))
+        }
+
+        Ok(Self {
+            sample: u16::from_le_bytes([data[TAKE_SAMPLE_OFFSET], data[TAKE_SAMPLE_OFFSET + 1]]),
+            start: read_u32(data, TAKE_START_OFFSET),
+            end: read_u32(data, TAKE_END_OFFSET),
+            level: data[TAKE_LEVEL_OFFSET],
+            pan: data[TAKE_PAN_OFFSET] as i8,
+            data: data.to_vec(),
+        })
+    }
+
+    pub fn name(&self) -> String {
+        take_name(&self.data)
+    }
+
+    // only a checked name replaces the bytes read, which may hold some outside the character map
+    pub fn set_name(&mut self, name: &SamplerName) {
+        self.data[TAKE_NAME_OFFSET..(TAKE_NAME_OFFSET + 12)].copy_from_slice(&name.to_sysex());
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = self.data.clone();
+        data[TAKE_SAMPLE_OFFSET..(TAKE_SAMPLE_OFFSET + 2)].copy_from_slice(&self.sample.to_le_bytes());
+        data[TAKE_START_OFFSET..(TAKE_START_OFFSET + 4)].copy_from_slice(&self.start.to_le_bytes());
+        data[TAKE_END_OFFSET..(TAKE_END_OFFSET + 4)].copy_from_slice(&self.end.to_le_bytes());
+        data[TAKE_LEVEL_OFFSET] = self.level;
+        data[TAKE_PAN_OFFSET] = self.pan as u8;
+
+        data
+    }
+
+    pub fn validate(&self) -> Result<(), TakeListError> {
+        if self.end < self.start {
+            return Err(TakeListError::EndBeforeStart(self.start, self.end))
+        }
+        if self.level > 99 {
+            return Err(TakeListError::OutOfRange("level", self.level as i64, 0, 99))
+        }
+        if !(-50..=50).contains(&self.pan) {
+            return Err(TakeListError::OutOfRange("pan", self.pan as i64, -50, 50))
+        }
+
+        Ok(())
+    }
+
+    pub fn length(&self) -> u32 {
+        self.end.saturating_sub(self.start)
+    }
+}
+
+pub fn take_list_name(header: &[u8]) -> Result<String, TakeListError> {
+    if header.len() != HEADER_SIZE {
+        return Err(TakeListError::WrongSize(header.len()))
+    }
+
+    Ok(crate::convert_sampler_sysex_name_to_name(&header[HEADER_NAME_OFFSET..(HEADER_NAME_OFFSET + 12)].to_vec()).trim_end().to_string())
+}
+
+pub fn number_of_takes_in_header(header: &[u8]) -> usize {
+    (header[HEADER_NUMBER_OF_TAKES_OFFSET] as usize | ((header[HEADER_NUMBER_OF_TAKES_OFFSET + 1] as usize) << 8)).min(MAXIMUM_NUMBER_OF_TAKES)
+}
+
+// An edit decision list of the takes in order - positions are in sample words, and in seconds too when
+// the sample rate is known.
+pub fn edit_decision_list(takes: &[Take], sample_rate: Option<u32>, format: &str) -> Result<String, TakeListError> {
+    let seconds = |position: u32| sample_rate.filter(|rate| *rate > 0).map(|rate| position as f64 / rate as f64);
+
+    match format {
+        "csv" => {
+            let mut csv = String::from("take,name,sample,start,end,length,level,pan,start_seconds,end_seconds\n");
+            for (index, take) in takes.iter().enumerate() {
+                let (start_seconds, end_seconds) = match (seconds(take.start), seconds(take.end)) {
+                    (Some(start), Some(end)) => (format!("{:.6}", start), format!("{:.6}", end)),
+                    _ => (String::new(), String::new()),
+                };
+                csv.push_str(format!("{},{},{},{},{},{},{},{},{},{}\n", index, csv_field(take.name().as_str()), take.sample, take.start, take.end, take.length(), take.level, take.pan, start_seconds, end_seconds).as_str());
+            }
+
+            Ok(csv)
+        }
+        "json" => {
+            let entries: Vec<String> = takes.iter().enumerate().map(|(index, take)| {
+                let mut entry = format!("{{\"take\":{},\"name\":{},\"sample\":{},\"start\":{},\"end\":{},\"length\":{},\"level\":{},\"pan\":{}",
+                    index, json_string(take.name().as_str()), take.sample, take.start, take.end, take.length(), take.level, take.pan);
+                if let (Some(start), Some(end)) = (seconds(take.start), seconds(take.end)) {
+                    entry.push_str(format!(",\"start_seconds\":{:.6},\"end_seconds\":{:.6}", start, end).as_str());
+                }
+                entry.push('}');
+                entry
+            }).collect();
+
+            Ok(format!("[{}]\n", entries.join(",")))
+        }
+        _ => Err(TakeListError::UnknownFormat(format.to_string())),
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(',') || value.contains('"') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    }
+    else {
+        value.to_string()
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for letter in value.chars() {
+        match letter {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            letter if (letter as u32) < 0x20 => escaped.push_str(format!("\\u{:04x}", letter as u32).as_str()),
+            letter => escaped.push(letter),
+        }
+    }
+    escaped.push('"');
+
+    escaped
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
@@ -0,0 +1,140 @@
+// Minimal RIFF/WAVE reading - PCM 8/16/24/32 bit and 32/64 bit float, any number of channels.
+// Files are always written as 16 bit PCM, which is what the sampler holds.
+
+use std::{fs, io};
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+#[derive(Clone, Debug)]
+pub struct WavFile {
+    pub sample_rate: u32,
+    pub channels: Vec<Vec<f64>>, // one entry per channel, samples between -1 and 1
+}
+
+impl WavFile {
+    pub fn read(path: &str) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+            return Err(invalid_data("not a RIFF/WAVE file"))
+        }
+
+        let mut format: Option<(u16, u16, u32, u16)> = None; // format, channels, sample rate, bits per sample
+        let mut position = 12;
+
+        while position + 8 <= data.len() {
+            let chunk_id = &data[position..(position + 4)];
+            let chunk_size = u32::from_le_bytes([data[position + 4], data[position + 5], data[position + 6], data[position + 7]]) as usize;
+            let chunk_start = position + 8;
+            let chunk_end = (chunk_start + chunk_size).min(data.len());
+            let chunk = &data[chunk_start..chunk_end];
+
+            if chunk_id == b"fmt " && chunk.len() >= 16 {
+                let mut format_tag = u16::from_le_bytes([chunk[0], chunk[1]]);
+                if format_tag == WAVE_FORMAT_EXTENSIBLE && chunk.len() >= 26 {
+                    // the real format is at the start of the sub format GUID
+                    format_tag = u16::from_le_bytes([chunk[24], chunk[25]]);
+                }
+
+                format = Some((
+                    format_tag,
+                    u16::from_le_bytes([chunk[2], chunk[3]]),
+                    u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]),
+                    u16::from_le_bytes([chunk[14], chunk[15]]),
+                ));
+            }
+            else if chunk_id == b"data" {
+                let (format_tag, number_of_channels, sample_rate, bits_per_sample) = format.ok_or_else(|| invalid_data("data chunk before fmt chunk"))?;
+                let channels = decode_samples(chunk, format_tag, number_of_channels as usize, bits_per_sample)?;
+
+                return Ok(Self { sample_rate, channels })
+            }
+
+            // chunks are padded to an even length
+            position = chunk_start + chunk_size + (chunk_size & 1);
+        }
+
+        Err(invalid_data("no data chunk"))
+    }
+
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let number_of_channels = self.channels.len() as u16;
+        let block_align = number_of_channels * 2;
+        let data_size = (self.len() * block_align as usize) as u32;
+        let mut data = Vec::with_capacity(44 + data_size as usize);
+
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(36 + data_size).to_le_bytes());
+        data.extend_from_slice(b"WAVE");
+
+        data.extend_from_slice(b"fmt ");
+        data.extend_from_slice(&16_u32.to_le_bytes());
+        data.extend_from_slice(&WAVE_FORMAT_PCM.to_le_bytes());
+        data.extend_from_slice(&number_of_channels.to_le_bytes());
+        data.extend_from_slice(&self.sample_rate.to_le_bytes());
+        data.extend_from_slice(&(self.sample_rate * block_align as u32).to_le_bytes());
+        data.extend_from_slice(&block_align.to_le_bytes());
+        data.extend_from_slice(&16_u16.to_le_bytes());
+
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&data_size.to_le_bytes());
+        for index in 0..self.len() {
+            for channel in self.channels.iter() {
+                let sample = (channel[index] * 32768.0).round().clamp(-32768.0, 32767.0) as i16;
+                data.extend_from_slice(&sample.to_le_bytes());
+            }
+        }
+
+        data
+    }
+
+    pub fn len(&self) -> usize {
+        self.channels.first().map(|channel| channel.len()).unwrap_or(0)
+    }
+
+    pub fn mono(&self) -> Vec<f64> {
+        let number_of_channels = self.channels.len().max(1) as f64;
+
+        (0..self.len())
+            .map(|index| self.channels.iter().map(|channel| channel[index]).sum::<f64>() / number_of_channels)
+            .collect()
+    }
+}
+
+fn decode_samples(data: &[u8], format_tag: u16, number_of_channels: usize, bits_per_sample: u16) -> io::Result<Vec<Vec<f64>>> {
+    if number_of_channels == 0 {
+        return Err(invalid_data("no channels"))
+    }
+
+    let bytes_per_sample = (bits_per_sample as usize).div_ceil(8);
+    let decode: fn(&[u8]) -> f64 = match (format_tag, bits_per_sample) {
+        (WAVE_FORMAT_PCM, 8) => |bytes| (bytes[0] as f64 - 128.0) / 128.0,
+        (WAVE_FORMAT_PCM, 16) => |bytes| i16::from_le_bytes([bytes[0], bytes[1]]) as f64 / 32768.0,
+        (WAVE_FORMAT_PCM, 24) => |bytes| (i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) as f64 / 8_388_608.0,
+        (WAVE_FORMAT_PCM, 32) => |bytes| i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64 / 2_147_483_648.0,
+        (WAVE_FORMAT_IEEE_FLOAT, 32) => |bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+        (WAVE_FORMAT_IEEE_FLOAT, 64) => |bytes| f64::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]),
+        _ => return Err(invalid_data("unsupported sample format")),
+    };
+
+    let mut channels = vec![vec![]; number_of_channels];
+    for frame in data.chunks_exact(bytes_per_sample * number_of_channels) {
+        for (channel, bytes) in channels.iter_mut().zip(frame.chunks_exact(bytes_per_sample)) {
+            channel.push(decode(bytes));
+        }
+    }
+
+    Ok(channels)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
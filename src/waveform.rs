@@ -0,0 +1,155 @@
+// Min/max/RMS peak pyramids so the UI never has to hold a whole sample to draw it.
+
+use std::{collections::HashMap, sync::{Arc, Mutex}};
+
+use crate::sample_header::SampleHeader;
+
+pub const BASE_SAMPLES_PER_PEAK: usize = 64;
+pub const DEFAULT_MAXIMUM_NUMBER_OF_PEAKS: usize = 2048;
+
+#[derive(Clone, Debug)]
+pub struct PeakLevel {
+    pub samples_per_peak: usize,
+    pub min: Vec<i16>,
+    pub max: Vec<i16>,
+    pub rms: Vec<f32>,
+    counts: Vec<usize>, // samples behind each peak - the last one is usually short
+}
+
+impl PeakLevel {
+    pub fn len(&self) -> usize {
+        self.min.len()
+    }
+
+    fn from_samples(samples: &[i16], samples_per_peak: usize) -> Self {
+        let mut level = Self::with_capacity(samples_per_peak, samples.len() / samples_per_peak + 1);
+
+        for chunk in samples.chunks(samples_per_peak) {
+            let sum_of_squares: f64 = chunk.iter().map(|sample| (*sample as f64) * (*sample as f64)).sum();
+
+            level.min.push(*chunk.iter().min().unwrap_or(&0));
+            level.max.push(*chunk.iter().max().unwrap_or(&0));
+            level.rms.push((sum_of_squares / chunk.len() as f64).sqrt() as f32);
+            level.counts.push(chunk.len());
+        }
+
+        level
+    }
+
+    // each peak of the next level covers two peaks of this one
+    fn halve(&self) -> Self {
+        let mut level = Self::with_capacity(self.samples_per_peak * 2, self.len() / 2 + 1);
+
+        for index in (0..self.len()).step_by(2) {
+            let pair = index..(index + 2).min(self.len());
+            let count: usize = self.counts[pair.clone()].iter().sum();
+            let sum_of_squares: f64 = pair.clone()
+                .map(|peak| (self.rms[peak] as f64).powi(2) * self.counts[peak] as f64)
+                .sum();
+
+            level.min.push(*self.min[pair.clone()].iter().min().unwrap_or(&0));
+            level.max.push(*self.max[pair.clone()].iter().max().unwrap_or(&0));
+            level.rms.push((sum_of_squares / count.max(1) as f64).sqrt() as f32);
+            level.counts.push(count);
+        }
+
+        level
+    }
+
+    fn with_capacity(samples_per_peak: usize, capacity: usize) -> Self {
+        Self {
+            samples_per_peak,
+            min: Vec::with_capacity(capacity),
+            max: Vec::with_capacity(capacity),
+            rms: Vec::with_capacity(capacity),
+            counts: Vec::with_capacity(capacity),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LoopOverlay {
+    pub start: u32,
+    pub end: u32,
+    pub dwell_time: u16,
+}
+
+#[derive(Clone, Debug)]
+pub struct WaveformOverview {
+    pub sample_length: u32,
+    pub sample_rate: u16,
+    pub levels: Vec<PeakLevel>,
+    pub loops: Vec<LoopOverlay>,
+}
+
+impl WaveformOverview {
+    pub fn new(header: &SampleHeader, samples: &[i16]) -> Self {
+        let mut levels = vec![PeakLevel::from_samples(samples, BASE_SAMPLES_PER_PEAK)];
+
+        while let Some(level) = levels.last() {
+            if level.len() <= 1 {
+                break
+            }
+            let next_level = level.halve();
+            levels.push(next_level);
+        }
+
+        let loops = header.used_loops()
+            .map(|sample_loop| LoopOverlay { start: sample_loop.start(), end: sample_loop.at, dwell_time: sample_loop.dwell_time })
+            .collect();
+
+        Self {
+            sample_length: header.sample_length,
+            sample_rate: header.sample_rate,
+            levels,
+            loops,
+        }
+    }
+
+    // the most detailed level that still fits into the number of peaks asked for
+    pub fn level_for_width(&self, maximum_number_of_peaks: usize) -> usize {
+        self.levels.iter()
+            .position(|level| level.len() <= maximum_number_of_peaks.max(1))
+            .unwrap_or(self.levels.len() - 1)
+    }
+}
+
+lazy_static! {
+    static ref WAVEFORM_CACHE: Mutex<HashMap<String, (u32, Arc<WaveformOverview>)>> = Mutex::new(HashMap::new());
+}
+
+pub fn cached_overview(header: &SampleHeader) -> Option<Arc<WaveformOverview>> {
+    if let Ok(cache) = WAVEFORM_CACHE.lock() {
+        if let Some((checksum, overview)) = cache.get(&header.name) {
+            if *checksum == header.checksum {
+                return Some(overview.clone())
+            }
+        }
+    }
+
+    None
+}
+
+// only the latest version of a sample is kept - an edited header replaces the old entry
+pub fn cache_overview(header: &SampleHeader, overview: WaveformOverview) -> Arc<WaveformOverview> {
+    let overview = Arc::new(overview);
+
+    if let Ok(mut cache) = WAVEFORM_CACHE.lock() {
+        cache.insert(header.name.clone(), (header.checksum, overview.clone()));
+    }
+
+    overview
+}
+
+// the audio of a sample can change without its header - after an upload or a delete
+pub fn forget_overview(name: &str) {
+    if let Ok(mut cache) = WAVEFORM_CACHE.lock() {
+        cache.retain(|cached_name, _| cached_name.trim_end() != name.trim_end());
+    }
+}
+
+pub fn clear_cache() {
+    if let Ok(mut cache) = WAVEFORM_CACHE.lock() {
+        cache.clear();
+    }
+}